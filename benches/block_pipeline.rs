@@ -0,0 +1,70 @@
+//! Backfill throughput against a local anvil chain
+//!
+//! Seeds anvil with many blocks full of transfers, then backfills the whole range
+//! over HTTP with different pipeline depths. Depth 1 is the old one-block-at-a-time
+//! behaviour.
+//!
+//! Run with `cargo bench --bench block_pipeline` (requires `anvil` on PATH).
+use std::time::Instant;
+
+use anyhow::Result;
+use ethers::{
+    providers::{Http, Middleware, Provider},
+    types::TransactionRequest,
+    utils::Anvil,
+};
+
+use rust_marathon::config::ScannerConfig;
+use rust_marathon::scanner::MevScanner;
+
+const BLOCKS: u64 = 200;
+const TXS_PER_BLOCK: usize = 20;
+const PIPELINE_DEPTHS: [usize; 4] = [1, 2, 5, 10];
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Mainnet's chain id, so the default ethereum profile accepts the node
+    let anvil = Anvil::new().arg("--silent").arg("--no-mining").chain_id(1u64).spawn();
+    let provider = Provider::<Http>::try_from(anvil.endpoint())?;
+
+    println!("Seeding {} blocks with {} transfers each...", BLOCKS, TXS_PER_BLOCK);
+    let accounts = anvil.addresses();
+    for _ in 0..BLOCKS {
+        for i in 0..TXS_PER_BLOCK {
+            let tx = TransactionRequest::new()
+                .from(accounts[i % accounts.len()])
+                .to(accounts[(i + 1) % accounts.len()])
+                .value(1_000u64);
+            provider.send_transaction(tx, None).await?;
+        }
+        provider.request::<_, String>("evm_mine", ()).await?;
+    }
+
+    // Unreachable WS endpoint forces receipts over HTTP, like a real backfill
+    unsafe {
+        std::env::set_var("WS_URL", "ws://127.0.0.1:1");
+        std::env::set_var("HTTP_URL", anvil.endpoint());
+        std::env::set_var("PRIVATE_KEY", "0xdeadbeef");
+    }
+
+    let mut baseline = None;
+    for depth in PIPELINE_DEPTHS {
+        unsafe {
+            std::env::set_var("BLOCK_PIPELINE_DEPTH", depth.to_string());
+        }
+        let scanner = MevScanner::new(ScannerConfig::from_env()?).await?;
+
+        let start = Instant::now();
+        let processed = scanner.backfill(1, BLOCKS).await;
+        let elapsed = start.elapsed();
+
+        let blocks_per_sec = processed as f64 / elapsed.as_secs_f64();
+        let speedup = blocks_per_sec / *baseline.get_or_insert(blocks_per_sec);
+        println!(
+            "depth {:>2}: {} blocks in {:>8.2?} ({:>8.1} blocks/s, {:.2}x)",
+            depth, processed, elapsed, blocks_per_sec, speedup
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,86 @@
+//! Throughput of the dedupe bloom filters
+//!
+//! Measures inserts and lookups on a single `BloomFilter` and on the rotating filter
+//! the scanner uses, single-threaded and from several threads at once.
+//!
+//! Run with `cargo bench --bench bloom_filter`.
+use std::{sync::Arc, thread, time::Instant};
+
+use ethers::types::H256;
+
+use rust_marathon::scanner::{BloomFilter, RotatingBloomFilter, tx_key};
+
+const ITEMS: usize = 1_000_000;
+const FALSE_POSITIVE_RATE: f64 = 1e-6;
+const THREADS: usize = 8;
+
+fn hashes(range: std::ops::Range<u64>) -> Vec<H256> {
+    range.map(|i| H256::from_low_u64_be(i.wrapping_mul(0x9E37_79B9_7F4A_7C15))).collect()
+}
+
+fn report(name: &str, operations: usize, start: Instant) {
+    let elapsed = start.elapsed();
+    println!(
+        "{:<32} {:>10.1} ns/op {:>12.0} ops/s",
+        name,
+        elapsed.as_nanos() as f64 / operations as f64,
+        operations as f64 / elapsed.as_secs_f64()
+    );
+}
+
+fn main() {
+    let inserted = hashes(0..ITEMS as u64);
+    let absent = hashes(ITEMS as u64..2 * ITEMS as u64);
+
+    let filter = BloomFilter::new(ITEMS, FALSE_POSITIVE_RATE);
+    println!("{} bits, {} hashes, {:.1} MiB",
+        filter.num_bits(), filter.num_hashes(), filter.num_bits() as f64 / 8.0 / 1024.0 / 1024.0);
+
+    let start = Instant::now();
+    for hash in &inserted {
+        filter.insert(&tx_key(*hash));
+    }
+    report("insert", ITEMS, start);
+
+    let start = Instant::now();
+    let hits = inserted.iter().filter(|hash| filter.contains(&tx_key(**hash))).count();
+    report("contains (present)", ITEMS, start);
+    assert_eq!(hits, ITEMS);
+
+    let start = Instant::now();
+    let false_positives = absent.iter().filter(|hash| filter.contains(&tx_key(**hash))).count();
+    report("contains (absent)", ITEMS, start);
+    println!("observed false-positive rate: {:.2e}", false_positives as f64 / ITEMS as f64);
+
+    let rotating = Arc::new(RotatingBloomFilter::new(ITEMS / 4, FALSE_POSITIVE_RATE, 3));
+    let start = Instant::now();
+    for hash in &inserted {
+        rotating.mark_tx(*hash);
+    }
+    report("rotating mark_tx", ITEMS, start);
+
+    let start = Instant::now();
+    for hash in &absent {
+        rotating.seen_tx(*hash);
+    }
+    report("rotating seen_tx", ITEMS, start);
+
+    let shared = Arc::new(BloomFilter::new(ITEMS, FALSE_POSITIVE_RATE));
+    let per_thread = ITEMS / THREADS;
+    let start = Instant::now();
+    let workers: Vec<_> = (0..THREADS)
+        .map(|t| {
+            let shared = shared.clone();
+            let chunk = inserted[t * per_thread..(t + 1) * per_thread].to_vec();
+            thread::spawn(move || {
+                for hash in chunk {
+                    shared.insert(&tx_key(hash));
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    report(&format!("insert ({} threads)", THREADS), per_thread * THREADS, start);
+}
@@ -0,0 +1,367 @@
+//! Token, factory, router and pool addresses per chain
+//!
+//! Addresses used to live in `const_and_addr` as strings parsed on every call. They now
+//! come from an address book file (TOML or JSON) that is validated once at load time:
+//! every address must be EIP-55 checksummed, and pools, top pairs and the wrapped native
+//! token must refer to tokens and factories defined in the same chain section. A default
+//! book covering the built-in chain profiles is embedded in the binary.
+use std::{collections::{BTreeMap, HashSet}, path::Path, str::FromStr};
+use anyhow::{Context, Result, anyhow};
+use ethers::{types::Address, utils::to_checksum};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+const EMBEDDED_ADDRESS_BOOK: &str = include_str!("../address_book.toml");
+
+static EMBEDDED: Lazy<AddressBook> = Lazy::new(|| {
+    AddressBook::from_toml(EMBEDDED_ADDRESS_BOOK).expect("embedded address book is valid")
+});
+
+/// AMM design of a factory, router or pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DexKind {
+    UniswapV2,
+    UniswapV3,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TokenInfo {
+    pub symbol: String,
+    pub address: Address,
+    pub decimals: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FactoryInfo {
+    pub name: String,
+    pub kind: DexKind,
+    pub address: Address,
+    /// Swap fee of every V2 pair, V3 pools carry their own fee tier
+    pub fee_bps: Option<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RouterInfo {
+    pub name: String,
+    pub kind: DexKind,
+    pub address: Address,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PoolInfo {
+    pub address: Address,
+    /// Name of the factory that deployed the pool
+    pub factory: String,
+    pub kind: DexKind,
+    pub token0: Address,
+    pub token1: Address,
+    /// V3 fee tier in hundredths of a basis point
+    pub fee: Option<u32>,
+}
+
+/// Addresses of one chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChainAddresses {
+    pub chain_id: u64,
+    wrapped_native: Address,
+    tokens: Vec<TokenInfo>,
+    factories: Vec<FactoryInfo>,
+    routers: Vec<RouterInfo>,
+    pools: Vec<PoolInfo>,
+    top_pairs: Vec<(Address, Address)>,
+}
+
+impl ChainAddresses {
+    pub fn wrapped_native(&self) -> Address {
+        self.wrapped_native
+    }
+
+    pub fn tokens(&self) -> &[TokenInfo] {
+        &self.tokens
+    }
+
+    pub fn factories(&self) -> &[FactoryInfo] {
+        &self.factories
+    }
+
+    pub fn routers(&self) -> &[RouterInfo] {
+        &self.routers
+    }
+
+    pub fn pools(&self) -> &[PoolInfo] {
+        &self.pools
+    }
+
+    /// Most liquid token pairs, the default set of pairs to discover pools for
+    pub fn top_pairs(&self) -> &[(Address, Address)] {
+        &self.top_pairs
+    }
+
+    /// Case-insensitive symbol lookup
+    pub fn token(&self, symbol: &str) -> Option<&TokenInfo> {
+        self.tokens.iter().find(|t| t.symbol.eq_ignore_ascii_case(symbol))
+    }
+
+    pub fn token_by_address(&self, address: Address) -> Option<&TokenInfo> {
+        self.tokens.iter().find(|t| t.address == address)
+    }
+
+    pub fn factory(&self, name: &str) -> Option<&FactoryInfo> {
+        self.factories.iter().find(|f| f.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn factory_by_address(&self, address: Address) -> Option<&FactoryInfo> {
+        self.factories.iter().find(|f| f.address == address)
+    }
+
+    pub fn router(&self, name: &str) -> Option<&RouterInfo> {
+        self.routers.iter().find(|r| r.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn router_by_address(&self, address: Address) -> Option<&RouterInfo> {
+        self.routers.iter().find(|r| r.address == address)
+    }
+
+    pub fn pool(&self, address: Address) -> Option<&PoolInfo> {
+        self.pools.iter().find(|p| p.address == address)
+    }
+
+    pub fn factory_addresses(&self) -> Vec<Address> {
+        self.factories.iter().map(|f| f.address).collect()
+    }
+}
+
+/// Addresses of every known chain, keyed by chain profile name
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AddressBook {
+    chains: BTreeMap<String, ChainAddresses>,
+}
+
+impl AddressBook {
+    /// The default book compiled into the binary
+    pub fn embedded() -> &'static AddressBook {
+        &EMBEDDED
+    }
+
+    /// Loads a `.json` file as JSON and anything else as TOML
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read address book {}", path.display()))?;
+        let book = if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json")) {
+            Self::from_json(&contents)
+        } else {
+            Self::from_toml(&contents)
+        };
+        book.with_context(|| format!("Invalid address book {}", path.display()))
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Self> {
+        Self::from_raw(toml::from_str(contents)?)
+    }
+
+    pub fn from_json(contents: &str) -> Result<Self> {
+        Self::from_raw(serde_json::from_str(contents)?)
+    }
+
+    fn from_raw(raw: BTreeMap<String, RawChain>) -> Result<Self> {
+        let chains = raw
+            .into_iter()
+            .map(|(name, chain)| {
+                let addresses = chain.validate(&name)?;
+                Ok((name.to_ascii_lowercase(), addresses))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { chains })
+    }
+
+    pub fn chain(&self, name: &str) -> Option<&ChainAddresses> {
+        self.chains.get(&name.to_ascii_lowercase())
+    }
+
+    pub fn chain_by_id(&self, chain_id: u64) -> Option<&ChainAddresses> {
+        self.chains.values().find(|c| c.chain_id == chain_id)
+    }
+
+    pub fn chain_names(&self) -> impl Iterator<Item = &str> {
+        self.chains.keys().map(String::as_str)
+    }
+}
+
+/// Parses an address and rejects it unless it is in EIP-55 checksum form
+fn checksummed(value: &str, what: &str) -> Result<Address> {
+    let address = Address::from_str(value).map_err(|e| anyhow!("{}: invalid address '{}': {}", what, value, e))?;
+    let expected = to_checksum(&address, None);
+    if value != expected {
+        return Err(anyhow!("{}: '{}' fails the EIP-55 checksum, expected {}", what, value, expected));
+    }
+    Ok(address)
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawChain {
+    chain_id: u64,
+    wrapped_native: String,
+    #[serde(default)]
+    top_pairs: Vec<[String; 2]>,
+    #[serde(default)]
+    tokens: Vec<RawToken>,
+    #[serde(default)]
+    factories: Vec<RawFactory>,
+    #[serde(default)]
+    routers: Vec<RawRouter>,
+    #[serde(default)]
+    pools: Vec<RawPool>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawToken {
+    symbol: String,
+    address: String,
+    decimals: u8,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawFactory {
+    name: String,
+    kind: DexKind,
+    address: String,
+    fee_bps: Option<u16>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRouter {
+    name: String,
+    kind: DexKind,
+    address: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPool {
+    address: String,
+    factory: String,
+    token0: String,
+    token1: String,
+    fee: Option<u32>,
+}
+
+impl RawChain {
+    /// Checks checksums and cross references, naming the offending entry on failure
+    fn validate(self, chain: &str) -> Result<ChainAddresses> {
+        let mut symbols = HashSet::new();
+        let tokens = self.tokens
+            .into_iter()
+            .map(|t| {
+                if !symbols.insert(t.symbol.to_ascii_uppercase()) {
+                    return Err(anyhow!("{}.tokens: duplicate symbol {}", chain, t.symbol));
+                }
+                let address = checksummed(&t.address, &format!("{}.tokens[{}]", chain, t.symbol))?;
+                Ok(TokenInfo { symbol: t.symbol, address, decimals: t.decimals })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let token = |symbol: &str, what: String| -> Result<Address> {
+            tokens.iter()
+                .find(|t| t.symbol.eq_ignore_ascii_case(symbol))
+                .map(|t| t.address)
+                .ok_or_else(|| anyhow!("{}: unknown token '{}'", what, symbol))
+        };
+
+        let factories = self.factories
+            .into_iter()
+            .map(|f| {
+                let address = checksummed(&f.address, &format!("{}.factories[{}]", chain, f.name))?;
+                Ok(FactoryInfo { name: f.name, kind: f.kind, address, fee_bps: f.fee_bps })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let routers = self.routers
+            .into_iter()
+            .map(|r| {
+                let address = checksummed(&r.address, &format!("{}.routers[{}]", chain, r.name))?;
+                Ok(RouterInfo { name: r.name, kind: r.kind, address })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let pools = self.pools
+            .into_iter()
+            .map(|p| {
+                let what = format!("{}.pools[{}]", chain, p.address);
+                let address = checksummed(&p.address, &what)?;
+                let factory = factories.iter()
+                    .find(|f| f.name.eq_ignore_ascii_case(&p.factory))
+                    .ok_or_else(|| anyhow!("{}: unknown factory '{}'", what, p.factory))?;
+                Ok(PoolInfo {
+                    address,
+                    factory: factory.name.clone(),
+                    kind: factory.kind,
+                    token0: token(&p.token0, what.clone())?,
+                    token1: token(&p.token1, what)?,
+                    fee: p.fee,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let top_pairs = self.top_pairs
+            .iter()
+            .map(|[a, b]| {
+                let what = format!("{}.top_pairs", chain);
+                Ok((token(a, what.clone())?, token(b, what)?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(ChainAddresses {
+            chain_id: self.chain_id,
+            wrapped_native: token(&self.wrapped_native, format!("{}.wrapped_native", chain))?,
+            tokens,
+            factories,
+            routers,
+            pools,
+            top_pairs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedded_book_is_valid_and_queryable() {
+        let ethereum = AddressBook::embedded().chain("ethereum").unwrap();
+        let usdc = ethereum.token("usdc").unwrap();
+        assert_eq!(usdc.address, Address::from_str("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48").unwrap());
+        assert_eq!(usdc.decimals, 6);
+        assert_eq!(ethereum.token_by_address(usdc.address).map(|t| t.symbol.as_str()), Some("USDC"));
+        assert_eq!(ethereum.factory("uniswap_v3").unwrap().kind, DexKind::UniswapV3);
+
+        let pool = ethereum.pool(Address::from_str("0xB4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc").unwrap()).unwrap();
+        assert_eq!((pool.token0, pool.token1), (usdc.address, ethereum.wrapped_native()));
+        assert_eq!(AddressBook::embedded().chain_by_id(137).unwrap().token("WMATIC").unwrap().decimals, 18);
+    }
+
+    #[test]
+    fn test_load_rejects_bad_checksums_and_dangling_references() {
+        let book = |token: &str, pair: &str| format!(
+            "[test]\nchain_id = 1\nwrapped_native = \"WETH\"\ntop_pairs = [[\"WETH\", \"{}\"]]\n\
+             [[test.tokens]]\nsymbol = \"WETH\"\naddress = \"{}\"\ndecimals = 18\n",
+            pair, token
+        );
+        assert!(AddressBook::from_toml(&book("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2", "WETH")).is_ok());
+
+        let error = AddressBook::from_toml(&book("0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2", "WETH")).unwrap_err();
+        assert!(error.to_string().contains("test.tokens[WETH]"), "{}", error);
+        assert!(error.to_string().contains("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"), "{}", error);
+
+        let error = AddressBook::from_toml(&book("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2", "USDC")).unwrap_err();
+        assert!(error.to_string().contains("unknown token 'USDC'"), "{}", error);
+
+        let json = r#"{"test": {"chain_id": 1, "wrapped_native": "WETH",
+            "tokens": [{"symbol": "WETH", "address": "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2", "decimals": 18}]}}"#;
+        assert_eq!(AddressBook::from_json(json).unwrap().chain("TEST").unwrap().tokens().len(), 1);
+    }
+}
@@ -0,0 +1,9 @@
+// ! Arbitrage module - finds and sizes price discrepancies between watched pools
+
+mod detector;
+mod opportunity;
+mod token_graph;
+
+pub use detector::ArbitrageDetector;
+pub use opportunity::{ArbitrageOpportunity, SwapHop};
+pub use token_graph::{Cycle, Edge, TokenGraph};
@@ -0,0 +1,72 @@
+//! A sized, priced arbitrage cycle
+use chrono::{DateTime, Utc};
+use ethers::types::{Address, I256, U256};
+use serde::Serialize;
+
+use crate::{address_book::DexKind, pricing::u256_to_f64};
+
+/// One swap of a cycle, with its exact amounts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SwapHop {
+    pub pool: Address,
+    pub dex: DexKind,
+    pub token_in: Address,
+    pub token_out: Address,
+    pub amount_in: U256,
+    pub amount_out: U256,
+}
+
+/// Buy `intermediate_token` with `profit_token` on `buy_pool`, sell it back on `sell_pool`.
+/// Multi-hop cycles list every swap in `path`; the buy and sell fields then describe the
+/// first and last hop.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ArbitrageOpportunity {
+    pub block_number: u64,
+    pub profit_token: Address,
+    pub intermediate_token: Address,
+    pub buy_pool: Address,
+    pub buy_dex: DexKind,
+    pub sell_pool: Address,
+    pub sell_dex: DexKind,
+    pub path: Vec<SwapHop>,
+    pub amount_in: U256,
+    pub intermediate_amount: U256,
+    pub amount_out: U256,
+    /// `amount_out` after the configured slippage tolerance, for the swap's minimum output.
+    /// Never below `amount_in + gas_cost`.
+    pub min_amount_out: U256,
+    /// `amount_out - amount_in`, before gas
+    pub profit: U256,
+    /// Gas the cycle is expected to use
+    pub gas_units: u64,
+    /// Cost of `gas_units` at the oracle's next-block price, in the profit token
+    pub gas_cost: U256,
+    /// `profit - gas_cost`
+    pub net_profit: U256,
+    /// Profit token balance change of the searcher when the bundle ran in simulation
+    pub simulated_profit: Option<I256>,
+    /// Gas the bundle used in simulation
+    pub simulated_gas: Option<u64>,
+    pub detected_at: DateTime<Utc>,
+}
+
+impl ArbitrageOpportunity {
+    pub fn hops(&self) -> usize {
+        self.path.len()
+    }
+
+    /// Profit relative to the input (0.01 = 1%)
+    pub fn profit_ratio(&self) -> f64 {
+        u256_to_f64(self.profit) / u256_to_f64(self.amount_in)
+    }
+
+    /// Profit in whole profit tokens
+    pub fn profit_in_units(&self, decimals: u8) -> f64 {
+        u256_to_f64(self.profit) / 10f64.powi(i32::from(decimals))
+    }
+
+    /// Profit after gas in whole profit tokens
+    pub fn net_profit_in_units(&self, decimals: u8) -> f64 {
+        u256_to_f64(self.net_profit) / 10f64.powi(i32::from(decimals))
+    }
+}
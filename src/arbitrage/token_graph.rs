@@ -0,0 +1,315 @@
+//! Token graph over the watched pools, searched for profitable cycles
+//!
+//! Every pool is an edge between its two tokens, weighted in each direction by the log
+//! of its spot rate after the LP fee. A cycle whose weights sum above zero (a negative
+//! cycle in `-ln` terms) returns more than it started with at the margin; the detector
+//! then sizes it with exact quotes. Edges are updated in place as pools change, from
+//! pool state or from the reserve deltas the drift detector derives, so the graph is
+//! never rebuilt per block.
+use std::collections::HashSet;
+use dashmap::DashMap;
+use ethers::types::Address;
+
+use crate::{
+    address_book::DexKind,
+    const_and_addr,
+    pools::{PoolReserves, PoolState},
+    pricing::{ConstantProductPool, u256_to_f64},
+    storage::StorageDelta,
+};
+
+/// 2^96 as f64, for converting sqrt prices
+const Q96: f64 = 79_228_162_514_264_337_593_543_950_336.0;
+
+/// One directed swap through a pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Edge {
+    pub pool: Address,
+    pub token_in: Address,
+    pub token_out: Address,
+}
+
+/// A closed path of swaps and the log of its marginal return after fees
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cycle {
+    pub edges: Vec<Edge>,
+    pub log_rate: f64,
+}
+
+#[derive(Debug, Clone)]
+struct PoolEdge {
+    token0: Address,
+    token1: Address,
+    /// Reserves of V2 pools, kept to apply reserve deltas to
+    v2: Option<ConstantProductPool>,
+    /// `ln` of the after-fee spot rate, token0 -> token1 and token1 -> token0
+    log_rates: (f64, f64),
+    /// LP fee as a fraction of the input
+    fee: f64,
+    block_number: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct TokenGraph {
+    edges: DashMap<Address, PoolEdge>,
+    /// Pools holding each token
+    adjacency: DashMap<Address, HashSet<Address>>,
+}
+
+impl TokenGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds or reprices a pool's edge. Pools without a usable price are dropped, and
+    /// state older than the edge's is ignored. Returns `false` if nothing changed.
+    pub fn update_pool(&self, pool: &PoolState) -> bool {
+        if self.edges.get(&pool.address).is_some_and(|edge| edge.block_number > pool.last_updated_block) {
+            return false;
+        }
+        let v2 = match pool.kind {
+            DexKind::UniswapV2 => ConstantProductPool::from_pool(pool),
+            _ => None,
+        };
+        let fee = f64::from(pool.fee) / 1_000_000.0;
+        let log_rates = match (&v2, &pool.reserves) {
+            (Some(v2), _) => v2_log_rates(v2),
+            (None, PoolReserves::V3 { sqrt_price_x96, .. }) => log_rates((u256_to_f64(*sqrt_price_x96) / Q96).powi(2), fee),
+            _ => None,
+        };
+        let Some(log_rates) = log_rates else {
+            return self.remove_pool(pool.address);
+        };
+
+        self.edges.insert(pool.address, PoolEdge {
+            token0: pool.token0,
+            token1: pool.token1,
+            v2,
+            log_rates,
+            fee,
+            block_number: pool.last_updated_block,
+        });
+        for token in [pool.token0, pool.token1] {
+            self.adjacency.entry(token).or_default().insert(pool.address);
+        }
+        true
+    }
+
+    /// Returns `false` if the pool was not in the graph
+    pub fn remove_pool(&self, address: Address) -> bool {
+        let Some((_, edge)) = self.edges.remove(&address) else {
+            return false;
+        };
+        for token in [edge.token0, edge.token1] {
+            if let Some(mut pools) = self.adjacency.get_mut(&token) {
+                pools.remove(&address);
+            }
+            self.adjacency.remove_if(&token, |_, pools| pools.is_empty());
+        }
+        true
+    }
+
+    /// Reprices V2 edges from reserve slot deltas, returns the pools that changed
+    pub fn apply_deltas(&self, deltas: &[StorageDelta]) -> HashSet<Address> {
+        let contracts: HashSet<Address> = deltas.iter().map(|delta| delta.contract).collect();
+        let mut updated = HashSet::new();
+        for contract in contracts {
+            let Some(mut edge) = self.edges.get_mut(&contract) else {
+                continue;
+            };
+            let Some(mut pool) = edge.v2 else {
+                continue;
+            };
+            if !pool.apply_deltas(contract, deltas) {
+                continue;
+            }
+            let Some(log_rates) = v2_log_rates(&pool) else {
+                continue;
+            };
+            let block_number = deltas.iter()
+                .filter(|delta| delta.contract == contract)
+                .map(|delta| delta.block_number)
+                .max()
+                .unwrap_or(edge.block_number);
+            edge.v2 = Some(pool);
+            edge.log_rates = log_rates;
+            edge.block_number = edge.block_number.max(block_number);
+            updated.insert(contract);
+        }
+        updated
+    }
+
+    pub fn contains(&self, pool: Address) -> bool {
+        self.edges.contains_key(&pool)
+    }
+
+    pub fn len(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty()
+    }
+
+    /// `ln` of the after-fee spot rate for selling `token_in` into `pool`
+    pub fn log_rate(&self, pool: Address, token_in: Address) -> Option<f64> {
+        let edge = self.edges.get(&pool)?;
+        if token_in == edge.token0 {
+            Some(edge.log_rates.0)
+        } else if token_in == edge.token1 {
+            Some(edge.log_rates.1)
+        } else {
+            None
+        }
+    }
+
+    /// Best spot rate before fees from `token_in` to `token_out` over the pools trading
+    /// both, in raw units; `None` without such a pool
+    pub fn spot_rate(&self, token_in: Address, token_out: Address) -> Option<f64> {
+        let pools = self.adjacency.get(&token_in)?.clone();
+        pools.into_iter()
+            .filter_map(|pool| {
+                let edge = self.edges.get(&pool)?;
+                let rate = if token_in == edge.token0 && token_out == edge.token1 {
+                    edge.log_rates.0
+                } else if token_in == edge.token1 && token_out == edge.token0 {
+                    edge.log_rates.1
+                } else {
+                    return None;
+                };
+                Some(rate.exp() / (1.0 - edge.fee))
+            })
+            .max_by(f64::total_cmp)
+    }
+
+    /// Profitable cycles from `start` back to it with 2 to `max_hops` swaps, best first.
+    /// No pool or intermediate token is visited twice. When `touching` is not empty,
+    /// only cycles through at least one of its pools are returned.
+    pub fn find_cycles(&self, start: Address, max_hops: usize, touching: &HashSet<Address>) -> Vec<Cycle> {
+        let mut cycles = Vec::new();
+        let mut path = Vec::new();
+        let mut visited = HashSet::from([start]);
+        self.extend_path(start, start, max_hops, 0.0, &mut path, &mut visited, touching, &mut cycles);
+
+        cycles.sort_by(|a, b| b.log_rate.total_cmp(&a.log_rate));
+        cycles.truncate(const_and_addr::MAX_CYCLE_CANDIDATES);
+        cycles
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn extend_path(
+        &self,
+        start: Address,
+        token: Address,
+        max_hops: usize,
+        log_rate: f64,
+        path: &mut Vec<Edge>,
+        visited: &mut HashSet<Address>,
+        touching: &HashSet<Address>,
+        cycles: &mut Vec<Cycle>,
+    ) {
+        if path.len() == max_hops {
+            return;
+        }
+        // Cloned so no map guard is held while recursing
+        let Some(pools) = self.adjacency.get(&token).map(|pools| pools.clone()) else {
+            return;
+        };
+        for pool in pools {
+            if path.iter().any(|edge| edge.pool == pool) {
+                continue;
+            }
+            let Some((token_out, rate)) = self.edges.get(&pool).map(|edge| {
+                if token == edge.token0 { (edge.token1, edge.log_rates.0) } else { (edge.token0, edge.log_rates.1) }
+            }) else {
+                continue;
+            };
+            path.push(Edge { pool, token_in: token, token_out });
+            let log_rate = log_rate + rate;
+            if token_out == start {
+                let touches = touching.is_empty() || path.iter().any(|edge| touching.contains(&edge.pool));
+                if path.len() >= 2 && log_rate > 0.0 && touches {
+                    cycles.push(Cycle { edges: path.clone(), log_rate });
+                }
+            } else if visited.insert(token_out) {
+                self.extend_path(start, token_out, max_hops, log_rate, path, visited, touching, cycles);
+                visited.remove(&token_out);
+            }
+            path.pop();
+        }
+    }
+}
+
+fn v2_log_rates(pool: &ConstantProductPool) -> Option<(f64, f64)> {
+    if pool.reserve0.is_zero() || pool.reserve1.is_zero() {
+        return None;
+    }
+    log_rates(pool.spot_price(true), f64::from(pool.fee_bps) / 10_000.0)
+}
+
+/// Log rates both ways from the token0 -> token1 spot price and the fee fraction
+fn log_rates(price: f64, fee: f64) -> Option<(f64, f64)> {
+    if !price.is_finite() || price <= 0.0 || fee >= 1.0 {
+        return None;
+    }
+    let fee = (1.0 - fee).ln();
+    Some((price.ln() + fee, -price.ln() + fee))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::{H256, U256};
+    use crate::storage::{SlotKey, StorageChangeType};
+
+    fn pool(address: u8, token0: Address, token1: Address, reserve0: u64, reserve1: u64) -> PoolState {
+        let mut pool = PoolState::v2(Address::repeat_byte(address), Address::zero(), token0, token1, 30);
+        pool.reserves = PoolReserves::V2 { reserve0: U256::from(reserve0), reserve1: U256::from(reserve1) };
+        pool
+    }
+
+    fn reserve_delta(contract: Address, slot: u64, value: u64) -> StorageDelta {
+        let mut new_value = [0u8; 32];
+        U256::from(value).to_big_endian(&mut new_value);
+        StorageDelta {
+            slot_key: SlotKey::Reserves(slot),
+            old_value: H256::zero(),
+            new_value: H256(new_value),
+            change_type: StorageChangeType::DirectWrite,
+            impact_score: 0.0,
+            confidence: 1.0,
+            block_number: 2,
+            contract,
+        }
+    }
+
+    #[test]
+    fn test_finds_triangle_and_reprices_from_deltas() {
+        let (a, b, c) = (Address::repeat_byte(0xa), Address::repeat_byte(0xb), Address::repeat_byte(0xc));
+        let graph = TokenGraph::new();
+        // a -> b -> c -> a multiplies by 2 * 1 * 0.6 before fees
+        graph.update_pool(&pool(1, a, b, 1_000_000, 2_000_000));
+        graph.update_pool(&pool(2, b, c, 1_000_000, 1_000_000));
+        graph.update_pool(&pool(3, a, c, 600_000, 1_000_000));
+
+        let cycles = graph.find_cycles(a, 3, &HashSet::new());
+        assert_eq!(cycles.len(), 1);
+        let pools: Vec<Address> = cycles[0].edges.iter().map(|edge| edge.pool).collect();
+        assert_eq!(pools, vec![Address::repeat_byte(1), Address::repeat_byte(2), Address::repeat_byte(3)]);
+        assert!((cycles[0].log_rate - (1.2f64.ln() + 3.0 * 0.997f64.ln())).abs() < 1e-9);
+        // Two hops can not close it, and it does not pass through an unrelated pool
+        assert!(graph.find_cycles(a, 2, &HashSet::new()).is_empty());
+        assert!(graph.find_cycles(a, 3, &HashSet::from([Address::repeat_byte(9)])).is_empty());
+
+        // The c -> a leg reprices to 0.5, which closes the gap
+        let updated = graph.apply_deltas(&[reserve_delta(Address::repeat_byte(3), 8, 500_000)]);
+        assert_eq!(updated, HashSet::from([Address::repeat_byte(3)]));
+        assert!(graph.find_cycles(a, 3, &HashSet::new()).is_empty());
+
+        // A stale pool state does not undo the delta, removing the pool drops its edges
+        assert!(!graph.update_pool(&pool(3, a, c, 600_000, 1_000_000)));
+        assert!(graph.remove_pool(Address::repeat_byte(3)));
+        assert!(graph.log_rate(Address::repeat_byte(3), a).is_none());
+        assert_eq!(graph.len(), 2);
+    }
+}
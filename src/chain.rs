@@ -0,0 +1,201 @@
+//! Per-chain profiles
+//!
+//! Everything the scanner assumes about a network lives in a `ChainProfile`: its chain
+//! id, block time, the chain's section of the address book (tokens, DEX factories,
+//! routers and pools), and the event signatures the drift detector decodes. A profile
+//! is picked by name or chain id in the config and checked against the node's
+//! `eth_chainId` at startup, so a Polygon endpoint cannot silently be scanned with
+//! Ethereum addresses.
+use std::{str::FromStr, time::Duration};
+use anyhow::{Result, anyhow};
+use ethers::types::H256;
+
+use crate::address_book::{AddressBook, ChainAddresses};
+use crate::const_and_addr::{self, ETHEREUM_BLOCK_TIME, POLYGON_BLOCK_TIME, BSC_BLOCK_TIME, ARBITRUM_BLOCK_TIME};
+
+/// Event topics the drift detector and pool manager interpret. Uniswap V2 and V3
+/// forks all emit the same ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventSignatures {
+    pub transfer: H256,
+    pub swap: H256,
+    pub sync: H256,
+    pub v3_swap: H256,
+    /// V3 liquidity added to or removed from a tick range
+    pub v3_mint: H256,
+    pub v3_burn: H256,
+}
+
+impl EventSignatures {
+    pub fn uniswap() -> Self {
+        Self {
+            transfer: const_and_addr::transfer_event_signature(),
+            swap: const_and_addr::swap_event_signature(),
+            sync: const_and_addr::sync_event_signature(),
+            v3_swap: const_and_addr::v3_swap_event_signature(),
+            v3_mint: const_and_addr::v3_mint_event_signature(),
+            v3_burn: const_and_addr::v3_burn_event_signature(),
+        }
+    }
+
+    /// Topics worth fetching logs for
+    pub fn tracked(&self) -> Vec<H256> {
+        vec![self.transfer, self.swap, self.sync, self.v3_swap, self.v3_mint, self.v3_burn]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainProfile {
+    /// Lower-case name, also used as `SlotDriftEvent.chain`
+    pub name: String,
+    pub chain_id: u64,
+    pub block_time: Duration,
+    /// Tokens, factories, routers and pools, from the address book
+    pub addresses: ChainAddresses,
+    pub events: EventSignatures,
+}
+
+impl ChainProfile {
+    /// Built-in profile with addresses from the embedded address book
+    fn builtin_profile(name: &str, block_time_seconds: u64) -> Self {
+        let addresses = AddressBook::embedded()
+            .chain(name)
+            .unwrap_or_else(|| panic!("embedded address book has no '{}' section", name))
+            .clone();
+        Self {
+            name: name.to_string(),
+            chain_id: addresses.chain_id,
+            block_time: Duration::from_secs(block_time_seconds),
+            addresses,
+            events: EventSignatures::uniswap(),
+        }
+    }
+
+    pub fn ethereum() -> Self {
+        Self::builtin_profile("ethereum", ETHEREUM_BLOCK_TIME)
+    }
+
+    pub fn polygon() -> Self {
+        Self::builtin_profile("polygon", POLYGON_BLOCK_TIME)
+    }
+
+    pub fn bsc() -> Self {
+        Self::builtin_profile("bsc", BSC_BLOCK_TIME)
+    }
+
+    pub fn arbitrum() -> Self {
+        Self::builtin_profile("arbitrum", ARBITRUM_BLOCK_TIME)
+    }
+
+    pub fn builtin() -> Vec<Self> {
+        vec![Self::ethereum(), Self::polygon(), Self::bsc(), Self::arbitrum()]
+    }
+
+    pub fn by_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        let name = match name.as_str() {
+            "mainnet" | "eth" => "ethereum",
+            "matic" => "polygon",
+            "bnb" => "bsc",
+            "arbitrum_one" | "arb" => "arbitrum",
+            other => other,
+        };
+        Self::builtin().into_iter().find(|profile| profile.name == name)
+    }
+
+    pub fn by_chain_id(chain_id: u64) -> Option<Self> {
+        Self::builtin().into_iter().find(|profile| profile.chain_id == chain_id)
+    }
+
+    /// Replaces the addresses with this chain's section of another address book
+    pub fn with_address_book(mut self, book: &AddressBook) -> Result<Self> {
+        let addresses = book.chain(&self.name)
+            .ok_or_else(|| anyhow!("Address book has no '{}' section", self.name))?;
+        if addresses.chain_id != self.chain_id {
+            return Err(anyhow!(
+                "Address book section '{}' is for chain id {}, expected {}",
+                self.name, addresses.chain_id, self.chain_id
+            ));
+        }
+        self.addresses = addresses.clone();
+        Ok(self)
+    }
+
+    /// Fails if a node reports a different chain than this profile describes
+    pub fn verify_chain_id(&self, reported: u64, endpoint: &str) -> Result<()> {
+        if reported != self.chain_id {
+            let actual = Self::by_chain_id(reported).map_or("an unknown chain".to_string(), |p| p.name);
+            return Err(anyhow!(
+                "{} reports chain id {} ({}), but chain.profile is '{}' (chain id {})",
+                endpoint, reported, actual, self.name, self.chain_id
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for ChainProfile {
+    fn default() -> Self {
+        Self::ethereum()
+    }
+}
+
+impl FromStr for ChainProfile {
+    type Err = anyhow::Error;
+
+    /// A profile name (`polygon`) or chain id (`137`)
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let profile = match s.parse::<u64>() {
+            Ok(chain_id) => Self::by_chain_id(chain_id),
+            Err(_) => Self::by_name(s),
+        };
+        profile.ok_or_else(|| {
+            let known: Vec<String> = Self::builtin().into_iter().map(|p| p.name).collect();
+            anyhow!("Unknown chain '{}', expected one of {}", s, known.join(", "))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles_resolve_by_name_and_id() {
+        for profile in ChainProfile::builtin() {
+            assert_eq!(ChainProfile::by_chain_id(profile.chain_id).as_ref(), Some(&profile));
+            assert_eq!(profile.name.parse::<ChainProfile>().unwrap(), profile);
+            assert_eq!(profile.chain_id.to_string().parse::<ChainProfile>().unwrap(), profile);
+            assert!(profile.addresses.token_by_address(profile.addresses.wrapped_native()).is_some());
+        }
+        assert_eq!("mainnet".parse::<ChainProfile>().unwrap().chain_id, 1);
+        assert!("solana".parse::<ChainProfile>().is_err());
+        assert_eq!(ChainProfile::bsc().addresses.token("busd").unwrap().decimals, 18);
+    }
+
+    #[test]
+    fn test_event_signatures_match_their_abi() {
+        use ethers::utils::keccak256;
+        let topic = |signature: &str| H256::from(keccak256(signature));
+
+        let events = EventSignatures::uniswap();
+        assert_eq!(events.transfer, topic("Transfer(address,address,uint256)"));
+        assert_eq!(events.swap, topic("Swap(address,uint256,uint256,uint256,uint256,address)"));
+        assert_eq!(events.sync, topic("Sync(uint112,uint112)"));
+        assert_eq!(events.v3_swap, topic("Swap(address,address,int256,int256,uint160,uint128,int24)"));
+        assert_eq!(events.v3_mint, topic("Mint(address,address,int24,int24,uint128,uint256,uint256)"));
+        assert_eq!(events.v3_burn, topic("Burn(address,int24,int24,uint128,uint256,uint256)"));
+        assert_eq!(const_and_addr::pair_created_event_signature(), topic("PairCreated(address,address,address,uint256)"));
+        assert_eq!(const_and_addr::pool_created_event_signature(), topic("PoolCreated(address,address,uint24,int24,address)"));
+    }
+
+    #[test]
+    fn test_chain_id_mismatch_names_both_chains() {
+        let profile = ChainProfile::ethereum();
+        assert!(profile.verify_chain_id(1, "http://node").is_ok());
+
+        let error = profile.verify_chain_id(137, "http://node").unwrap_err().to_string();
+        assert!(error.contains("polygon") && error.contains("ethereum"), "{}", error);
+    }
+}
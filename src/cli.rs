@@ -0,0 +1,242 @@
+//! Command-line interface of the `rust_marathon` binary
+//!
+//! Every subcommand except `replay` and `layout` loads the layered `ScannerConfig`
+//! (defaults < `--config` file < environment < `--set`) and drives a `MevScanner`.
+//! `scan` accepts `--config` once per chain and runs one scanner for each.
+//! Results go to stdout as JSON, logs go to stderr.
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result, anyhow};
+use clap::{Parser, Subcommand};
+use ethers::types::Address;
+use futures::future;
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tracing::{Instrument, error, info, info_span, warn};
+
+use rust_marathon::chain::ChainProfile;
+use rust_marathon::config::{ScannerConfig, parse_override};
+use rust_marathon::scanner::{MevScanner, load_replay_file, save_replay_file};
+use rust_marathon::storage::StorageDriftDetector;
+
+#[derive(Debug, Parser)]
+#[command(name = "rust_marathon", version, about = "Ethereum MEV scanner and storage drift detector")]
+pub struct Cli {
+    /// TOML config file (defaults to $SCANNER_CONFIG). Repeat to scan several chains.
+    #[arg(long, global = true)]
+    pub config: Vec<PathBuf>,
+
+    /// Override a config key, e.g. --set circuit_breaker.threshold=10 (repeatable)
+    #[arg(long = "set", value_name = "KEY=VALUE", value_parser = parse_override, global = true)]
+    pub overrides: Vec<(String, String)>,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Scan new blocks live until Ctrl-C, one scanner per `--config`
+    Scan,
+    /// Process an inclusive block range over HTTP
+    Backfill {
+        #[arg(long)]
+        from: u64,
+        #[arg(long)]
+        to: u64,
+    },
+    /// Analyze blocks recorded in a replay file, without network access
+    Replay {
+        file: PathBuf,
+        /// Chain the blocks come from (name or chain id)
+        #[arg(long, default_value = "ethereum")]
+        chain: String,
+    },
+    /// Print the storage deltas and drift events of one block
+    InspectBlock {
+        number: u64,
+        /// Also write the block and its receipts as a replay file
+        #[arg(long, value_name = "FILE")]
+        save: Option<PathBuf>,
+    },
+    /// Print the storage layout the detector assumes for a contract
+    Layout {
+        address: Address,
+    },
+    /// Configuration utilities
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigAction {
+    /// Load and validate the layered configuration, then print the effective values
+    Check,
+}
+
+impl Cli {
+    pub async fn run(self) -> Result<()> {
+        match self.command {
+            Command::Scan => {
+                let configs = self.load_configs()?;
+                ScannerConfig::check_isolated(&configs)?;
+
+                let mut scanners = Vec::with_capacity(configs.len());
+                for config in configs {
+                    let chain = config.chain().name.clone();
+                    let scanner = MevScanner::new(config)
+                        .instrument(info_span!("scanner", chain = %chain))
+                        .await
+                        .with_context(|| format!("Failed to start {} scanner", chain))?;
+                    scanners.push((chain, scanner));
+                }
+
+                let mut shutdown_txs = Vec::with_capacity(scanners.len());
+                let runs = scanners.iter().map(|(chain, scanner)| {
+                    let (shutdown_tx, shutdown_rx) = mpsc::channel(1);
+                    shutdown_txs.push(shutdown_tx);
+                    async move {
+                        let result = scanner.run_cycle(shutdown_rx).await;
+                        if let Err(e) = &result {
+                            error!("❌ {} scanner stopped: {:#}", chain, e);
+                        }
+                        result
+                    }
+                    .instrument(info_span!("scanner", chain = %chain))
+                }).collect::<Vec<_>>();
+
+                tokio::spawn(async move {
+                    if tokio::signal::ctrl_c().await.is_ok() {
+                        for shutdown_tx in shutdown_txs {
+                            let _ = shutdown_tx.send(()).await;
+                        }
+                    }
+                });
+                future::join_all(runs).await.into_iter().collect()
+            }
+            Command::Backfill { from, to } => {
+                if from > to {
+                    return Err(anyhow!("--from {} is after --to {}", from, to));
+                }
+                let scanner = MevScanner::http_only(self.load_http_config()?).await?;
+                // Log-filter mode filters on the watched pools, so they come first
+                if let Err(e) = scanner.initialize_pools().await {
+                    warn!("⚠️ Pool initialization failed, continuing with the pools known so far: {:#}", e);
+                }
+                let processed = scanner.backfill(from, to).await;
+                scanner.save_state().await?;
+
+                print_json(&BackfillReport {
+                    from,
+                    to,
+                    processed,
+                    pending_retry: scanner.pending_retry_blocks().await,
+                    dead_letters: scanner.dead_letter_blocks().await.into_iter().map(|d| d.block_number).collect(),
+                })
+            }
+            Command::Replay { file, chain } => {
+                let detector = StorageDriftDetector::for_chain(&chain.parse::<ChainProfile>()?);
+                let mut analyses = Vec::new();
+                for block in load_replay_file(&file)? {
+                    let number = block.number()?;
+                    let analysis = detector.analyze_block_with_deltas(&block.block, block.receipts)
+                        .await
+                        .with_context(|| format!("Failed to analyze replayed block {}", number))?;
+                    analyses.push(analysis);
+                }
+                print_json(&analyses)
+            }
+            Command::InspectBlock { number, ref save } => {
+                let scanner = MevScanner::new(self.load_config()?).await?;
+                let (replay, analysis) = scanner.inspect_block(number).await?;
+                if let Some(path) = save {
+                    save_replay_file(path, &replay)?;
+                    info!("💾 Saved block {} with {} receipts to {}", number, replay.receipts.len(), path.display());
+                }
+                print_json(&analysis)
+            }
+            Command::Layout { address } => {
+                let layout = StorageDriftDetector::new().get_storage_layout(address).await;
+                print_json(&layout)
+            }
+            Command::Config { action: ConfigAction::Check } => {
+                let config = self.load_config()?;
+                for (key, value) in config.entries() {
+                    println!("{} = {}", key, value);
+                }
+                eprintln!("✅ Configuration is valid");
+                Ok(())
+            }
+        }
+    }
+
+    /// The single config of a non-`scan` subcommand
+    fn load_config(&self) -> Result<ScannerConfig> {
+        ScannerConfig::load(self.single_config_path()?, &self.overrides)
+    }
+
+    /// The single config of a subcommand that never opens a WebSocket
+    fn load_http_config(&self) -> Result<ScannerConfig> {
+        ScannerConfig::load_http_only(self.single_config_path()?, &self.overrides)
+    }
+
+    fn single_config_path(&self) -> Result<Option<&Path>> {
+        match self.config.as_slice() {
+            [] => Ok(None),
+            [path] => Ok(Some(path)),
+            _ => Err(anyhow!("Only `scan` accepts more than one --config")),
+        }
+    }
+
+    fn load_configs(&self) -> Result<Vec<ScannerConfig>> {
+        if self.config.is_empty() {
+            return Ok(vec![ScannerConfig::load(None, &self.overrides)?]);
+        }
+        self.config
+            .iter()
+            .map(|path| ScannerConfig::load(Some(path), &self.overrides))
+            .collect()
+    }
+}
+
+#[derive(Serialize)]
+struct BackfillReport {
+    from: u64,
+    to: u64,
+    processed: u64,
+    pending_retry: Vec<u64>,
+    dead_letters: Vec<u64>,
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_subcommands_and_global_flags() {
+        let cli = Cli::try_parse_from([
+            "rust_marathon", "backfill", "--from", "10", "--to", "20",
+            "--config", "scanner.toml", "--set", "concurrency.block_pipeline_depth=8",
+        ]).unwrap();
+        assert!(matches!(cli.command, Command::Backfill { from: 10, to: 20 }));
+        assert_eq!(cli.config, vec![PathBuf::from("scanner.toml")]);
+        assert_eq!(cli.overrides, vec![("concurrency.block_pipeline_depth".to_string(), "8".to_string())]);
+
+        let cli = Cli::try_parse_from(["rust_marathon", "inspect-block", "123", "--save", "b.json"]).unwrap();
+        assert!(matches!(cli.command, Command::InspectBlock { number: 123, save: Some(_) }));
+
+        assert!(Cli::try_parse_from(["rust_marathon", "config", "check"]).is_ok());
+        let cli = Cli::try_parse_from(["rust_marathon", "scan", "--config", "eth.toml", "--config", "polygon.toml"]).unwrap();
+        assert_eq!(cli.config.len(), 2);
+        let cli = Cli::try_parse_from(["rust_marathon", "replay", "blocks.json", "--chain", "137"]).unwrap();
+        assert!(matches!(cli.command, Command::Replay { chain, .. } if chain == "137"));
+        assert!(Cli::try_parse_from(["rust_marathon", "layout", "not-an-address"]).is_err());
+        assert!(Cli::try_parse_from(["rust_marathon", "scan", "--set", "missing-equals"]).is_err());
+    }
+}
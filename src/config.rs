@@ -1,91 +1,759 @@
-use ethers::types::U256;
+use ethers::types::{Address, U256};
 use anyhow::{anyhow,Context,Result};
 use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
     time::Duration,
     str::FromStr,
 };
 
 use crate::make_getters;
+use crate::address_book::AddressBook;
+use crate::chain::ChainProfile;
 use crate::const_and_addr;
+use crate::gas::GasTier;
+use crate::simulation::SimulationBackend;
+use crate::scanner::{FailureWindow, TripPolicy};
+use crate::signer::{SignerSettings, SignerSource};
 
 
 
+/// How the scanner obtains the logs it analyzes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanMode {
+    /// Fetch every receipt of every block
+    FullReceipts,
+    /// Subscribe to / poll only logs of monitored contracts and tracked events
+    LogFilter,
+}
+
+impl FromStr for ScanMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "receipts" | "full_receipts" => Ok(ScanMode::FullReceipts),
+            "logs" | "log_filter" => Ok(ScanMode::LogFilter),
+            other => Err(anyhow!("Unknown scan mode '{}', expected 'receipts' or 'logs'", other)),
+        }
+    }
+}
+
+/// A contract the scanner watches from startup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MonitoredContract {
+    pub address: Address,
+    /// Analysis needs trace data, so the contract stays on the full-receipt path
+    pub needs_traces: bool,
+}
+
+impl FromStr for MonitoredContract {
+    type Err = anyhow::Error;
+
+    /// `0xabc...` or `0xabc...:traces`
+    fn from_str(s: &str) -> Result<Self> {
+        let (address, needs_traces) = match s.trim().split_once(':') {
+            Some((address, "traces")) => (address, true),
+            Some((_, flag)) => return Err(anyhow!("Unknown contract flag '{}', expected 'traces'", flag)),
+            None => (s.trim(), false),
+        };
+        let address = Address::from_str(address).map_err(|e| anyhow!("Invalid address '{}': {}", address, e))?;
+        Ok(Self { address, needs_traces })
+    }
+}
+
+/// Which analyses the scanner runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StrategyToggles {
+    pub storage_drift: bool,
+    pub arbitrage: bool,
+    pub mempool: bool,
+}
+
+impl Default for StrategyToggles {
+    fn default() -> Self {
+        Self {
+            storage_drift: true,
+            arbitrage: false,
+            mempool: false,
+        }
+    }
+}
+
+/// Environment variables and the config keys they override
+const ENV_OVERRIDES: [(&str, &str); 33] = [
+    ("CHAIN", "chain.profile"),
+    ("ADDRESS_BOOK", "chain.address_book"),
+    ("WS_URL", "endpoints.ws_url"),
+    ("HTTP_URL", "endpoints.http_url"),
+    ("MAX_TRADE_SIZE", "trading.max_trade_size"),
+    ("MIN_PROFIT_THRESHOLD", "trading.min_profit_threshold"),
+    ("MAX_SLIPPAGE", "trading.max_slippage"),
+    ("MAX_ARBITRAGE_HOPS", "trading.max_arbitrage_hops"),
+    ("GAS_TIER", "trading.gas_tier"),
+    ("SUBMISSION_ENABLED", "trading.submission_enabled"),
+    ("SIGNER_KIND", "signer.kind"),
+    ("KEYSTORE_PATH", "signer.keystore"),
+    ("KEYSTORE_PASSWORD_FILE", "signer.password_file"),
+    ("DERIVATION_PATH", "signer.derivation_path"),
+    ("CIRCUIT_BREAKER_THRESHOLD", "circuit_breaker.threshold"),
+    ("CIRCUIT_BREAKER_COOLDOWN_SECONDS", "circuit_breaker.cooldown_seconds"),
+    ("CIRCUIT_BREAKER_FAILURE_RATE", "circuit_breaker.failure_rate"),
+    ("CIRCUIT_BREAKER_WINDOW", "circuit_breaker.window"),
+    ("CIRCUIT_BREAKER_MIN_CALLS", "circuit_breaker.min_calls"),
+    ("BLOCK_PIPELINE_DEPTH", "concurrency.block_pipeline_depth"),
+    ("MAX_RECEIPT_CONCURRENCY", "concurrency.max_receipt_concurrency"),
+    ("SCAN_MODE", "scanner.scan_mode"),
+    ("SCANNER_STATE_DIR", "scanner.state_dir"),
+    ("CHECKPOINT_INTERVAL_SECONDS", "scanner.checkpoint_interval_seconds"),
+    ("BLOCK_PROCESSING_TIMEOUT_MS", "scanner.block_processing_timeout_ms"),
+    ("HTTP_POLL_INTERVAL_MS", "scanner.http_poll_interval_ms"),
+    ("MAX_BLOCK_RETRIES", "scanner.max_block_retries"),
+    ("RETRY_BASE_DELAY_MS", "scanner.retry_base_delay_ms"),
+    ("RESOLVE_TOP_PAIRS", "pools.resolve_top_pairs"),
+    ("POOL_DISCOVERY_FROM_BLOCK", "pools.discover_from_block"),
+    ("SIMULATION_BACKEND", "simulation.backend"),
+    ("SIMULATION_FORK_URL", "simulation.fork_url"),
+    ("MONITORED_CONTRACTS", "monitored_contracts"),
+];
+
+/// Config file used when no path is given explicitly
+const CONFIG_PATH_ENV: &str = "SCANNER_CONFIG";
+
 #[derive(Debug, Clone)]
 pub struct ScannerConfig {
+    /// Network the endpoints must serve, checked against `eth_chainId` at startup
+    chain: ChainProfile,
+    /// Replaces the embedded address book when set
+    address_book: Option<PathBuf>,
     primary_rpc_url: String,
     fallback_rpc_url: String,
     max_trade_size: U256,
     min_profit_threshold: f64,
     max_slippage: f64,
-    private_key: String,
+    /// Longest arbitrage cycle searched, in swaps
+    max_arbitrage_hops: usize,
+    /// Priority fee percentile gas costs are estimated at
+    gas_tier: GasTier,
+    /// Transactions are only signed and sent when enabled
+    submission_enabled: bool,
+    signer_settings: SignerSettings,
+    /// Resolved from `signer_settings` and the environment, read-only unless submitting
+    signer: SignerSource,
     circuit_breaker_threshold: usize,
     circuit_breaker_cooldown_seconds: Duration,
+    /// Switches the breaker to failure-rate mode when set
+    circuit_breaker_failure_rate: Option<f64>,
+    circuit_breaker_window: FailureWindow,
+    circuit_breaker_min_calls: usize,
+    block_pipeline_depth: usize,
+    max_receipt_concurrency: usize,
+    scan_mode: ScanMode,
+    /// Where the block checkpoint and seen-set are persisted, nothing is saved when unset
+    state_dir: Option<PathBuf>,
+    checkpoint_interval: Duration,
+    block_processing_timeout: Duration,
+    http_poll_interval: Duration,
+    max_block_retries: usize,
+    retry_base_delay: Duration,
+    seen_filter_items: usize,
+    seen_filter_false_positive_rate: f64,
+    seen_filter_generations: usize,
+    /// Looks up the address book's top pairs on every factory at startup
+    resolve_top_pairs: bool,
+    /// Scans factory creation events from this block at startup when set
+    discover_pools_from_block: Option<u64>,
+    /// Opportunities are only kept once their bundle succeeds in simulation when set
+    simulation_backend: Option<SimulationBackend>,
+    /// Node the simulation fork is taken from, `fallback_rpc_url` when unset
+    simulation_fork_url: Option<String>,
+    monitored_contracts: Vec<MonitoredContract>,
+    strategies: StrategyToggles,
 }
 
+impl Default for ScannerConfig {
+    /// Built-in defaults, endpoints must still be provided
+    fn default() -> Self {
+        Self {
+            chain: ChainProfile::ethereum(),
+            address_book: None,
+            primary_rpc_url: String::new(),
+            fallback_rpc_url: String::new(),
+            max_trade_size: U256::exp10(18),
+            min_profit_threshold: 0.001,
+            max_slippage: 0.005,
+            max_arbitrage_hops: const_and_addr::ARBITRAGE_MAX_HOPS,
+            gas_tier: GasTier::Standard,
+            submission_enabled: false,
+            signer_settings: SignerSettings::default(),
+            signer: SignerSource::ReadOnly,
+            circuit_breaker_threshold: const_and_addr::CIRCUIT_BREAKER_THRESHOLD,
+            circuit_breaker_cooldown_seconds: const_and_addr::COOL_DOWN_PERIOD,
+            circuit_breaker_failure_rate: None,
+            circuit_breaker_window: FailureWindow::Time(const_and_addr::CIRCUIT_BREAKER_WINDOW),
+            circuit_breaker_min_calls: const_and_addr::CIRCUIT_BREAKER_MIN_CALLS,
+            block_pipeline_depth: const_and_addr::MAX_BLOCK_BATCH_SIZE,
+            max_receipt_concurrency: const_and_addr::MAX_RECEIPT_CONCURRENCY,
+            scan_mode: ScanMode::FullReceipts,
+            state_dir: None,
+            checkpoint_interval: Duration::from_secs(const_and_addr::CHECKPOINT_INTERVAL_SECONDS),
+            block_processing_timeout: Duration::from_millis(const_and_addr::BLOCK_PROCESSING_TIMEOUT_MS),
+            http_poll_interval: Duration::from_millis(const_and_addr::HTTP_POLL_INTERVAL_MS),
+            max_block_retries: const_and_addr::MAX_RETRIES,
+            retry_base_delay: Duration::from_millis(const_and_addr::RETRY_DELAY_MS),
+            seen_filter_items: const_and_addr::SEEN_FILTER_ITEMS_PER_GENERATION,
+            seen_filter_false_positive_rate: const_and_addr::SEEN_FILTER_FALSE_POSITIVE_RATE,
+            seen_filter_generations: const_and_addr::SEEN_FILTER_GENERATIONS,
+            resolve_top_pairs: false,
+            discover_pools_from_block: None,
+            simulation_backend: None,
+            simulation_fork_url: None,
+            monitored_contracts: Vec::new(),
+            strategies: StrategyToggles::default(),
+        }
+    }
+}
 
-
-fn parse_env_var<T>(key: &str, default: T) -> T 
-where 
+/// Parses a config value, naming the key on failure
+fn parse_value<T>(key: &str, value: &str) -> Result<T>
+where
     T: FromStr,
+    T::Err: Display,
+{
+    value.trim()
+        .parse::<T>()
+        .map_err(|e| anyhow!("Invalid value '{}' for {}: {}", value, key, e))
+}
+
+fn parse_positive<T>(key: &str, value: &str) -> Result<T>
+where
+    T: FromStr + PartialOrd + Default,
+    T::Err: Display,
 {
-    std::env::var(key)
-        .ok()
-        .and_then(|s| s.parse::<T>().ok())
-        .unwrap_or(default)
+    let parsed: T = parse_value(key, value)?;
+    if parsed <= T::default() {
+        return Err(anyhow!("{} must be greater than 0, got '{}'", key, value));
+    }
+    Ok(parsed)
+}
+
+fn parse_fraction(key: &str, value: &str, allow_zero: bool, allow_one: bool) -> Result<f64> {
+    let parsed: f64 = parse_value(key, value)?;
+    let above_min = if allow_zero { parsed >= 0.0 } else { parsed > 0.0 };
+    let below_max = if allow_one { parsed <= 1.0 } else { parsed < 1.0 };
+    if !(above_min && below_max) {
+        return Err(anyhow!("{} must be between 0 and 1, got {}", key, parsed));
+    }
+    Ok(parsed)
+}
 
+/// Splits a `key=value` override as given on the command line
+pub fn parse_override(s: &str) -> Result<(String, String)> {
+    let (key, value) = s.split_once('=')
+        .ok_or_else(|| anyhow!("Invalid override '{}', expected key=value", s))?;
+    Ok((key.trim().to_string(), value.trim().to_string()))
 }
 
 impl ScannerConfig {
 
     make_getters!(
         ref: 
+            (chain: ChainProfile),
             (primary_rpc_url: String),
             (fallback_rpc_url: String),
             (max_trade_size: U256),
-            (private_key: String),
+            (signer: SignerSource),
             (circuit_breaker_cooldown_seconds: Duration),
+            (state_dir: Option<PathBuf>),
+            (simulation_fork_url: Option<String>),
+            (monitored_contracts: Vec<MonitoredContract>),
     );
 
     make_getters!(
         copy:
             (min_profit_threshold: f64),
             (max_slippage: f64),
+            (max_arbitrage_hops: usize),
+            (gas_tier: GasTier),
+            (submission_enabled: bool),
+            (resolve_top_pairs: bool),
+            (discover_pools_from_block: Option<u64>),
+            (simulation_backend: Option<SimulationBackend>),
             (circuit_breaker_threshold: usize),
+            (circuit_breaker_failure_rate: Option<f64>),
+            (circuit_breaker_window: FailureWindow),
+            (circuit_breaker_min_calls: usize),
+            (block_pipeline_depth: usize),
+            (max_receipt_concurrency: usize),
+            (scan_mode: ScanMode),
+            (checkpoint_interval: Duration),
+            (block_processing_timeout: Duration),
+            (http_poll_interval: Duration),
+            (max_block_retries: usize),
+            (retry_base_delay: Duration),
+            (seen_filter_items: usize),
+            (seen_filter_false_positive_rate: f64),
+            (seen_filter_generations: usize),
+            (strategies: StrategyToggles),
     );
+    /// Consecutive-failure counting unless a failure rate is configured
+    pub fn circuit_breaker_policy(&self) -> TripPolicy {
+        match self.circuit_breaker_failure_rate {
+            Some(max_failure_rate) => TripPolicy::FailureRate {
+                window: self.circuit_breaker_window,
+                max_failure_rate,
+                min_calls: self.circuit_breaker_min_calls,
+            },
+            None => TripPolicy::ConsecutiveFailures,
+        }
+    }
+
+    /// Environment only, plus the file named by `SCANNER_CONFIG` if set
     pub fn from_env() -> Result<Self> {
+        Self::load(None, &[])
+    }
+
+    /// Builds the config from defaults < config file < environment < `overrides`.
+    ///
+    /// `config_path` falls back to `SCANNER_CONFIG`. `overrides` are `(key, value)`
+    /// pairs such as `("circuit_breaker.threshold", "10")`, usually from the CLI.
+    pub fn load(config_path: Option<&Path>, overrides: &[(String, String)]) -> Result<Self> {
+        let config = Self::layered(config_path, overrides)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Like `load`, for commands that only use the HTTP endpoint: `endpoints.ws_url` may
+    /// be left unset
+    pub fn load_http_only(config_path: Option<&Path>, overrides: &[(String, String)]) -> Result<Self> {
+        let config = Self::layered(config_path, overrides)?;
+        config.validate_http_only()?;
+        Ok(config)
+    }
+
+    fn layered(config_path: Option<&Path>, overrides: &[(String, String)]) -> Result<Self> {
         let _ = dotenv::dotenv();
-        let primary_rpc_url = std::env::var("WS_URL")
-            .context("Missing WS_URL in enviornment")?;
-        let fallback_rpc_url= std::env::var("HTTP_URL")
-            .context("Missing RPC_URL in environmnet")?;
-        let private_key = std::env::var("PRIVATE_KEY")
-            .context("Missing PRIVATE_KEY in environment")?;
-        
-        if !primary_rpc_url.starts_with("ws"){
-            return Err(anyhow!("WS_URL must start with ws:// or wss://"));
-        }
-        if !fallback_rpc_url.starts_with("http"){
-            return Err(anyhow!("RPC_URL must start with http://or https://"));
-        }
-        if private_key.is_empty(){
-            return Err(anyhow!("PRIVATE_KEY cannot be empty"));
-        }
-        let max_trade_size = std::env::var("MAX_TRADE_SIZE")
-                .ok()
-                .and_then(|s| U256::from_dec_str(&s).ok())
-                .unwrap_or_else(|| U256::exp10(18));
-
-        Ok(Self { 
-            primary_rpc_url,
-            fallback_rpc_url,
-            max_trade_size,
-            min_profit_threshold: parse_env_var("MIN_PROFIT_THRESHOLD", 0.001),
-            max_slippage: parse_env_var("MAX_SLIPPAGE", 0.005),
-            circuit_breaker_cooldown_seconds: const_and_addr::COOL_DOWN_PERIOD,
-            circuit_breaker_threshold: const_and_addr::CIRCUIT_BREAKER_THRESHOLD,
-            private_key,
+        let mut config = Self::default();
+
+        let env_path = std::env::var(CONFIG_PATH_ENV).ok().filter(|p| !p.is_empty()).map(PathBuf::from);
+        if let Some(path) = config_path.map(Path::to_path_buf).or(env_path) {
+            config.apply_file(&path)?;
+        }
+        config.apply_env()?;
+        for (key, value) in overrides {
+            config.apply_override(key, value)?;
+        }
+
+        // The profile may be set after the book path, so the book is applied last
+        if let Some(path) = &config.address_book {
+            let book = AddressBook::load(path)?;
+            config.chain = config.chain.clone().with_address_book(&book)
+                .with_context(|| format!("Invalid chain.address_book {}", path.display()))?;
+        }
+        if config.submission_enabled {
+            config.signer = config.signer_settings.resolve(|name| std::env::var(name).ok())?;
+        }
+        Ok(config)
+    }
+
+    /// Applies every key of a TOML config file
+    pub fn apply_file(&mut self, path: &Path) -> Result<()> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        self.apply_toml(&contents)
+            .with_context(|| format!("Invalid config file {}", path.display()))
+    }
+
+    fn apply_toml(&mut self, contents: &str) -> Result<()> {
+        let table: toml::Table = contents.parse()?;
+
+        for (section, value) in table {
+            if section == "monitored_contracts" {
+                self.monitored_contracts = Self::contracts_from_toml(&value)?;
+                continue;
+            }
+            let toml::Value::Table(entries) = value else {
+                return Err(anyhow!("Unexpected top-level key '{}', expected a [section]", section));
+            };
+            for (name, value) in entries {
+                let key = format!("{}.{}", section, name);
+                let value = match value {
+                    toml::Value::String(s) => s,
+                    toml::Value::Integer(_) | toml::Value::Float(_) | toml::Value::Boolean(_) => value.to_string(),
+                    _ => return Err(anyhow!("{} must be a string, number or boolean", key)),
+                };
+                self.apply_override(&key, &value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// `monitored_contracts = ["0x..", { address = "0x..", needs_traces = true }]`
+    fn contracts_from_toml(value: &toml::Value) -> Result<Vec<MonitoredContract>> {
+        let toml::Value::Array(entries) = value else {
+            return Err(anyhow!("monitored_contracts must be an array"));
+        };
 
-        })
+        entries.iter().enumerate().map(|(i, entry)| {
+            let key = format!("monitored_contracts[{}]", i);
+            match entry {
+                toml::Value::String(s) => parse_value(&key, s),
+                toml::Value::Table(table) => {
+                    let address = table.get("address")
+                        .and_then(|a| a.as_str())
+                        .ok_or_else(|| anyhow!("{}.address is required", key))?;
+                    let needs_traces = match table.get("needs_traces") {
+                        None => false,
+                        Some(toml::Value::Boolean(b)) => *b,
+                        Some(_) => return Err(anyhow!("{}.needs_traces must be a boolean", key)),
+                    };
+                    if let Some(unknown) = table.keys().find(|k| *k != "address" && *k != "needs_traces") {
+                        return Err(anyhow!("Unknown config key '{}.{}'", key, unknown));
+                    }
+                    let address = Address::from_str(address)
+                        .map_err(|e| anyhow!("Invalid value '{}' for {}.address: {}", address, key, e))?;
+                    Ok(MonitoredContract { address, needs_traces })
+                }
+                _ => Err(anyhow!("{} must be an address or a table", key)),
+            }
+        }).collect()
+    }
+
+    fn apply_env(&mut self) -> Result<()> {
+        for (var, key) in ENV_OVERRIDES {
+            if let Ok(value) = std::env::var(var) {
+                self.apply_override(key, &value)
+                    .with_context(|| format!("from environment variable {}", var))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets a single dotted config key. Every layer (file, environment, CLI) goes
+    /// through here, so all of them accept the same keys and report errors the same way.
+    pub fn apply_override(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "chain.profile" => self.chain = parse_value(key, value)?,
+            "chain.address_book" => {
+                self.address_book = Some(value.trim()).filter(|p| !p.is_empty()).map(PathBuf::from);
+            }
+
+            "endpoints.ws_url" => self.primary_rpc_url = value.trim().to_string(),
+            "endpoints.http_url" => self.fallback_rpc_url = value.trim().to_string(),
+
+            "trading.max_trade_size" => {
+                self.max_trade_size = U256::from_dec_str(value.trim())
+                    .map_err(|e| anyhow!("Invalid value '{}' for {}: {}", value, key, e))?;
+            }
+            "trading.min_profit_threshold" => {
+                self.min_profit_threshold = parse_value(key, value)?;
+                if self.min_profit_threshold < 0.0 {
+                    return Err(anyhow!("{} cannot be negative", key));
+                }
+            }
+            "trading.max_slippage" => self.max_slippage = parse_fraction(key, value, true, false)?,
+            "trading.max_arbitrage_hops" => {
+                self.max_arbitrage_hops = parse_value(key, value)?;
+                if !(2..=const_and_addr::ARBITRAGE_HOPS_LIMIT).contains(&self.max_arbitrage_hops) {
+                    return Err(anyhow!("{} must be between 2 and {}", key, const_and_addr::ARBITRAGE_HOPS_LIMIT));
+                }
+            }
+            "trading.gas_tier" => self.gas_tier = parse_value(key, value)?,
+            "trading.submission_enabled" => self.submission_enabled = parse_value(key, value)?,
+
+            "signer.kind" => {
+                self.signer_settings.kind = Some(value.trim()).filter(|k| !k.is_empty()).map(|k| parse_value(key, k)).transpose()?;
+            }
+            "signer.keystore" => {
+                self.signer_settings.keystore = Some(value.trim()).filter(|p| !p.is_empty()).map(PathBuf::from);
+            }
+            "signer.password_file" => {
+                self.signer_settings.password_file = Some(value.trim()).filter(|p| !p.is_empty()).map(PathBuf::from);
+            }
+            "signer.derivation_path" => {
+                self.signer_settings.derivation_path = Some(value.trim().to_string()).filter(|p| !p.is_empty());
+            }
+
+            "circuit_breaker.threshold" => self.circuit_breaker_threshold = parse_positive(key, value)?,
+            "circuit_breaker.cooldown_seconds" => {
+                self.circuit_breaker_cooldown_seconds = Duration::from_secs(parse_value(key, value)?);
+            }
+            "circuit_breaker.failure_rate" => {
+                self.circuit_breaker_failure_rate = match value.trim() {
+                    "" | "none" | "off" => None,
+                    rate => Some(parse_fraction(key, rate, false, true)?),
+                };
+            }
+            "circuit_breaker.window" => self.circuit_breaker_window = parse_value(key, value)?,
+            "circuit_breaker.min_calls" => self.circuit_breaker_min_calls = parse_positive(key, value)?,
+
+            "concurrency.block_pipeline_depth" => self.block_pipeline_depth = parse_positive(key, value)?,
+            "concurrency.max_receipt_concurrency" => self.max_receipt_concurrency = parse_positive(key, value)?,
+
+            "scanner.scan_mode" => self.scan_mode = parse_value(key, value)?,
+            "scanner.state_dir" => {
+                self.state_dir = Some(value.trim()).filter(|p| !p.is_empty()).map(PathBuf::from);
+            }
+            "scanner.checkpoint_interval_seconds" => {
+                self.checkpoint_interval = Duration::from_secs(parse_positive(key, value)?);
+            }
+            "scanner.block_processing_timeout_ms" => {
+                self.block_processing_timeout = Duration::from_millis(parse_positive(key, value)?);
+            }
+            "scanner.http_poll_interval_ms" => {
+                self.http_poll_interval = Duration::from_millis(parse_positive(key, value)?);
+            }
+            "scanner.max_block_retries" => self.max_block_retries = parse_positive(key, value)?,
+            "scanner.retry_base_delay_ms" => self.retry_base_delay = Duration::from_millis(parse_value(key, value)?),
+
+            "cache.seen_filter_items" => self.seen_filter_items = parse_positive(key, value)?,
+            "cache.seen_filter_false_positive_rate" => {
+                self.seen_filter_false_positive_rate = parse_fraction(key, value, false, false)?;
+            }
+            "cache.seen_filter_generations" => self.seen_filter_generations = parse_positive(key, value)?,
+
+            "pools.resolve_top_pairs" => self.resolve_top_pairs = parse_value(key, value)?,
+            "pools.discover_from_block" => {
+                self.discover_pools_from_block = match value.trim() {
+                    "" | "none" | "off" => None,
+                    block => Some(parse_value(key, block)?),
+                };
+            }
+
+            "simulation.backend" => {
+                self.simulation_backend = match value.trim() {
+                    "" | "none" | "off" => None,
+                    backend => Some(parse_value(key, backend)?),
+                };
+            }
+            "simulation.fork_url" => {
+                self.simulation_fork_url = Some(value.trim().to_string()).filter(|url| !url.is_empty());
+            }
+
+            "strategies.storage_drift" => self.strategies.storage_drift = parse_value(key, value)?,
+            "strategies.arbitrage" => self.strategies.arbitrage = parse_value(key, value)?,
+            "strategies.mempool" => self.strategies.mempool = parse_value(key, value)?,
+
+            "monitored_contracts" => {
+                self.monitored_contracts = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|entry| !entry.is_empty())
+                    .map(|entry| parse_value(key, entry))
+                    .collect::<Result<_>>()?;
+            }
+
+            _ => return Err(anyhow!("Unknown config key '{}'", key)),
+        }
+        Ok(())
+    }
+
+    /// Effective value of every key in `apply_override` syntax, secrets excluded
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        let window = match self.circuit_breaker_window {
+            FailureWindow::Time(window) => format!("{}s", window.as_secs()),
+            FailureWindow::Calls(calls) => format!("{}calls", calls),
+        };
+        let contracts: Vec<String> = self.monitored_contracts
+            .iter()
+            .map(|c| if c.needs_traces { format!("{:?}:traces", c.address) } else { format!("{:?}", c.address) })
+            .collect();
+
+        vec![
+            ("chain.profile", format!("{} (chain id {})", self.chain.name, self.chain.chain_id)),
+            ("chain.address_book", self.address_book.as_ref().map_or("embedded".to_string(), |p| p.display().to_string())),
+            ("endpoints.ws_url", self.primary_rpc_url.clone()),
+            ("endpoints.http_url", self.fallback_rpc_url.clone()),
+            ("trading.max_trade_size", self.max_trade_size.to_string()),
+            ("trading.min_profit_threshold", self.min_profit_threshold.to_string()),
+            ("trading.max_slippage", self.max_slippage.to_string()),
+            ("trading.max_arbitrage_hops", self.max_arbitrage_hops.to_string()),
+            ("trading.gas_tier", format!("{:?}", self.gas_tier)),
+            ("trading.submission_enabled", self.submission_enabled.to_string()),
+            ("signer", self.signer.describe()),
+            ("circuit_breaker.threshold", self.circuit_breaker_threshold.to_string()),
+            ("circuit_breaker.cooldown_seconds", self.circuit_breaker_cooldown_seconds.as_secs().to_string()),
+            ("circuit_breaker.failure_rate", self.circuit_breaker_failure_rate.map_or("off".to_string(), |r| r.to_string())),
+            ("circuit_breaker.window", window),
+            ("circuit_breaker.min_calls", self.circuit_breaker_min_calls.to_string()),
+            ("concurrency.block_pipeline_depth", self.block_pipeline_depth.to_string()),
+            ("concurrency.max_receipt_concurrency", self.max_receipt_concurrency.to_string()),
+            ("scanner.scan_mode", format!("{:?}", self.scan_mode)),
+            ("scanner.state_dir", self.state_dir.as_ref().map_or(String::new(), |p| p.display().to_string())),
+            ("scanner.checkpoint_interval_seconds", self.checkpoint_interval.as_secs().to_string()),
+            ("scanner.block_processing_timeout_ms", self.block_processing_timeout.as_millis().to_string()),
+            ("scanner.http_poll_interval_ms", self.http_poll_interval.as_millis().to_string()),
+            ("scanner.max_block_retries", self.max_block_retries.to_string()),
+            ("scanner.retry_base_delay_ms", self.retry_base_delay.as_millis().to_string()),
+            ("cache.seen_filter_items", self.seen_filter_items.to_string()),
+            ("cache.seen_filter_false_positive_rate", self.seen_filter_false_positive_rate.to_string()),
+            ("cache.seen_filter_generations", self.seen_filter_generations.to_string()),
+            ("pools.resolve_top_pairs", self.resolve_top_pairs.to_string()),
+            ("pools.discover_from_block", self.discover_pools_from_block.map_or("off".to_string(), |b| b.to_string())),
+            ("simulation.backend", self.simulation_backend.map_or("off", SimulationBackend::as_str).to_string()),
+            ("simulation.fork_url", self.simulation_fork_url.clone().unwrap_or_default()),
+            ("strategies.storage_drift", self.strategies.storage_drift.to_string()),
+            ("strategies.arbitrage", self.strategies.arbitrage.to_string()),
+            ("strategies.mempool", self.strategies.mempool.to_string()),
+            ("monitored_contracts", contracts.join(",")),
+        ]
+    }
+
+    /// Checks that several scanners in one process do not share persisted state
+    pub fn check_isolated(configs: &[ScannerConfig]) -> Result<()> {
+        for (i, config) in configs.iter().enumerate() {
+            let Some(dir) = &config.state_dir else { continue };
+            if let Some(other) = configs[..i].iter().find(|c| c.state_dir.as_ref() == Some(dir)) {
+                return Err(anyhow!(
+                    "The {} and {} scanners both use scanner.state_dir {}, give each its own",
+                    other.chain.name, config.chain.name, dir.display()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that span several keys or that no layer set
+    pub fn validate(&self) -> Result<()> {
+        if self.primary_rpc_url.is_empty() {
+            return Err(anyhow!("endpoints.ws_url is required (config file, WS_URL or --set)"));
+        }
+        if !self.primary_rpc_url.starts_with("ws") {
+            return Err(anyhow!("endpoints.ws_url must start with ws:// or wss://, got '{}'", self.primary_rpc_url));
+        }
+        self.validate_http_only()
+    }
+
+    /// `validate` without the WebSocket endpoint
+    pub fn validate_http_only(&self) -> Result<()> {
+        if self.fallback_rpc_url.is_empty() {
+            return Err(anyhow!("endpoints.http_url is required (config file, HTTP_URL or --set)"));
+        }
+        if !self.fallback_rpc_url.starts_with("http") {
+            return Err(anyhow!("endpoints.http_url must start with http:// or https://, got '{}'", self.fallback_rpc_url));
+        }
+        if self.submission_enabled && self.signer.is_read_only() {
+            return Err(anyhow!(
+                "trading.submission_enabled requires a signer: set PRIVATE_KEY, MNEMONIC or signer.keystore"
+            ));
+        }
+        Ok(())
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"
+        monitored_contracts = [
+            "0xB4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc",
+            { address = "0x0d4a11d5EEaaC28EC3F61d100daF4d40471f1852", needs_traces = true },
+        ]
+
+        [endpoints]
+        ws_url = "ws://file"
+        http_url = "http://file"
+
+        [circuit_breaker]
+        threshold = 7
+        failure_rate = 0.5
+        window = "100calls"
+
+        [concurrency]
+        block_pipeline_depth = 8
+
+        [strategies]
+        arbitrage = true
+    "#;
+
+    #[test]
+    fn test_layers_override_in_order() {
+        let mut config = ScannerConfig::default();
+        config.apply_toml(SAMPLE).unwrap();
+        assert_eq!(config.circuit_breaker_threshold(), 7);
+        assert_eq!(config.block_pipeline_depth(), 8);
+        assert_eq!(config.circuit_breaker_window(), FailureWindow::Calls(100));
+        assert!(config.strategies().arbitrage);
+        assert_eq!(config.monitored_contracts().len(), 2);
+        assert!(config.monitored_contracts()[1].needs_traces);
+        // Untouched keys keep their defaults
+        assert_eq!(config.max_receipt_concurrency(), const_and_addr::MAX_RECEIPT_CONCURRENCY);
+
+        // A later layer (env or CLI) wins
+        config.apply_override("circuit_breaker.threshold", "3").unwrap();
+        config.apply_override("circuit_breaker.failure_rate", "off").unwrap();
+        assert_eq!(config.circuit_breaker_threshold(), 3);
+        assert_eq!(config.circuit_breaker_policy(), TripPolicy::ConsecutiveFailures);
+    }
+
+    #[test]
+    fn test_example_config_is_valid() {
+        let mut config = ScannerConfig::default();
+        config.apply_file(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/config.example.toml"))).unwrap();
+        assert_eq!(config.primary_rpc_url(), "ws://127.0.0.1:8546");
+        assert_eq!(config.monitored_contracts().len(), 2);
+    }
+
+    #[test]
+    fn test_errors_name_the_failing_key() {
+        let mut config = ScannerConfig::default();
+        let error = config.apply_override("concurrency.block_pipeline_depth", "0").unwrap_err();
+        assert!(error.to_string().contains("concurrency.block_pipeline_depth"));
+
+        let error = config.apply_override("circuit_breaker.failure_rate", "1.5").unwrap_err();
+        assert!(error.to_string().contains("circuit_breaker.failure_rate"));
+
+        let error = config.apply_toml("[cache]\nseen_filter_itemz = 5").unwrap_err();
+        assert!(error.to_string().contains("cache.seen_filter_itemz"));
+
+        let error = config.apply_toml("monitored_contracts = [\"0x1234\"]").unwrap_err();
+        assert!(error.to_string().contains("monitored_contracts[0]"));
+
+        let error = config.validate().unwrap_err();
+        assert!(error.to_string().contains("endpoints.ws_url"));
+        // Backfills run on the HTTP endpoint alone
+        let error = config.validate_http_only().unwrap_err();
+        assert!(error.to_string().contains("endpoints.http_url"));
+        config.apply_override("endpoints.http_url", "http://node").unwrap();
+        assert!(config.validate_http_only().is_ok());
+    }
+
+    #[test]
+    fn test_scanners_for_different_chains_need_separate_state() {
+        let mut ethereum = ScannerConfig::default();
+        let mut polygon = ScannerConfig::default();
+        polygon.apply_override("chain.profile", "polygon").unwrap();
+        assert_eq!(polygon.chain().chain_id, const_and_addr::POLYGON_CHAIN_ID);
+        assert!(ScannerConfig::check_isolated(&[ethereum.clone(), polygon.clone()]).is_ok());
+
+        ethereum.apply_override("scanner.state_dir", "./state").unwrap();
+        polygon.apply_override("scanner.state_dir", "./state").unwrap();
+        let error = ScannerConfig::check_isolated(&[ethereum, polygon]).unwrap_err();
+        assert!(error.to_string().contains("scanner.state_dir"));
+
+        let error = ScannerConfig::default().apply_override("chain.profile", "solana").unwrap_err();
+        assert!(error.to_string().contains("chain.profile"));
+    }
+
+    #[test]
+    fn test_submission_requires_a_signer() {
+        let mut config = ScannerConfig::default();
+        config.apply_override("endpoints.ws_url", "ws://node").unwrap();
+        config.apply_override("endpoints.http_url", "http://node").unwrap();
+        // Read-only needs no key material at all
+        assert!(config.validate().is_ok());
+
+        config.apply_override("trading.submission_enabled", "true").unwrap();
+        let error = config.validate().unwrap_err();
+        assert!(error.to_string().contains("trading.submission_enabled"));
+
+        config.apply_override("signer.keystore", "key.json").unwrap();
+        config.signer = config.signer_settings.resolve(|_| None).unwrap();
+        assert!(config.validate().is_ok());
+
+        let key = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+        config.signer = config.signer_settings.resolve(|name| (name == "PRIVATE_KEY").then(|| key.to_string())).unwrap();
+        assert!(!format!("{:?}", config).contains(&key[2..]));
+    }
+}
@@ -6,46 +6,46 @@ use tokio::sync::Semaphore;
 use ethers::types::{Address, H256, TransactionReceipt};
 use std::str::FromStr;
 
+use crate::address_book::{AddressBook, ChainAddresses};
+
 
 
 // Network connection
 pub const COOL_DOWN_PERIOD: Duration = Duration::from_secs(30);
 pub const CIRCUIT_BREAKER_THRESHOLD : usize = 5;
+pub const HALF_OPEN_MAX_PROBES: usize = 3;
+pub const CIRCUIT_BREAKER_WINDOW: Duration = Duration::from_secs(60);
+pub const CIRCUIT_BREAKER_MIN_CALLS: usize = 10;
 
 // Tuned for self-hosted nodes (adjust based on your hardware)
 
 pub const MAX_RECEIPT_CONCURRENCY: usize = 150;  // Geth/Erigon can handle 500+ RPC calls
 pub const MAX_LOG_CONCURRENCY: usize = 384;     // Memory-bound processing
 pub const MAX_RPC_INFLIGHT: usize = 400;        // Total concurrent RPCs
+pub const MAX_BLOCK_BATCH_SIZE: usize = 5;      // Blocks fetched ahead during backfill
+pub const BLOCK_PROCESSING_TIMEOUT_MS: u64 = 10_000;
+pub const HTTP_POLL_INTERVAL_MS: u64 = 200;
 
-// Common token addresses on Ethereum mainnet
-pub const WETH_ADDRESS: &str = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2";
-pub const USDC_ADDRESS: &str = "0xA0b86a33E6441E4e8B1e25D88A9b6C3D1B8b9c50";
-pub const USDT_ADDRESS: &str = "0xdAC17F958D2ee523a2206206994597C13D831ec7";
-pub const DAI_ADDRESS: &str = "0x6B175474E89094C44Da98b954EedeAC495271d0F";
-pub const WBTC_ADDRESS: &str = "0x2260FAC5E5542a773Aa44fBCfeDf7C193bc2C599";
-
-// Uniswap V2 addresses
-pub const UNISWAP_V2_FACTORY: &str = "0x5C69bEe701ef814a2B6a3EDD4B1652CB9cc5aA6f";
-pub const UNISWAP_V2_ROUTER: &str = "0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D";
-
-// Uniswap V3 addresses
-pub const UNISWAP_V3_FACTORY: &str = "0x1F98431c8aD98523631AE4a59f267346ea31F984";
-pub const UNISWAP_V3_ROUTER: &str = "0xE592427A0AEce92De3Edee1F18E0157C05861564";
-
-// SushiSwap addresses
-pub const SUSHISWAP_FACTORY: &str = "0xC0AEe478e3658e2610c5F7A4A2E1777cE9e4f2Ac";
-pub const SUSHISWAP_ROUTER: &str = "0xd9e1cE17f2641f24aE83637ab66a2cca9C378B9F";
+// Token, factory, router and pool addresses live in the address book (address_book.toml)
 
 // Event signatures
 pub const SWAP_EVENT_SIGNATURE: &str = "0xd78ad95fa46c994b6551d0da85fc275fe613ce37657fb8d5e3d130840159d822";
 pub const SYNC_EVENT_SIGNATURE: &str = "0x1c411e9a96e071241c2f21f7726b17ae89e3cab4c78be50e062b03a9fffbbad1";
 pub const TRANSFER_EVENT_SIGNATURE: &str = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+pub const V3_SWAP_EVENT_SIGNATURE: &str = "0xc42079f94a6350d7e6235f29174924f928cc2ac818eb64fed8004e115fbcca67";
+pub const V3_MINT_EVENT_SIGNATURE: &str = "0x7a53080ba414158be7ec69b987b5fb7d07dee101fe85488f0853ae16239d0bde";
+pub const V3_BURN_EVENT_SIGNATURE: &str = "0x0c396cd989a39f4459b5fa1aed6a9a8dcdbc45908acfd67e028cd568da98982c";
+pub const PAIR_CREATED_EVENT_SIGNATURE: &str = "0x0d3648bd0f6ba80134a33ba9275ac585d9d315f0ad8355cddefde31afa28d0e9";
+pub const POOL_CREATED_EVENT_SIGNATURE: &str = "0x783cca1c0412dd0d695e784568c96da2e9c22ff989357a2e8b1d9b2b4e6b7118";
 
 // Gas constants
 pub const DEFAULT_GAS_LIMIT: u64 = 300_000;
 pub const PRIORITY_GAS_LIMIT: u64 = 500_000;
 pub const MAX_GAS_PRICE: u64 = 500_000_000_000; // 500 Gwei
+pub const GAS_HISTORY_BLOCKS: u64 = 20; // Blocks of eth_feeHistory behind each estimate
+pub const ARBITRAGE_BASE_GAS: u64 = 50_000; // Transaction and executor overhead of an arbitrage
+pub const V2_SWAP_GAS: u64 = 90_000;
+pub const V3_SWAP_GAS: u64 = 130_000; // More when ticks are crossed
 
 // Storage slot constants for Uniswap V2 pairs
 pub const UNISWAP_V2_RESERVE0_SLOT: u64 = 8;
@@ -73,46 +73,64 @@ pub const DEFAULT_CACHE_SIZE: usize = 10_000;
 pub const DEFAULT_CACHE_TTL_SECONDS: u64 = 300;
 pub const SLOT_CACHE_SIZE: usize = 100_000;
 
+// Dedupe filter for seen transactions and logs (~1 day of mainnet traffic per generation)
+pub const SEEN_FILTER_ITEMS_PER_GENERATION: usize = 1_000_000;
+pub const SEEN_FILTER_FALSE_POSITIVE_RATE: f64 = 1e-6;
+pub const SEEN_FILTER_GENERATIONS: usize = 3;
+pub const CHECKPOINT_INTERVAL_SECONDS: u64 = 30;
+
 // Retry constants
 pub const MAX_RETRIES: usize = 3;
 pub const RETRY_DELAY_MS: u64 = 1000;
 pub const BACKOFF_MULTIPLIER: f64 = 2.0;
 
-// Helper functions to convert string addresses to Address type
+// Mainnet addresses from the embedded address book
+fn mainnet() -> &'static ChainAddresses {
+    AddressBook::embedded().chain("ethereum").expect("embedded address book has an ethereum section")
+}
+
+fn mainnet_token(symbol: &str) -> Address {
+    mainnet().token(symbol).map(|t| t.address).unwrap_or_else(|| panic!("{} missing from address book", symbol))
+}
+
+fn mainnet_factory(name: &str) -> Address {
+    mainnet().factory(name).map(|f| f.address).unwrap_or_else(|| panic!("{} missing from address book", name))
+}
+
 pub fn weth() -> Address {
-    Address::from_str(WETH_ADDRESS).unwrap()
+    mainnet().wrapped_native()
 }
 
 pub fn usdc() -> Address {
-    Address::from_str(USDC_ADDRESS).unwrap()
+    mainnet_token("USDC")
 }
 
 pub fn usdt() -> Address {
-    Address::from_str(USDT_ADDRESS).unwrap()
+    mainnet_token("USDT")
 }
 
 pub fn dai() -> Address {
-    Address::from_str(DAI_ADDRESS).unwrap()
+    mainnet_token("DAI")
 }
 
 pub fn wbtc() -> Address {
-    Address::from_str(WBTC_ADDRESS).unwrap()
+    mainnet_token("WBTC")
 }
 
 pub fn uniswap_v2_factory() -> Address {
-    Address::from_str(UNISWAP_V2_FACTORY).unwrap()
+    mainnet_factory("uniswap_v2")
 }
 
 pub fn uniswap_v2_router() -> Address {
-    Address::from_str(UNISWAP_V2_ROUTER).unwrap()
+    mainnet().router("uniswap_v2").map(|r| r.address).expect("uniswap_v2 missing from address book")
 }
 
 pub fn uniswap_v3_factory() -> Address {
-    Address::from_str(UNISWAP_V3_FACTORY).unwrap()
+    mainnet_factory("uniswap_v3")
 }
 
 pub fn sushiswap_factory() -> Address {
-    Address::from_str(SUSHISWAP_FACTORY).unwrap()
+    mainnet_factory("sushiswap")
 }
 
 // Event signature helpers
@@ -128,25 +146,34 @@ pub fn transfer_event_signature() -> H256 {
     H256::from_str(TRANSFER_EVENT_SIGNATURE).unwrap()
 }
 
+pub fn v3_swap_event_signature() -> H256 {
+    H256::from_str(V3_SWAP_EVENT_SIGNATURE).unwrap()
+}
+
+pub fn v3_mint_event_signature() -> H256 {
+    H256::from_str(V3_MINT_EVENT_SIGNATURE).unwrap()
+}
+
+pub fn v3_burn_event_signature() -> H256 {
+    H256::from_str(V3_BURN_EVENT_SIGNATURE).unwrap()
+}
+
+pub fn pair_created_event_signature() -> H256 {
+    H256::from_str(PAIR_CREATED_EVENT_SIGNATURE).unwrap()
+}
+
+pub fn pool_created_event_signature() -> H256 {
+    H256::from_str(POOL_CREATED_EVENT_SIGNATURE).unwrap()
+}
+
 // Top trading pairs on Ethereum
 pub fn get_top_pairs() -> Vec<(Address, Address)> {
-    vec![
-        (weth(), usdc()),
-        (weth(), usdt()),
-        (weth(), dai()),
-        (usdc(), usdt()),
-        (usdc(), dai()),
-        (weth(), wbtc()),
-    ]
+    mainnet().top_pairs().to_vec()
 }
 
 // Common DEX factory addresses
 pub fn get_dex_factories() -> Vec<Address> {
-    vec![
-        uniswap_v2_factory(),
-        sushiswap_factory(),
-        // Add more DEX factories as needed
-    ]
+    mainnet().factory_addresses()
 }
 
 // Gas price tiers
@@ -160,4 +187,24 @@ pub const UNISWAP_V2_FEE: u16 = 30; // 0.3%
 pub const SUSHISWAP_FEE: u16 = 30; // 0.3%
 pub const UNISWAP_V3_FEE_LOW: u16 = 5; // 0.05%
 pub const UNISWAP_V3_FEE_MEDIUM: u16 = 30; // 0.3%
-pub const UNISWAP_V3_FEE_HIGH: u16 = 100; // 1%
\ No newline at end of file
+pub const UNISWAP_V3_FEE_HIGH: u16 = 100; // 1%
+
+// Uniswap V3 fee tiers as passed to `getPool` (hundredths of a basis point)
+pub const UNISWAP_V3_FEE_TIERS: [u32; 4] = [100, 500, 3_000, 10_000];
+
+// Pool discovery
+pub const POOL_DISCOVERY_BLOCK_RANGE: u64 = 10_000; // Blocks per eth_getLogs request
+// Log-filter scanning
+pub const LOG_FILTER_BLOCK_RANGE: u64 = 2_000; // Blocks per eth_getLogs request, halved while the node rejects it
+// Arbitrage search
+pub const V3_TICK_WORD_RADIUS: i16 = 2; // Tick bitmap words loaded on each side of the current price
+pub const ARBITRAGE_SEARCH_ITERATIONS: usize = 128; // Bisection/ternary steps when sizing a trade
+pub const MAX_RECENT_OPPORTUNITIES: usize = 1_000;
+pub const ARBITRAGE_MAX_HOPS: usize = 3; // Default swaps per arbitrage cycle
+pub const ARBITRAGE_HOPS_LIMIT: usize = 5; // Cycle search grows exponentially with depth
+pub const MAX_CYCLE_CANDIDATES: usize = 32; // Best cycles sized per search
+pub const SIMULATION_SEARCHER: Address = ethers::types::H160([0x5e; 20]); // Sender of simulated bundles without a signer
+// Mempool
+pub const MAX_PENDING_SWAPS: usize = 10_000; // Decoded pending transactions kept at once
+pub const PENDING_SWAP_TTL_SECONDS: u64 = 120; // Pending swaps not mined by then are dropped
+pub const MEMPOOL_FETCH_CONCURRENCY: usize = 16; // Transactions fetched at once when the node only sends hashes
@@ -0,0 +1,5 @@
+// ! Gas module - EIP-1559 fee estimation from recent blocks
+
+pub mod oracle;
+
+pub use oracle::{GasEstimate, GasOracle, GasTier, next_base_fee};
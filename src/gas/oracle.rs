@@ -0,0 +1,263 @@
+//! EIP-1559 gas price oracle
+//!
+//! `eth_feeHistory` over the last `GAS_HISTORY_BLOCKS` blocks gives the base fee and the
+//! priority fees paid at a few percentiles. The base fee of the next block follows from
+//! the latest header, so block headers roll the estimate forward between history calls.
+//! Until the first estimate the static `GAS_PRICE_*` tiers stand in.
+use std::str::FromStr;
+use std::sync::RwLock;
+use anyhow::{anyhow, Context, Result};
+use ethers::{
+    providers::Middleware,
+    types::{Block, BlockNumber, FeeHistory, U256},
+};
+use serde::Serialize;
+use tracing::debug;
+
+use crate::const_and_addr;
+
+/// `BASE_FEE_MAX_CHANGE_DENOMINATOR`, the base fee moves by at most 1/8 per block
+const BASE_FEE_CHANGE_DENOMINATOR: u64 = 8;
+/// `ELASTICITY_MULTIPLIER`, blocks target half their gas limit
+const ELASTICITY_MULTIPLIER: u64 = 2;
+
+/// How much priority fee a transaction offers, as a percentile of recent inclusions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum GasTier {
+    Slow,
+    Standard,
+    Fast,
+    Instant,
+}
+
+impl GasTier {
+    pub const ALL: [GasTier; 4] = [GasTier::Slow, GasTier::Standard, GasTier::Fast, GasTier::Instant];
+
+    /// Reward percentile requested from `eth_feeHistory`
+    pub fn percentile(self) -> f64 {
+        match self {
+            GasTier::Slow => 10.0,
+            GasTier::Standard => 50.0,
+            GasTier::Fast => 75.0,
+            GasTier::Instant => 95.0,
+        }
+    }
+
+    /// Static gas price used before the first estimate
+    pub fn fallback_price(self) -> U256 {
+        U256::from(match self {
+            GasTier::Slow => const_and_addr::GAS_PRICE_SLOW,
+            GasTier::Standard => const_and_addr::GAS_PRICE_STANDARD,
+            GasTier::Fast => const_and_addr::GAS_PRICE_FAST,
+            GasTier::Instant => const_and_addr::GAS_PRICE_INSTANT,
+        })
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl FromStr for GasTier {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "slow" => Ok(GasTier::Slow),
+            "standard" => Ok(GasTier::Standard),
+            "fast" => Ok(GasTier::Fast),
+            "instant" => Ok(GasTier::Instant),
+            other => Err(anyhow!("Unknown gas tier '{}', expected slow, standard, fast or instant", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct GasEstimate {
+    /// Latest block the estimate has seen
+    pub block_number: u64,
+    pub base_fee: U256,
+    /// Base fee of the block after `block_number`
+    pub next_base_fee: U256,
+    /// Median priority fee per tier, in `GasTier::ALL` order
+    pub priority_fees: [U256; 4],
+}
+
+impl GasEstimate {
+    /// Estimate from an `eth_feeHistory` response requested with every tier's percentile
+    pub fn from_fee_history(history: &FeeHistory) -> Option<Self> {
+        let blocks = history.gas_used_ratio.len();
+        if blocks == 0 || history.base_fee_per_gas.len() != blocks + 1 {
+            return None;
+        }
+        let block_number = (history.oldest_block + blocks - 1).low_u64();
+
+        // Empty blocks report zero rewards, they say nothing about the going rate
+        let mut rewards: Vec<&Vec<U256>> = history.reward.iter()
+            .zip(&history.gas_used_ratio)
+            .filter(|(_, ratio)| **ratio > 0.0)
+            .map(|(reward, _)| reward)
+            .collect();
+        if rewards.is_empty() {
+            rewards = history.reward.iter().collect();
+        }
+        let mut priority_fees = [U256::zero(); 4];
+        for tier in GasTier::ALL {
+            let mut fees: Vec<U256> = rewards.iter().filter_map(|reward| reward.get(tier.index()).copied()).collect();
+            fees.sort();
+            priority_fees[tier.index()] = fees.get(fees.len() / 2).copied().unwrap_or_default();
+        }
+
+        Some(Self {
+            block_number,
+            base_fee: history.base_fee_per_gas[blocks - 1],
+            next_base_fee: history.base_fee_per_gas[blocks],
+            priority_fees,
+        })
+    }
+
+    pub fn priority_fee(&self, tier: GasTier) -> U256 {
+        self.priority_fees[tier.index()]
+    }
+
+    /// Price per gas a transaction in the next block pays at `tier`, capped at `MAX_GAS_PRICE`
+    pub fn gas_price(&self, tier: GasTier) -> U256 {
+        (self.next_base_fee + self.priority_fee(tier)).min(const_and_addr::MAX_GAS_PRICE.into())
+    }
+
+    /// `maxFeePerGas` that stays valid through several full blocks of rising base fee
+    pub fn max_fee_per_gas(&self, tier: GasTier) -> U256 {
+        (self.next_base_fee * U256::from(2) + self.priority_fee(tier)).min(const_and_addr::MAX_GAS_PRICE.into())
+    }
+}
+
+/// Base fee of the block after one with `base_fee`, `gas_used` and `gas_limit`
+pub fn next_base_fee(base_fee: U256, gas_used: U256, gas_limit: U256) -> U256 {
+    let target = gas_limit / ELASTICITY_MULTIPLIER;
+    if target.is_zero() || gas_used == target {
+        return base_fee;
+    }
+    if gas_used > target {
+        let change = base_fee * (gas_used - target) / target / BASE_FEE_CHANGE_DENOMINATOR;
+        base_fee + change.max(U256::one())
+    } else {
+        let change = base_fee * (target - gas_used) / target / BASE_FEE_CHANGE_DENOMINATOR;
+        base_fee.saturating_sub(change)
+    }
+}
+
+pub struct GasOracle {
+    tier: GasTier,
+    estimate: RwLock<Option<GasEstimate>>,
+}
+
+impl GasOracle {
+    pub fn new(tier: GasTier) -> Self {
+        Self { tier, estimate: RwLock::new(None) }
+    }
+
+    pub fn tier(&self) -> GasTier {
+        self.tier
+    }
+
+    pub fn estimate(&self) -> Option<GasEstimate> {
+        *self.estimate.read().unwrap()
+    }
+
+    pub fn set_estimate(&self, estimate: GasEstimate) {
+        *self.estimate.write().unwrap() = Some(estimate);
+    }
+
+    /// Whether the estimate misses the block before `block_number`
+    pub fn is_stale(&self, block_number: u64) -> bool {
+        self.estimate().is_none_or(|estimate| estimate.block_number + 1 < block_number)
+    }
+
+    /// Fetches the fee history of recent blocks and replaces the estimate
+    pub async fn refresh<M: Middleware + 'static>(&self, provider: &M) -> Result<GasEstimate> {
+        let percentiles = GasTier::ALL.map(GasTier::percentile);
+        let history = provider
+            .fee_history(const_and_addr::GAS_HISTORY_BLOCKS, BlockNumber::Latest, &percentiles)
+            .await
+            .context("eth_feeHistory failed")?;
+        let estimate = GasEstimate::from_fee_history(&history)
+            .ok_or_else(|| anyhow!("Malformed eth_feeHistory response"))?;
+        debug!("⛽ Base fee {} -> {} after block {}, {:?} tip {}",
+            estimate.base_fee, estimate.next_base_fee, estimate.block_number, self.tier, estimate.priority_fee(self.tier));
+        self.set_estimate(estimate);
+        Ok(estimate)
+    }
+
+    /// Rolls the estimate forward to a newer block header, keeping the priority fees.
+    /// Needs a first estimate from `refresh` and a post-London header.
+    pub fn observe_block<T>(&self, block: &Block<T>) -> bool {
+        let (Some(number), Some(base_fee)) = (block.number, block.base_fee_per_gas) else {
+            return false;
+        };
+        let mut estimate = self.estimate.write().unwrap();
+        let Some(current) = estimate.as_mut() else {
+            return false;
+        };
+        if number.as_u64() <= current.block_number {
+            return false;
+        }
+        current.block_number = number.as_u64();
+        current.base_fee = base_fee;
+        current.next_base_fee = next_base_fee(base_fee, block.gas_used, block.gas_limit);
+        true
+    }
+
+    /// Price per gas at the oracle's tier, the static tier price until the first estimate
+    pub fn gas_price(&self) -> U256 {
+        self.estimate().map_or_else(|| self.tier.fallback_price(), |estimate| estimate.gas_price(self.tier))
+    }
+
+    /// Cost of `gas` units in wei
+    pub fn gas_cost(&self, gas: u64) -> U256 {
+        self.gas_price() * gas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_base_fee_follows_eip1559() {
+        let base_fee = U256::from(100_000_000_000u64);
+        let limit = U256::from(30_000_000u64);
+        assert_eq!(next_base_fee(base_fee, U256::from(15_000_000u64), limit), base_fee);
+        // Full blocks raise it by 12.5%, empty ones lower it by 12.5%
+        assert_eq!(next_base_fee(base_fee, limit, limit), U256::from(112_500_000_000u64));
+        assert_eq!(next_base_fee(base_fee, U256::zero(), limit), U256::from(87_500_000_000u64));
+        // Any excess raises it by at least 1 wei
+        assert_eq!(next_base_fee(U256::from(7u64), U256::from(15_000_001u64), limit), U256::from(8u64));
+    }
+
+    #[test]
+    fn test_estimate_from_fee_history() {
+        let gwei = |n: u64| U256::from(n) * U256::exp10(9);
+        let history = FeeHistory {
+            base_fee_per_gas: vec![gwei(10), gwei(11), gwei(12), gwei(13)],
+            gas_used_ratio: vec![0.9, 0.0, 0.6],
+            oldest_block: U256::from(100u64),
+            reward: vec![
+                vec![gwei(1), gwei(2), gwei(3), gwei(10)],
+                vec![U256::zero(); 4],
+                vec![gwei(1), gwei(4), gwei(5), gwei(20)],
+            ],
+        };
+        let estimate = GasEstimate::from_fee_history(&history).unwrap();
+        assert_eq!(estimate.block_number, 102);
+        assert_eq!((estimate.base_fee, estimate.next_base_fee), (gwei(12), gwei(13)));
+        // The empty block is left out of the medians
+        assert_eq!(estimate.priority_fee(GasTier::Standard), gwei(4));
+        assert_eq!(estimate.gas_price(GasTier::Instant), gwei(33));
+
+        let oracle = GasOracle::new(GasTier::Standard);
+        assert_eq!(oracle.gas_price(), GasTier::Standard.fallback_price());
+        oracle.set_estimate(estimate);
+        assert_eq!(oracle.gas_cost(100_000), gwei(17) * 100_000u64);
+        assert!(!oracle.is_stale(103) && oracle.is_stale(104));
+    }
+}
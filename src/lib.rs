@@ -0,0 +1,19 @@
+//! Ethereum MEV detection client
+//!
+//! Library crate behind the `rust_marathon` binary. Exposes the scanner, the storage
+//! drift detector and configuration so they can be driven from benches and tools.
+
+pub mod scanner;
+pub mod arbitrage;
+pub mod storage;
+pub mod pools;
+pub mod pricing;
+pub mod gas;
+pub mod simulation;
+pub mod mempool;
+pub mod address_book;
+pub mod chain;
+pub mod config;
+pub mod signer;
+pub mod macros;
+pub mod const_and_addr;
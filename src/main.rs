@@ -1,29 +1,21 @@
 
-mod scanner;
-mod storage;
-mod config;
-mod macros; 
-mod const_and_addr;
-
-
-use config::ScannerConfig;
 use anyhow::Result;
+use clap::Parser;
 use dotenv::dotenv;
 
-use crate::scanner::MevScanner;
-use crate::storage::StorageDriftDetector;
-use crate::storage::SimpleStateCache;
+mod cli;
+
+use cli::Cli;
 
 #[tokio::main]
 async fn main () -> Result<()>{
 
-    // Initialize logging
-    tracing_subscriber::fmt::init();
+    // Initialize logging (stdout is reserved for command output)
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .init();
    
     // Load configuration
     dotenv().ok();
-    let config = ScannerConfig::from_env()?;
-    let scanner = MevScanner::new(config).await?;
-    Ok(())
-
-}
\ No newline at end of file
+    Cli::parse().run().await
+}
@@ -0,0 +1,396 @@
+//! Uniswap V2/V3 router and pool calldata, decoded into swaps
+//!
+//! Covers the V2 router (`UniswapV2Router02` and its Sushiswap fork), the V3
+//! `SwapRouter` and `SwapRouter02`, whose V3 structs drop the deadline and which also
+//! routes V2 swaps, and the `multicall` variants both routers batch swaps with. Calls
+//! straight to a pool's `swap` are decoded against the pool's tokens.
+use std::collections::HashMap;
+use ethers::{
+    abi::{self, ParamType, Token, param_type::Reader},
+    types::{Address, I256, U256},
+    utils::id,
+};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::{address_book::DexKind, pools::PoolState};
+
+/// Multicalls nested deeper than this are not unpacked
+const MAX_MULTICALL_DEPTH: usize = 2;
+/// Encoded V3 path: a token, then a 3-byte fee and a token per hop
+const V3_PATH_TOKEN: usize = 20;
+const V3_PATH_HOP: usize = 23;
+
+/// Argument layouts, `deadline` marks the `SwapRouter` structs that carry one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RouterFunction {
+    /// `(amountIn, amountOutMin, path, to, ..)`
+    V2ExactIn,
+    /// `(amountOut, amountInMax, path, to, ..)`
+    V2ExactOut,
+    /// `(amountOutMin, path, to, deadline)`, paid in ETH
+    V2ExactEthIn,
+    /// `(amountOut, path, to, deadline)`, paid in ETH with the excess refunded
+    V2EthForExactOut,
+    V3ExactInputSingle { deadline: bool },
+    V3ExactOutputSingle { deadline: bool },
+    V3ExactInput { deadline: bool },
+    V3ExactOutput { deadline: bool },
+    /// The last argument holds the batched calls
+    Multicall,
+}
+
+const ROUTER_FUNCTIONS: [(&str, RouterFunction); 20] = [
+    ("swapExactTokensForTokens(uint256,uint256,address[],address,uint256)", RouterFunction::V2ExactIn),
+    ("swapExactTokensForETH(uint256,uint256,address[],address,uint256)", RouterFunction::V2ExactIn),
+    ("swapExactTokensForTokensSupportingFeeOnTransferTokens(uint256,uint256,address[],address,uint256)", RouterFunction::V2ExactIn),
+    ("swapExactTokensForETHSupportingFeeOnTransferTokens(uint256,uint256,address[],address,uint256)", RouterFunction::V2ExactIn),
+    ("swapExactTokensForTokens(uint256,uint256,address[],address)", RouterFunction::V2ExactIn),
+    ("swapTokensForExactTokens(uint256,uint256,address[],address,uint256)", RouterFunction::V2ExactOut),
+    ("swapTokensForExactETH(uint256,uint256,address[],address,uint256)", RouterFunction::V2ExactOut),
+    ("swapTokensForExactTokens(uint256,uint256,address[],address)", RouterFunction::V2ExactOut),
+    ("swapExactETHForTokens(uint256,address[],address,uint256)", RouterFunction::V2ExactEthIn),
+    ("swapExactETHForTokensSupportingFeeOnTransferTokens(uint256,address[],address,uint256)", RouterFunction::V2ExactEthIn),
+    ("swapETHForExactTokens(uint256,address[],address,uint256)", RouterFunction::V2EthForExactOut),
+    ("exactInputSingle((address,address,uint24,address,uint256,uint256,uint256,uint160))", RouterFunction::V3ExactInputSingle { deadline: true }),
+    ("exactInputSingle((address,address,uint24,address,uint256,uint256,uint160))", RouterFunction::V3ExactInputSingle { deadline: false }),
+    ("exactOutputSingle((address,address,uint24,address,uint256,uint256,uint256,uint160))", RouterFunction::V3ExactOutputSingle { deadline: true }),
+    ("exactOutputSingle((address,address,uint24,address,uint256,uint256,uint160))", RouterFunction::V3ExactOutputSingle { deadline: false }),
+    ("exactInput((bytes,address,uint256,uint256,uint256))", RouterFunction::V3ExactInput { deadline: true }),
+    ("exactInput((bytes,address,uint256,uint256))", RouterFunction::V3ExactInput { deadline: false }),
+    ("exactOutput((bytes,address,uint256,uint256,uint256))", RouterFunction::V3ExactOutput { deadline: true }),
+    ("exactOutput((bytes,address,uint256,uint256))", RouterFunction::V3ExactOutput { deadline: false }),
+    ("multicall(bytes[])", RouterFunction::Multicall),
+];
+
+/// `SwapRouter02` multicalls that check a deadline or the parent block hash first
+const MULTICALL_VARIANTS: [&str; 2] = ["multicall(uint256,bytes[])", "multicall(bytes32,bytes[])"];
+
+const V2_PAIR_SWAP: &str = "swap(uint256,uint256,address,bytes)";
+const V3_POOL_SWAP: &str = "swap(address,bool,int256,uint160,bytes)";
+
+/// Layout and argument types of a router function
+type RouterSignature = (RouterFunction, Vec<ParamType>);
+
+static ROUTER_SELECTORS: Lazy<HashMap<[u8; 4], RouterSignature>> = Lazy::new(|| {
+    ROUTER_FUNCTIONS.iter()
+        .copied()
+        .chain(MULTICALL_VARIANTS.iter().map(|signature| (*signature, RouterFunction::Multicall)))
+        .map(|(signature, function)| (id(signature), (function, argument_types(signature))))
+        .collect()
+});
+
+/// How much goes in or comes out of a swap, and the bound on the other side
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum SwapAmount {
+    ExactIn { amount_in: U256, min_amount_out: U256 },
+    /// `max_amount_in` is `U256::MAX` when the call does not bound it
+    ExactOut { amount_out: U256, max_amount_in: U256 },
+}
+
+/// One swap along a token path, possibly over several pools
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SwapCall {
+    pub dex: DexKind,
+    /// The pool when it was called directly, routers pick pools from the path
+    pub pool: Option<Address>,
+    /// Tokens from input to output
+    pub path: Vec<Address>,
+    /// V3 fee tier of each hop, empty for V2
+    pub fees: Vec<u32>,
+    pub amount: SwapAmount,
+    pub recipient: Address,
+}
+
+impl SwapCall {
+    pub fn token_in(&self) -> Address {
+        self.path[0]
+    }
+
+    pub fn token_out(&self) -> Address {
+        self.path[self.path.len() - 1]
+    }
+
+    pub fn hops(&self) -> usize {
+        self.path.len() - 1
+    }
+}
+
+/// Swaps in a router call, in execution order. `value` is the ETH sent along, which
+/// ETH-paying V2 swaps spend. Unknown functions and malformed calldata give none.
+pub fn decode_router_call(data: &[u8], value: U256) -> Vec<SwapCall> {
+    let mut swaps = Vec::new();
+    decode_calls(data, value, 0, &mut swaps);
+    swaps
+}
+
+fn decode_calls(data: &[u8], value: U256, depth: usize, swaps: &mut Vec<SwapCall>) {
+    let Some((selector, arguments)) = data.split_first_chunk::<4>() else {
+        return;
+    };
+    let Some((function, types)) = ROUTER_SELECTORS.get(selector) else {
+        return;
+    };
+    let Ok(tokens) = abi::decode(types, arguments) else {
+        return;
+    };
+    if *function == RouterFunction::Multicall {
+        if depth < MAX_MULTICALL_DEPTH && let Some(Token::Array(calls)) = tokens.last() {
+            for call in calls {
+                if let Token::Bytes(call) = call {
+                    decode_calls(call, value, depth + 1, swaps);
+                }
+            }
+        }
+        return;
+    }
+    if let Some(swap) = decode_swap(*function, &tokens, value) {
+        swaps.push(swap);
+    }
+}
+
+fn decode_swap(function: RouterFunction, tokens: &[Token], value: U256) -> Option<SwapCall> {
+    match function {
+        RouterFunction::V2ExactIn => v2_swap(
+            address_array(tokens.get(2)?)?,
+            SwapAmount::ExactIn { amount_in: uint(tokens.first()?)?, min_amount_out: uint(tokens.get(1)?)? },
+            tokens.get(3)?.clone().into_address()?,
+        ),
+        RouterFunction::V2ExactOut => v2_swap(
+            address_array(tokens.get(2)?)?,
+            SwapAmount::ExactOut { amount_out: uint(tokens.first()?)?, max_amount_in: uint(tokens.get(1)?)? },
+            tokens.get(3)?.clone().into_address()?,
+        ),
+        RouterFunction::V2ExactEthIn => v2_swap(
+            address_array(tokens.get(1)?)?,
+            SwapAmount::ExactIn { amount_in: value, min_amount_out: uint(tokens.first()?)? },
+            tokens.get(2)?.clone().into_address()?,
+        ),
+        RouterFunction::V2EthForExactOut => v2_swap(
+            address_array(tokens.get(1)?)?,
+            SwapAmount::ExactOut { amount_out: uint(tokens.first()?)?, max_amount_in: value },
+            tokens.get(2)?.clone().into_address()?,
+        ),
+        RouterFunction::V3ExactInputSingle { deadline } | RouterFunction::V3ExactOutputSingle { deadline } => {
+            let params = tokens.first()?.clone().into_tuple()?;
+            let skip = usize::from(deadline);
+            let (amount, bound) = (uint(params.get(4 + skip)?)?, uint(params.get(5 + skip)?)?);
+            let amount = match function {
+                RouterFunction::V3ExactInputSingle { .. } => SwapAmount::ExactIn { amount_in: amount, min_amount_out: bound },
+                _ => SwapAmount::ExactOut { amount_out: amount, max_amount_in: bound },
+            };
+            Some(SwapCall {
+                dex: DexKind::UniswapV3,
+                pool: None,
+                path: vec![params.first()?.clone().into_address()?, params.get(1)?.clone().into_address()?],
+                fees: vec![uint(params.get(2)?)?.low_u32()],
+                amount,
+                recipient: params.get(3)?.clone().into_address()?,
+            })
+        }
+        RouterFunction::V3ExactInput { deadline } | RouterFunction::V3ExactOutput { deadline } => {
+            let params = tokens.first()?.clone().into_tuple()?;
+            let skip = usize::from(deadline);
+            let (mut path, mut fees) = decode_v3_path(&params.first()?.clone().into_bytes()?)?;
+            let (amount, bound) = (uint(params.get(2 + skip)?)?, uint(params.get(3 + skip)?)?);
+            let amount = match function {
+                RouterFunction::V3ExactInput { .. } => SwapAmount::ExactIn { amount_in: amount, min_amount_out: bound },
+                _ => {
+                    // Exact output paths are encoded from the output token back
+                    path.reverse();
+                    fees.reverse();
+                    SwapAmount::ExactOut { amount_out: amount, max_amount_in: bound }
+                }
+            };
+            Some(SwapCall {
+                dex: DexKind::UniswapV3,
+                pool: None,
+                path,
+                fees,
+                amount,
+                recipient: params.get(1)?.clone().into_address()?,
+            })
+        }
+        RouterFunction::Multicall => None,
+    }
+}
+
+fn v2_swap(path: Vec<Address>, amount: SwapAmount, recipient: Address) -> Option<SwapCall> {
+    (path.len() >= 2).then(|| SwapCall { dex: DexKind::UniswapV2, pool: None, path, fees: Vec::new(), amount, recipient })
+}
+
+/// The swap in a direct call to `pool`'s `swap`, `None` for any other call
+pub fn decode_pool_call(pool: &PoolState, data: &[u8]) -> Option<SwapCall> {
+    let (selector, arguments) = data.split_first_chunk::<4>()?;
+    match pool.kind {
+        DexKind::UniswapV2 if *selector == id(V2_PAIR_SWAP) => {
+            let tokens = abi::decode(&argument_types(V2_PAIR_SWAP), arguments).ok()?;
+            let (amount0_out, amount1_out) = (uint(tokens.first()?)?, uint(tokens.get(1)?)?);
+            // The pair checks the input already transferred, the call only names outputs
+            let (path, amount_out) = match (amount0_out.is_zero(), amount1_out.is_zero()) {
+                (true, false) => (vec![pool.token0, pool.token1], amount1_out),
+                (false, true) => (vec![pool.token1, pool.token0], amount0_out),
+                _ => return None,
+            };
+            Some(SwapCall {
+                dex: DexKind::UniswapV2,
+                pool: Some(pool.address),
+                path,
+                fees: Vec::new(),
+                amount: SwapAmount::ExactOut { amount_out, max_amount_in: U256::MAX },
+                recipient: tokens.get(2)?.clone().into_address()?,
+            })
+        }
+        DexKind::UniswapV3 if *selector == id(V3_POOL_SWAP) => {
+            let tokens = abi::decode(&argument_types(V3_POOL_SWAP), arguments).ok()?;
+            let zero_for_one = tokens.get(1)?.clone().into_bool()?;
+            let amount_specified = I256::from_raw(tokens.get(2)?.clone().into_int()?);
+            let path = if zero_for_one { vec![pool.token0, pool.token1] } else { vec![pool.token1, pool.token0] };
+            // Positive amounts are exact input, negative ones exact output
+            let amount = if amount_specified.is_negative() {
+                SwapAmount::ExactOut { amount_out: amount_specified.unsigned_abs(), max_amount_in: U256::MAX }
+            } else {
+                SwapAmount::ExactIn { amount_in: amount_specified.into_raw(), min_amount_out: U256::zero() }
+            };
+            Some(SwapCall {
+                dex: DexKind::UniswapV3,
+                pool: Some(pool.address),
+                path,
+                fees: vec![pool.fee],
+                amount,
+                recipient: tokens.first()?.clone().into_address()?,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Tokens and fee tiers of an encoded V3 path, in encoding order
+pub fn decode_v3_path(path: &[u8]) -> Option<(Vec<Address>, Vec<u32>)> {
+    if path.len() < V3_PATH_TOKEN + V3_PATH_HOP || !(path.len() - V3_PATH_TOKEN).is_multiple_of(V3_PATH_HOP) {
+        return None;
+    }
+    let mut tokens = vec![Address::from_slice(&path[..V3_PATH_TOKEN])];
+    let mut fees = Vec::new();
+    for hop in path[V3_PATH_TOKEN..].chunks_exact(V3_PATH_HOP) {
+        fees.push(u32::from_be_bytes([0, hop[0], hop[1], hop[2]]));
+        tokens.push(Address::from_slice(&hop[3..]));
+    }
+    Some((tokens, fees))
+}
+
+/// Argument types of a function signature
+fn argument_types(signature: &str) -> Vec<ParamType> {
+    let arguments = &signature[signature.find('(').expect("signature has arguments")..];
+    match Reader::read(arguments) {
+        Ok(ParamType::Tuple(types)) => types,
+        _ => panic!("invalid signature {}", signature),
+    }
+}
+
+fn uint(token: &Token) -> Option<U256> {
+    token.clone().into_uint()
+}
+
+fn address_array(token: &Token) -> Option<Vec<Address>> {
+    token.clone().into_array()?.into_iter().map(Token::into_address).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(signature: &str, args: &[Token]) -> Vec<u8> {
+        let mut data = id(signature).to_vec();
+        data.extend(abi::encode(args));
+        data
+    }
+
+    fn v3_path(tokens: &[Address], fees: &[u32]) -> Vec<u8> {
+        let mut path = tokens[0].as_bytes().to_vec();
+        for (fee, token) in fees.iter().zip(&tokens[1..]) {
+            path.extend(&fee.to_be_bytes()[1..]);
+            path.extend(token.as_bytes());
+        }
+        path
+    }
+
+    #[test]
+    fn test_decodes_v2_and_v3_router_swaps() {
+        let (a, b, c, to) = (Address::repeat_byte(0xa), Address::repeat_byte(0xb), Address::repeat_byte(0xc), Address::repeat_byte(0xee));
+        let path = Token::Array(vec![Token::Address(a), Token::Address(b)]);
+
+        let data = call("swapExactTokensForTokens(uint256,uint256,address[],address,uint256)",
+            &[Token::Uint(100.into()), Token::Uint(90.into()), path.clone(), Token::Address(to), Token::Uint(U256::MAX)]);
+        assert_eq!(decode_router_call(&data, U256::zero()), vec![SwapCall {
+            dex: DexKind::UniswapV2,
+            pool: None,
+            path: vec![a, b],
+            fees: Vec::new(),
+            amount: SwapAmount::ExactIn { amount_in: 100.into(), min_amount_out: 90.into() },
+            recipient: to,
+        }]);
+
+        // ETH-paying swaps spend the transaction value
+        let data = call("swapExactETHForTokens(uint256,address[],address,uint256)",
+            &[Token::Uint(90.into()), path, Token::Address(to), Token::Uint(U256::MAX)]);
+        assert_eq!(decode_router_call(&data, 7.into())[0].amount, SwapAmount::ExactIn { amount_in: 7.into(), min_amount_out: 90.into() });
+
+        let data = call("exactInput((bytes,address,uint256,uint256,uint256))", &[Token::Tuple(vec![
+            Token::Bytes(v3_path(&[a, b, c], &[500, 3_000])),
+            Token::Address(to),
+            Token::Uint(U256::MAX),
+            Token::Uint(100.into()),
+            Token::Uint(80.into()),
+        ])]);
+        let swap = &decode_router_call(&data, U256::zero())[0];
+        assert_eq!((swap.path.clone(), swap.fees.clone(), swap.hops()), (vec![a, b, c], vec![500, 3_000], 2));
+
+        // Exact output paths run backwards
+        let data = call("exactOutput((bytes,address,uint256,uint256))", &[Token::Tuple(vec![
+            Token::Bytes(v3_path(&[c, b, a], &[3_000, 500])),
+            Token::Address(to),
+            Token::Uint(50.into()),
+            Token::Uint(120.into()),
+        ])]);
+        let swap = &decode_router_call(&data, U256::zero())[0];
+        assert_eq!((swap.token_in(), swap.token_out(), swap.fees.clone()), (a, c, vec![500, 3_000]));
+        assert_eq!(swap.amount, SwapAmount::ExactOut { amount_out: 50.into(), max_amount_in: 120.into() });
+
+        assert!(decode_router_call(&id("deposit()"), U256::zero()).is_empty());
+        assert!(decode_router_call(&data[..40], U256::zero()).is_empty());
+    }
+
+    #[test]
+    fn test_unpacks_multicall_and_direct_pool_swaps() {
+        let (a, b, to) = (Address::repeat_byte(0xa), Address::repeat_byte(0xb), Address::repeat_byte(0xee));
+        let single = call("exactInputSingle((address,address,uint24,address,uint256,uint256,uint160))", &[Token::Tuple(vec![
+            Token::Address(a), Token::Address(b), Token::Uint(500.into()), Token::Address(to),
+            Token::Uint(100.into()), Token::Uint(95.into()), Token::Uint(U256::zero()),
+        ])]);
+        let v2 = call("swapTokensForExactTokens(uint256,uint256,address[],address)", &[
+            Token::Uint(40.into()), Token::Uint(60.into()), Token::Array(vec![Token::Address(b), Token::Address(a)]), Token::Address(to),
+        ]);
+        let data = call("multicall(uint256,bytes[])", &[
+            Token::Uint(U256::MAX),
+            Token::Array(vec![Token::Bytes(single), Token::Bytes(id("refundETH()").to_vec()), Token::Bytes(v2)]),
+        ]);
+        let swaps = decode_router_call(&data, U256::zero());
+        assert_eq!(swaps.iter().map(|swap| swap.dex).collect::<Vec<_>>(), vec![DexKind::UniswapV3, DexKind::UniswapV2]);
+        assert_eq!(swaps[0].fees, vec![500]);
+        assert_eq!(swaps[1].amount, SwapAmount::ExactOut { amount_out: 40.into(), max_amount_in: 60.into() });
+
+        // Direct pool calls take the direction from the pool's tokens
+        let pool = PoolState::v3(Address::repeat_byte(1), Address::zero(), a, b, 3_000);
+        let data = call(V3_POOL_SWAP, &[
+            Token::Address(to), Token::Bool(false), Token::Int(I256::from(-25).into_raw()), Token::Uint(U256::zero()), Token::Bytes(Vec::new()),
+        ]);
+        let swap = decode_pool_call(&pool, &data).unwrap();
+        assert_eq!((swap.pool, swap.path.clone()), (Some(pool.address), vec![b, a]));
+        assert_eq!(swap.amount, SwapAmount::ExactOut { amount_out: 25.into(), max_amount_in: U256::MAX });
+        let pair = PoolState::v2(Address::repeat_byte(2), Address::zero(), a, b, 30);
+        let data = call(V2_PAIR_SWAP, &[Token::Uint(0.into()), Token::Uint(10.into()), Token::Address(to), Token::Bytes(Vec::new())]);
+        assert_eq!(decode_pool_call(&pair, &data).unwrap().path, vec![a, b]);
+        assert!(decode_pool_call(&pool, &data).is_none());
+    }
+}
@@ -0,0 +1,9 @@
+// ! Mempool module - decodes pending DEX swaps from the node's transaction pool
+
+mod decoder;
+mod projection;
+mod watcher;
+
+pub use decoder::{SwapAmount, SwapCall, decode_pool_call, decode_router_call, decode_v3_path};
+pub use projection::{PendingProjection, ProjectedPool, block_order};
+pub use watcher::{MempoolWatcher, PendingSwap};
@@ -0,0 +1,398 @@
+//! Pool state after the pending swaps, as the next block would leave it
+//!
+//! Pending transactions are ordered the way block builders fill a block: by the gas
+//! price they pay at the next base fee, each sender's transactions in nonce order. Each
+//! one runs on copies of the pools it touches. A transaction whose slippage bound fails
+//! reverts and leaves the pools as they were; one through a pool that is not watched, or
+//! a V3 pool without tick data, is skipped. Every pending transaction is assumed to fit
+//! into the next block.
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use chrono::{DateTime, Utc};
+use ethers::types::{Address, H256, U256};
+use serde::Serialize;
+
+use crate::{
+    address_book::{ChainAddresses, DexKind},
+    pools::{PoolManager, PoolReserves, PoolState},
+    pricing::{ConstantProductPool, V3PoolSnapshot},
+    storage::ReserveProjection,
+};
+use super::{PendingSwap, SwapAmount, SwapCall};
+
+/// A pool the pending swaps move
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProjectedPool {
+    pub before: PoolState,
+    pub after: PoolState,
+    /// Pending transactions that swap through it
+    pub transactions: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PendingProjection {
+    /// Block whose post-state the projection starts from
+    pub base_block: u64,
+    pub pools: HashMap<Address, ProjectedPool>,
+    /// Transactions applied, in block order
+    pub applied: Vec<H256>,
+    /// Transactions that would revert on their slippage bounds
+    pub reverted: Vec<H256>,
+    /// Transactions through unwatched pools or pools without the data to quote them
+    pub skipped: Vec<H256>,
+}
+
+/// Why a transaction could not be applied
+enum Failure {
+    Reverts,
+    Unknown,
+}
+
+/// Pool working copy, with what a swap through it needs
+#[derive(Clone)]
+struct WorkingPool {
+    state: PoolState,
+    /// Tick data of V3 pools
+    v3: Option<V3PoolSnapshot>,
+}
+
+impl PendingProjection {
+    /// Applies `swaps` in block order on top of the pools' current state. V3 pools are
+    /// quoted with the tick data `v3_snapshot` returns for them.
+    pub fn project(
+        base_block: u64,
+        swaps: &[PendingSwap],
+        base_fee: U256,
+        pool_manager: &PoolManager,
+        addresses: &ChainAddresses,
+        v3_snapshot: impl Fn(Address) -> Option<V3PoolSnapshot>,
+    ) -> Self {
+        let mut projection = Self { base_block, ..Self::default() };
+        let mut pools: HashMap<Address, WorkingPool> = HashMap::new();
+        let mut transactions: HashMap<Address, usize> = HashMap::new();
+
+        for pending in block_order(swaps, base_fee) {
+            // Pools this transaction moved, committed once all its swaps succeed
+            let mut touched: HashMap<Address, WorkingPool> = HashMap::new();
+            let mut result = Ok(());
+            for call in &pending.swaps {
+                result = apply_call(call, pending.to, &pools, &mut touched, pool_manager, addresses, &v3_snapshot);
+                if result.is_err() {
+                    break;
+                }
+            }
+            match result {
+                Ok(()) => {
+                    for (address, pool) in touched {
+                        *transactions.entry(address).or_default() += 1;
+                        pools.insert(address, pool);
+                    }
+                    projection.applied.push(pending.tx_hash);
+                }
+                Err(Failure::Reverts) => projection.reverted.push(pending.tx_hash),
+                Err(Failure::Unknown) => projection.skipped.push(pending.tx_hash),
+            }
+        }
+
+        for (address, pool) in pools {
+            let Some(before) = pool_manager.get(address) else { continue };
+            projection.pools.insert(address, ProjectedPool {
+                before,
+                after: pool.state,
+                transactions: transactions.get(&address).copied().unwrap_or_default(),
+            });
+        }
+        projection
+    }
+
+    /// Current and predicted reserves of the V2 pools that move
+    pub fn reserve_projections(&self) -> HashMap<Address, ReserveProjection> {
+        self.pools.iter()
+            .filter_map(|(address, pool)| {
+                let current = pool.before.v2_reserves()?;
+                let predicted = pool.after.v2_reserves()?;
+                (current != predicted).then_some((*address, ReserveProjection {
+                    current: [current.0, current.1],
+                    predicted: [predicted.0, predicted.1],
+                    transactions: pool.transactions,
+                }))
+            })
+            .collect()
+    }
+}
+
+/// Transactions in the order a block builder includes them: highest effective gas
+/// price first, each sender's in nonce order. Those that can not pay `base_fee` wait.
+pub fn block_order(swaps: &[PendingSwap], base_fee: U256) -> Vec<&PendingSwap> {
+    let mut senders: HashMap<Address, Vec<&PendingSwap>> = HashMap::new();
+    for swap in swaps.iter().filter(|swap| swap.gas_price >= base_fee) {
+        senders.entry(swap.from).or_default().push(swap);
+    }
+    let mut queues: Vec<Vec<&PendingSwap>> = senders.into_values()
+        .map(|mut queue| {
+            // Reversed so the lowest nonce pops first
+            queue.sort_by_key(|swap| Reverse(swap.nonce));
+            queue
+        })
+        .collect();
+
+    let mut heads: BinaryHeap<(U256, Reverse<DateTime<Utc>>, usize)> = BinaryHeap::new();
+    let push_head = |heads: &mut BinaryHeap<_>, queues: &[Vec<&PendingSwap>], index: usize| {
+        if let Some(swap) = queues[index].last() {
+            heads.push((swap.effective_gas_price(base_fee), Reverse(swap.seen_at), index));
+        }
+    };
+    for index in 0..queues.len() {
+        push_head(&mut heads, &queues, index);
+    }
+    let mut ordered = Vec::with_capacity(swaps.len());
+    while let Some((_, _, index)) = heads.pop() {
+        ordered.extend(queues[index].pop());
+        push_head(&mut heads, &queues, index);
+    }
+    ordered
+}
+
+fn apply_call(
+    call: &SwapCall,
+    router: Address,
+    pools: &HashMap<Address, WorkingPool>,
+    touched: &mut HashMap<Address, WorkingPool>,
+    pool_manager: &PoolManager,
+    addresses: &ChainAddresses,
+    v3_snapshot: &impl Fn(Address) -> Option<V3PoolSnapshot>,
+) -> Result<(), Failure> {
+    let mut hops = Vec::with_capacity(call.hops());
+    for (i, tokens) in call.path.windows(2).enumerate() {
+        let address = match call.pool {
+            Some(pool) => pool,
+            None => find_pool(call, i, tokens[0], tokens[1], router, pool_manager, addresses).ok_or(Failure::Unknown)?,
+        };
+        let pool = match touched.get(&address).or_else(|| pools.get(&address)) {
+            Some(pool) => pool.clone(),
+            None => working_pool(pool_manager.get(address).ok_or(Failure::Unknown)?, v3_snapshot)?,
+        };
+        let zero_for_one = tokens[0] == pool.state.token0;
+        hops.push((address, pool, zero_for_one));
+    }
+
+    match call.amount {
+        SwapAmount::ExactIn { amount_in, min_amount_out } => {
+            let mut amount = amount_in;
+            for (address, mut pool, zero_for_one) in hops {
+                let amount_out = pool.swap_exact_in(zero_for_one, amount)?;
+                touched.insert(address, pool);
+                amount = amount_out;
+            }
+            if amount < min_amount_out {
+                return Err(Failure::Reverts);
+            }
+        }
+        SwapAmount::ExactOut { amount_out, max_amount_in } => {
+            // Quoted from the output back, each hop owes the next one its input
+            let mut amount = amount_out;
+            for (address, mut pool, zero_for_one) in hops.into_iter().rev() {
+                let amount_in = pool.swap_exact_out(zero_for_one, amount)?;
+                touched.insert(address, pool);
+                amount = amount_in;
+            }
+            if amount > max_amount_in {
+                return Err(Failure::Reverts);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The watched pool a router swaps `token_in` for `token_out` through on hop `hop`: the
+/// one of the factory named like the router, `None` if that pool is not watched. Only a
+/// router without a known factory falls back to the lowest addressed candidate.
+fn find_pool(
+    call: &SwapCall,
+    hop: usize,
+    token_in: Address,
+    token_out: Address,
+    router: Address,
+    pool_manager: &PoolManager,
+    addresses: &ChainAddresses,
+) -> Option<Address> {
+    let fee = call.fees.get(hop).copied();
+    let candidates: Vec<PoolState> = pool_manager.pools_for_pair(token_in, token_out)
+        .into_iter()
+        .filter(|pool| pool.kind == call.dex && (call.dex != DexKind::UniswapV3 || Some(pool.fee) == fee))
+        .collect();
+    let router_factory = addresses.router_by_address(router)
+        .and_then(|router| addresses.factory(&router.name))
+        .filter(|factory| factory.kind == call.dex)
+        .map(|factory| factory.address);
+    match router_factory {
+        Some(factory) => candidates.iter().find(|pool| pool.factory == factory),
+        None => candidates.iter().min_by_key(|pool| pool.address),
+    }
+    .map(|pool| pool.address)
+}
+
+fn working_pool(state: PoolState, v3_snapshot: &impl Fn(Address) -> Option<V3PoolSnapshot>) -> Result<WorkingPool, Failure> {
+    let v3 = match (state.kind, &state.reserves) {
+        (DexKind::UniswapV2, PoolReserves::V2 { .. }) => None,
+        (DexKind::UniswapV3, PoolReserves::V3 { sqrt_price_x96, liquidity, tick }) => {
+            // Tick data may be older than the price the pool's events keep current
+            let mut snapshot = v3_snapshot(state.address).ok_or(Failure::Unknown)?;
+            snapshot.sqrt_price_x96 = *sqrt_price_x96;
+            snapshot.liquidity = *liquidity;
+            snapshot.tick = *tick;
+            Some(snapshot)
+        }
+        _ => return Err(Failure::Unknown),
+    };
+    Ok(WorkingPool { state, v3 })
+}
+
+impl WorkingPool {
+    /// Swaps `amount_in` and returns the output
+    fn swap_exact_in(&mut self, zero_for_one: bool, amount_in: U256) -> Result<U256, Failure> {
+        if let Some(snapshot) = &mut self.v3 {
+            let result = snapshot.swap(zero_for_one, amount_in, true, None).map_err(|_| Failure::Unknown)?;
+            snapshot.apply_swap(&result);
+            self.sync_v3();
+            return Ok(result.amount_out);
+        }
+        let pool = ConstantProductPool::from_pool(&self.state).ok_or(Failure::Unknown)?;
+        let amount_out = pool.amount_out(zero_for_one, amount_in).map_err(|_| Failure::Reverts)?;
+        self.set_v2_reserves(pool, zero_for_one, amount_in, amount_out)?;
+        Ok(amount_out)
+    }
+
+    /// Swaps for exactly `amount_out` and returns the input it takes
+    fn swap_exact_out(&mut self, zero_for_one: bool, amount_out: U256) -> Result<U256, Failure> {
+        if let Some(snapshot) = &mut self.v3 {
+            let result = snapshot.swap(zero_for_one, amount_out, false, None).map_err(|_| Failure::Unknown)?;
+            if result.amount_out != amount_out {
+                return Err(Failure::Reverts);
+            }
+            snapshot.apply_swap(&result);
+            self.sync_v3();
+            return Ok(result.amount_in);
+        }
+        let pool = ConstantProductPool::from_pool(&self.state).ok_or(Failure::Unknown)?;
+        let amount_in = pool.amount_in(zero_for_one, amount_out).map_err(|_| Failure::Reverts)?;
+        self.set_v2_reserves(pool, zero_for_one, amount_in, amount_out)?;
+        Ok(amount_in)
+    }
+
+    /// Reserves after the pair received `amount_in` and sent `amount_out`
+    fn set_v2_reserves(&mut self, pool: ConstantProductPool, zero_for_one: bool, amount_in: U256, amount_out: U256) -> Result<(), Failure> {
+        let (reserve_in, reserve_out) = pool.reserves(zero_for_one);
+        let reserve_in = reserve_in.checked_add(amount_in).ok_or(Failure::Reverts)?;
+        let reserve_out = reserve_out.checked_sub(amount_out).ok_or(Failure::Reverts)?;
+        let (reserve0, reserve1) = if zero_for_one { (reserve_in, reserve_out) } else { (reserve_out, reserve_in) };
+        self.state.reserves = PoolReserves::V2 { reserve0, reserve1 };
+        Ok(())
+    }
+
+    fn sync_v3(&mut self) {
+        if let Some(snapshot) = &self.v3 {
+            self.state.reserves = PoolReserves::V3 {
+                sqrt_price_x96: snapshot.sqrt_price_x96,
+                liquidity: snapshot.liquidity,
+                tick: snapshot.tick,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::ChainProfile;
+
+    fn pending(hash: u8, from: u8, nonce: u64, gas_price: u64, swaps: Vec<SwapCall>) -> PendingSwap {
+        PendingSwap {
+            tx_hash: H256::repeat_byte(hash),
+            from: Address::repeat_byte(from),
+            to: Address::zero(),
+            nonce: nonce.into(),
+            gas_price: gas_price.into(),
+            max_priority_fee_per_gas: None,
+            swaps,
+            seen_at: Utc::now(),
+        }
+    }
+
+    fn exact_in(path: Vec<Address>, amount_in: u64, min_amount_out: u64) -> SwapCall {
+        SwapCall {
+            dex: DexKind::UniswapV2,
+            pool: None,
+            path,
+            fees: Vec::new(),
+            amount: SwapAmount::ExactIn { amount_in: amount_in.into(), min_amount_out: min_amount_out.into() },
+            recipient: Address::zero(),
+        }
+    }
+
+    #[test]
+    fn test_orders_by_gas_price_and_nonce() {
+        let swaps = vec![
+            pending(1, 0xa, 1, 50, Vec::new()),
+            pending(2, 0xa, 0, 10, Vec::new()),
+            pending(3, 0xb, 0, 30, Vec::new()),
+            pending(4, 0xc, 0, 5, Vec::new()),
+        ];
+        // Sender a's second transaction pays most but waits for its first
+        let order: Vec<u8> = block_order(&swaps, 8.into()).iter().map(|swap| swap.tx_hash[0]).collect();
+        assert_eq!(order, vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn test_projects_v2_reserves_and_skips_reverting_swaps() {
+        let chain = ChainProfile::ethereum();
+        let (a, b, c) = (Address::repeat_byte(0xa), Address::repeat_byte(0xb), Address::repeat_byte(0xc));
+        let manager = PoolManager::new(Vec::new(), chain.events.clone());
+        let mut pool = PoolState::v2(Address::repeat_byte(1), Address::zero(), a, b, 30);
+        pool.reserves = PoolReserves::V2 { reserve0: 1_000_000.into(), reserve1: 1_000_000.into() };
+        manager.add_pool(pool.clone());
+
+        let swaps = vec![
+            pending(1, 1, 0, 100, vec![exact_in(vec![a, b], 10_000, 9_000)]),
+            // Runs after the first one moved the price, its bound no longer holds
+            pending(2, 2, 0, 50, vec![exact_in(vec![a, b], 10_000, 9_800)]),
+            pending(3, 3, 0, 40, vec![exact_in(vec![a, c], 10_000, 0)]),
+        ];
+        let projection = PendingProjection::project(7, &swaps, U256::zero(), &manager, &chain.addresses, |_| None);
+        assert_eq!(projection.applied, vec![H256::repeat_byte(1)]);
+        assert_eq!(projection.reverted, vec![H256::repeat_byte(2)]);
+        assert_eq!(projection.skipped, vec![H256::repeat_byte(3)]);
+
+        let amount_out = ConstantProductPool::from_pool(&pool).unwrap().amount_out(true, 10_000.into()).unwrap();
+        let reserves = projection.reserve_projections();
+        let projected = &reserves[&pool.address];
+        assert_eq!(projected.current, [U256::from(1_000_000), U256::from(1_000_000)]);
+        assert_eq!(projected.predicted, [U256::from(1_010_000), U256::from(1_000_000) - amount_out]);
+        assert_eq!(projected.transactions, 1);
+    }
+
+    #[test]
+    fn test_router_swaps_stay_on_their_own_factory() {
+        let chain = ChainProfile::ethereum();
+        let (a, b) = (Address::repeat_byte(0xa), Address::repeat_byte(0xb));
+        let addresses = &chain.addresses;
+        let pool = |address: u8, factory: &str| {
+            let mut pool = PoolState::v2(Address::repeat_byte(address), addresses.factory(factory).unwrap().address, a, b, 30);
+            pool.reserves = PoolReserves::V2 { reserve0: 1_000_000.into(), reserve1: 1_000_000.into() };
+            pool
+        };
+        let manager = PoolManager::new(Vec::new(), chain.events.clone());
+        manager.add_pool(pool(1, "sushiswap"));
+
+        let mut swap = pending(1, 1, 0, 100, vec![exact_in(vec![a, b], 10_000, 0)]);
+        swap.to = addresses.router("uniswap_v2").unwrap().address;
+        // The Uniswap router never trades on the Sushiswap pair
+        let projection = PendingProjection::project(7, std::slice::from_ref(&swap), U256::zero(), &manager, addresses, |_| None);
+        assert_eq!(projection.skipped, vec![swap.tx_hash]);
+
+        manager.add_pool(pool(2, "uniswap_v2"));
+        let projection = PendingProjection::project(7, std::slice::from_ref(&swap), U256::zero(), &manager, addresses, |_| None);
+        assert_eq!(projection.applied, vec![swap.tx_hash]);
+        assert_eq!(projection.pools.keys().copied().collect::<Vec<_>>(), vec![Address::repeat_byte(2)]);
+    }
+}
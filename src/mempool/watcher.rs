@@ -0,0 +1,256 @@
+//! Pending transactions that swap through watched routers and pools
+//!
+//! Subscribes to `newPendingTransactions` asking for full transaction objects. Nodes
+//! that only send hashes, or reject the flag, are served by fetching each transaction.
+//! Transactions to a chain's routers or to a watched pool are decoded and kept until
+//! they are mined, replaced or expire.
+use std::collections::HashSet;
+use std::sync::Arc;
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use ethers::{
+    providers::{Middleware, Provider, StreamExt, Ws},
+    types::{Address, Transaction, H256, U256},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::{chain::ChainProfile, const_and_addr, pools::PoolManager};
+use super::{SwapCall, decode_pool_call, decode_router_call};
+
+/// A pending transaction and the swaps it makes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PendingSwap {
+    pub tx_hash: H256,
+    pub from: Address,
+    /// Router or pool called
+    pub to: Address,
+    pub nonce: U256,
+    /// Legacy gas price, or `maxFeePerGas` for EIP-1559 transactions
+    pub gas_price: U256,
+    /// `None` for legacy transactions
+    pub max_priority_fee_per_gas: Option<U256>,
+    pub swaps: Vec<SwapCall>,
+    pub seen_at: DateTime<Utc>,
+}
+
+impl PendingSwap {
+    /// Price per gas the transaction pays in a block with `base_fee`, which is also what
+    /// block builders order by
+    pub fn effective_gas_price(&self, base_fee: U256) -> U256 {
+        match self.max_priority_fee_per_gas {
+            Some(tip) => self.gas_price.min(base_fee.saturating_add(tip)),
+            None => self.gas_price,
+        }
+    }
+}
+
+/// Items of a pending transaction subscription, depending on what the node supports
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum PendingItem {
+    Hash(H256),
+    Transaction(Box<Transaction>),
+}
+
+pub struct MempoolWatcher {
+    routers: HashSet<Address>,
+    pool_manager: Arc<PoolManager>,
+    pending: DashMap<H256, PendingSwap>,
+}
+
+impl MempoolWatcher {
+    pub fn new(chain: &ChainProfile, pool_manager: Arc<PoolManager>) -> Self {
+        Self {
+            routers: chain.addresses.routers().iter().map(|router| router.address).collect(),
+            pool_manager,
+            pending: DashMap::new(),
+        }
+    }
+
+    /// Whether transactions to `address` are decoded
+    pub fn is_watched(&self, address: Address) -> bool {
+        self.routers.contains(&address) || self.pool_manager.is_monitored_pool(address)
+    }
+
+    /// The swaps `tx` makes, `None` when it calls nothing watched or swaps nothing
+    pub fn decode(&self, tx: &Transaction) -> Option<PendingSwap> {
+        let to = tx.to?;
+        let swaps = if self.routers.contains(&to) {
+            decode_router_call(&tx.input, tx.value)
+        } else {
+            decode_pool_call(&self.pool_manager.get(to)?, &tx.input).into_iter().collect()
+        };
+        if swaps.is_empty() {
+            return None;
+        }
+        Some(PendingSwap {
+            tx_hash: tx.hash,
+            from: tx.from,
+            to,
+            nonce: tx.nonce,
+            gas_price: tx.max_fee_per_gas.or(tx.gas_price).unwrap_or_default(),
+            max_priority_fee_per_gas: tx.max_priority_fee_per_gas,
+            swaps,
+            seen_at: Utc::now(),
+        })
+    }
+
+    /// Keeps a decoded transaction. One from the same sender with the same nonce is
+    /// replaced when this one pays more, otherwise this one is dropped. Returns `false`
+    /// if the swap was not kept.
+    pub fn insert(&self, swap: PendingSwap) -> bool {
+        if self.pending.contains_key(&swap.tx_hash) {
+            return false;
+        }
+        let replaced = self.pending.iter()
+            .find(|entry| entry.from == swap.from && entry.nonce == swap.nonce)
+            .map(|entry| (entry.tx_hash, entry.gas_price));
+        if let Some((hash, gas_price)) = replaced {
+            if gas_price >= swap.gas_price {
+                return false;
+            }
+            self.pending.remove(&hash);
+        }
+        if self.pending.len() >= const_and_addr::MAX_PENDING_SWAPS {
+            return false;
+        }
+        self.pending.insert(swap.tx_hash, swap);
+        true
+    }
+
+    /// Decodes and keeps `tx`, returns the swap if it was kept
+    pub fn observe(&self, tx: &Transaction) -> Option<PendingSwap> {
+        let swap = self.decode(tx)?;
+        self.insert(swap.clone()).then_some(swap)
+    }
+
+    /// Drops the transactions a block included, returns how many were pending
+    pub fn remove_included(&self, tx_hashes: &[H256]) -> usize {
+        tx_hashes.iter().filter(|hash| self.pending.remove(hash).is_some()).count()
+    }
+
+    /// Drops transactions first seen more than `PENDING_SWAP_TTL_SECONDS` ago, which were
+    /// most likely dropped or mined in a block this scanner did not see
+    pub fn prune(&self) -> usize {
+        let cutoff = Utc::now() - chrono::Duration::seconds(const_and_addr::PENDING_SWAP_TTL_SECONDS as i64);
+        let before = self.pending.len();
+        self.pending.retain(|_, swap| swap.seen_at >= cutoff);
+        before - self.pending.len()
+    }
+
+    pub fn get(&self, tx_hash: H256) -> Option<PendingSwap> {
+        self.pending.get(&tx_hash).map(|swap| swap.clone())
+    }
+
+    /// Pending swaps, highest gas price first, then in the order they were seen
+    pub fn pending(&self) -> Vec<PendingSwap> {
+        let mut swaps: Vec<PendingSwap> = self.pending.iter().map(|entry| entry.clone()).collect();
+        swaps.sort_by(|a, b| b.gas_price.cmp(&a.gas_price).then(a.seen_at.cmp(&b.seen_at)));
+        swaps
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Follows the node's pending transactions until the subscription ends
+    pub async fn watch(&self, provider: &Provider<Ws>) -> Result<()> {
+        let stream = match provider.subscribe::<_, PendingItem>(("newPendingTransactions", true)).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                debug!("Full pending transactions not supported ({}), subscribing to hashes", e);
+                provider.subscribe(["newPendingTransactions"]).await
+                    .context("Failed to subscribe to pending transactions")?
+            }
+        };
+        info!("📡 Mempool subscription established, watching {} routers", self.routers.len());
+
+        let mut transactions = stream
+            .map(|item| async move {
+                match item {
+                    PendingItem::Transaction(tx) => Some(*tx),
+                    PendingItem::Hash(hash) => match provider.get_transaction(hash).await {
+                        Ok(tx) => tx,
+                        Err(e) => {
+                            debug!("Failed to fetch pending transaction {:?}: {}", hash, e);
+                            None
+                        }
+                    },
+                }
+            })
+            .buffer_unordered(const_and_addr::MEMPOOL_FETCH_CONCURRENCY);
+
+        while let Some(tx) = transactions.next().await {
+            let Some(tx) = tx else { continue };
+            // Fetched transactions may have been mined meanwhile
+            if tx.block_number.is_some() || !tx.to.is_some_and(|to| self.is_watched(to)) {
+                continue;
+            }
+            if let Some(swap) = self.observe(&tx) {
+                debug!("📥 Pending swap {:?}: {} swaps via {:?}, gas price {}",
+                    swap.tx_hash, swap.swaps.len(), swap.to, swap.gas_price);
+            }
+        }
+        warn!("⚠️ Mempool subscription ended");
+        Err(anyhow!("Mempool subscription terminated"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::{
+        abi::{self, Token},
+        types::TransactionRequest,
+        utils::{Anvil, id},
+    };
+    use std::time::Duration;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[ignore = "requires anvil"]
+    async fn test_watches_pending_router_swaps_on_anvil() -> Result<()> {
+        // Without automining transactions stay in the pool until a block is mined on request
+        let anvil = Anvil::new().arg("--no-mining").arg("--silent").spawn();
+        let provider = Arc::new(Provider::<Ws>::connect(anvil.ws_endpoint()).await?);
+        let chain = ChainProfile::ethereum();
+        let router = chain.addresses.router("uniswap_v2").unwrap().address;
+        let watcher = Arc::new(MempoolWatcher::new(&chain, Arc::new(PoolManager::for_chain(&chain))));
+        let task = tokio::spawn({
+            let (watcher, provider) = (watcher.clone(), provider.clone());
+            async move { watcher.watch(&provider).await }
+        });
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        let (weth, usdc) = (chain.addresses.wrapped_native(), chain.addresses.token("USDC").unwrap().address);
+        let mut data = id("swapExactETHForTokens(uint256,address[],address,uint256)").to_vec();
+        data.extend(abi::encode(&[
+            Token::Uint(1.into()),
+            Token::Array(vec![Token::Address(weth), Token::Address(usdc)]),
+            Token::Address(anvil.addresses()[0]),
+            Token::Uint(U256::MAX),
+        ]));
+        let swap_tx = TransactionRequest::new().from(anvil.addresses()[0]).to(router).data(data).value(1_000u64);
+        let swap_hash = provider.send_transaction(swap_tx, None).await?.tx_hash();
+        // Plain transfers are not swaps
+        let transfer = TransactionRequest::new().from(anvil.addresses()[1]).to(anvil.addresses()[2]).value(1u64);
+        provider.send_transaction(transfer, None).await?;
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(watcher.len(), 1);
+        let pending = watcher.get(swap_hash).expect("swap is pending");
+        assert_eq!(pending.swaps[0].path, vec![weth, usdc]);
+
+        provider.request::<_, ()>("evm_mine", ()).await?;
+        let block = provider.get_block(1u64).await?.expect("mined block");
+        assert_eq!(watcher.remove_included(&block.transactions), 1);
+        assert!(watcher.is_empty());
+        task.abort();
+        Ok(())
+    }
+}
@@ -0,0 +1,5 @@
+pub mod pool_manager;
+pub mod pool_state;
+
+pub use pool_manager::PoolManager;
+pub use pool_state::{PoolReserves, PoolState};
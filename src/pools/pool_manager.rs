@@ -0,0 +1,520 @@
+//! Registry of the pools the scanner watches
+//!
+//! Pools come from three places: the address book, factory creation events
+//! (`PairCreated`/`PoolCreated`) over a block range, and `getPair`/`getPool` lookups for
+//! a list of token pairs. Reserves are fetched once over RPC and then kept current from
+//! the pools' own `Sync` (V2) and `Swap`, `Mint` and `Burn` (V3) logs. Lookups go through a `DashMap`, so
+//! `is_monitored_pool` is a cheap synchronous call on the hot path.
+use std::collections::HashSet;
+use anyhow::{Context, Result, anyhow};
+use dashmap::{DashMap, mapref::entry::Entry};
+use ethers::{
+    abi::{self, ParamType, Token},
+    providers::Middleware,
+    types::{Address, Bytes, Filter, Log, TransactionRequest, U256},
+    utils::id,
+};
+use futures::stream::{self, StreamExt};
+use tracing::{debug, info};
+
+use crate::{
+    address_book::{DexKind, FactoryInfo},
+    chain::{ChainProfile, EventSignatures},
+    const_and_addr,
+};
+use super::pool_state::{PoolReserves, PoolState, sort_tokens, topic_to_u256, word_to_address, word_to_i24};
+
+/// Concurrent `eth_call`s while refreshing pool state
+const REFRESH_CONCURRENCY: usize = 16;
+
+pub struct PoolManager {
+    pools: DashMap<Address, PoolState>,
+    /// Pool addresses per token pair, keyed in pool order
+    by_pair: DashMap<(Address, Address), Vec<Address>>,
+    factories: Vec<FactoryInfo>,
+    events: EventSignatures,
+}
+
+impl PoolManager {
+    pub fn new(factories: Vec<FactoryInfo>, events: EventSignatures) -> Self {
+        Self {
+            pools: DashMap::new(),
+            by_pair: DashMap::new(),
+            factories,
+            events,
+        }
+    }
+
+    /// Manager for the chain's factories, seeded with the address book pools
+    pub fn for_chain(profile: &ChainProfile) -> Self {
+        let manager = Self::new(profile.addresses.factories().to_vec(), profile.events.clone());
+        for pool in profile.addresses.pools() {
+            if let Some(state) = PoolState::from_pool_info(pool, profile.addresses.factories()) {
+                manager.add_pool(state);
+            }
+        }
+        manager
+    }
+
+    /// Starts watching a pool. Returns `false` if it was already watched, in which case
+    /// the existing state is kept.
+    pub fn add_pool(&self, pool: PoolState) -> bool {
+        let address = pool.address;
+        let pair = sort_tokens(pool.token0, pool.token1);
+        // The entry stays locked until the pair index is updated, so concurrent adds of
+        // the same pool index it once
+        let Entry::Vacant(entry) = self.pools.entry(address) else {
+            return false;
+        };
+        let _pool = entry.insert(pool);
+        self.by_pair.entry(pair).or_default().push(address);
+        true
+    }
+
+    pub fn remove_pool(&self, address: Address) -> Option<PoolState> {
+        let (_, pool) = self.pools.remove(&address)?;
+        let pair = sort_tokens(pool.token0, pool.token1);
+        if let Some(mut addresses) = self.by_pair.get_mut(&pair) {
+            addresses.retain(|a| *a != address);
+        }
+        self.by_pair.remove_if(&pair, |_, addresses| addresses.is_empty());
+        Some(pool)
+    }
+
+    pub fn is_monitored_pool(&self, address: Address) -> bool {
+        self.pools.contains_key(&address)
+    }
+
+    pub fn get(&self, address: Address) -> Option<PoolState> {
+        self.pools.get(&address).map(|pool| pool.clone())
+    }
+
+    /// Every watched pool trading `a` against `b`, in either order
+    pub fn pools_for_pair(&self, a: Address, b: Address) -> Vec<PoolState> {
+        let Some(addresses) = self.by_pair.get(&sort_tokens(a, b)).map(|a| a.clone()) else {
+            return Vec::new();
+        };
+        addresses.into_iter().filter_map(|address| self.get(address)).collect()
+    }
+
+    pub fn addresses(&self) -> Vec<Address> {
+        self.pools.iter().map(|entry| *entry.key()).collect()
+    }
+
+    pub fn all(&self) -> Vec<PoolState> {
+        self.pools.iter().map(|entry| entry.value().clone()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pools.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pools.is_empty()
+    }
+
+    /// Updates a watched pool from its `Sync` or V3 `Swap`, `Mint` or `Burn` log. Returns
+    /// `true` if the log changed some pool's state.
+    pub fn apply_log(&self, log: &Log) -> bool {
+        let (Some(topic0), Some(block_number)) = (log.topics.first(), log.block_number) else {
+            return false;
+        };
+        if log.removed == Some(true) {
+            return false;
+        }
+        let Some(mut pool) = self.pools.get_mut(&log.address) else {
+            return false;
+        };
+        let log_index = log.log_index.map(|index| index.as_u64());
+        // Mint and Burn apply a delta, which must not land twice
+        if !pool.is_behind(block_number.as_u64(), log_index) {
+            return false;
+        }
+
+        let reserves = if *topic0 == self.events.sync && log.data.len() >= 64 {
+            PoolReserves::V2 {
+                reserve0: U256::from_big_endian(&log.data[0..32]),
+                reserve1: U256::from_big_endian(&log.data[32..64]),
+            }
+        } else if *topic0 == self.events.v3_swap && log.data.len() >= 160 {
+            PoolReserves::V3 {
+                sqrt_price_x96: U256::from_big_endian(&log.data[64..96]),
+                liquidity: U256::from_big_endian(&log.data[96..128]).low_u128(),
+                tick: word_to_i24(&log.data[128..160]),
+            }
+        } else if let Some((tick_lower, tick_upper, delta)) = self.position_change(log)
+            && let PoolReserves::V3 { sqrt_price_x96, liquidity, tick } = pool.reserves
+        {
+            // Only positions around the current tick add to the active liquidity
+            if tick < tick_lower || tick >= tick_upper {
+                return false;
+            }
+            match liquidity.checked_add_signed(delta) {
+                Some(liquidity) => PoolReserves::V3 { sqrt_price_x96, liquidity, tick },
+                // The tracked liquidity is off, the pool is not quoted until its next swap or refresh
+                None => PoolReserves::Unknown,
+            }
+        } else {
+            return false;
+        };
+        pool.set_reserves(reserves, block_number.as_u64(), log_index)
+    }
+
+    /// Tick range and liquidity change of a V3 `Mint` or `Burn` log
+    fn position_change(&self, log: &Log) -> Option<(i32, i32, i128)> {
+        let topic0 = *log.topics.first()?;
+        let (amount, sign) = if topic0 == self.events.v3_mint && log.data.len() >= 64 {
+            // `sender` comes before the amount
+            (&log.data[32..64], 1)
+        } else if topic0 == self.events.v3_burn && log.data.len() >= 32 {
+            (&log.data[0..32], -1)
+        } else {
+            return None;
+        };
+        let (tick_lower, tick_upper) = (word_to_i24(log.topics.get(2)?.as_bytes()), word_to_i24(log.topics.get(3)?.as_bytes()));
+        let amount = i128::try_from(U256::from_big_endian(amount).low_u128()).ok()?;
+        Some((tick_lower, tick_upper, sign * amount))
+    }
+
+    /// Watched V3 pools with a `Mint` or `Burn` among `logs`, whose tick data is stale
+    pub fn position_changes<'a>(&self, logs: impl IntoIterator<Item = &'a Log>) -> HashSet<Address> {
+        logs.into_iter()
+            .filter(|log| log.removed != Some(true) && self.is_monitored_pool(log.address))
+            .filter(|log| self.position_change(log).is_some())
+            .map(|log| log.address)
+            .collect()
+    }
+
+    /// Applies every relevant log, returns the pools that changed
+    pub fn apply_logs<'a>(&self, logs: impl IntoIterator<Item = &'a Log>) -> HashSet<Address> {
+        logs.into_iter().filter(|log| self.apply_log(log)).map(|log| log.address).collect()
+    }
+
+    /// Reads a pool's current reserves (V2) or price and liquidity (V3) over RPC
+    pub async fn refresh<M: Middleware + 'static>(&self, provider: &M, address: Address) -> Result<PoolState> {
+        let kind = self.get(address)
+            .ok_or_else(|| anyhow!("Pool {:?} is not monitored", address))?
+            .kind;
+        let block_number = provider.get_block_number().await
+            .context("Failed to fetch block number")?
+            .as_u64();
+
+        let reserves = match kind {
+            DexKind::UniswapV2 => {
+                let out = call(provider, address, "getReserves()", &[], &[
+                    ParamType::Uint(112), ParamType::Uint(112), ParamType::Uint(32),
+                ]).await?;
+                PoolReserves::V2 { reserve0: uint(&out[0])?, reserve1: uint(&out[1])? }
+            }
+            DexKind::UniswapV3 => {
+                let slot0 = call(provider, address, "slot0()", &[], &[
+                    ParamType::Uint(160), ParamType::Int(24), ParamType::Uint(16), ParamType::Uint(16),
+                    ParamType::Uint(16), ParamType::Uint(8), ParamType::Bool,
+                ]).await?;
+                let liquidity = call(provider, address, "liquidity()", &[], &[ParamType::Uint(128)]).await?;
+                let tick = match &slot0[1] {
+                    Token::Int(raw) => raw.low_u32() as i32,
+                    other => return Err(anyhow!("Unexpected tick value {:?}", other)),
+                };
+                PoolReserves::V3 { sqrt_price_x96: uint(&slot0[0])?, liquidity: uint(&liquidity[0])?.low_u128(), tick }
+            }
+        };
+
+        let mut pool = self.pools.get_mut(&address)
+            .ok_or_else(|| anyhow!("Pool {:?} was removed during refresh", address))?;
+        pool.set_reserves(reserves, block_number, None);
+        Ok(pool.clone())
+    }
+
+    /// Refreshes every watched pool, returning the ones that failed
+    pub async fn refresh_all<M: Middleware + 'static>(&self, provider: &M) -> Vec<(Address, anyhow::Error)> {
+        stream::iter(self.addresses())
+            .map(|address| async move { (address, self.refresh(provider, address).await) })
+            .buffer_unordered(REFRESH_CONCURRENCY)
+            .filter_map(|(address, result)| async move { result.err().map(|e| (address, e)) })
+            .collect()
+            .await
+    }
+
+    /// Scans factory creation events in `[from_block, to_block]` and watches the pools
+    /// found. With `known_tokens` set, only pools between two known tokens are kept.
+    /// Returns the newly added pools.
+    pub async fn discover_from_factories<M: Middleware + 'static>(
+        &self,
+        provider: &M,
+        from_block: u64,
+        to_block: u64,
+        known_tokens: Option<&HashSet<Address>>,
+    ) -> Result<Vec<PoolState>> {
+        let mut added = Vec::new();
+        for factory in &self.factories {
+            let topic = match factory.kind {
+                DexKind::UniswapV2 => const_and_addr::pair_created_event_signature(),
+                DexKind::UniswapV3 => const_and_addr::pool_created_event_signature(),
+            };
+
+            let mut start = from_block;
+            while start <= to_block {
+                let end = to_block.min(start + const_and_addr::POOL_DISCOVERY_BLOCK_RANGE - 1);
+                let filter = Filter::new().address(factory.address).topic0(topic).from_block(start).to_block(end);
+                let logs = provider.get_logs(&filter).await
+                    .with_context(|| format!("Failed to fetch {} pool creations in blocks {}-{}", factory.name, start, end))?;
+
+                for pool in logs.iter().filter_map(|log| decode_pool_created(log, factory)) {
+                    let known = known_tokens.is_none_or(|tokens| tokens.contains(&pool.token0) && tokens.contains(&pool.token1));
+                    if known && self.add_pool(pool.clone()) {
+                        added.push(pool);
+                    }
+                }
+                start = end + 1;
+            }
+        }
+
+        info!("🏊 Discovered {} new pools in blocks {}-{}", added.len(), from_block, to_block);
+        Ok(added)
+    }
+
+    /// Looks up the pool of every factory for each pair (`getPair`, or `getPool` for
+    /// each V3 fee tier) and watches the ones that exist. Returns the newly added pools.
+    pub async fn resolve_pairs<M: Middleware + 'static>(&self, provider: &M, pairs: &[(Address, Address)]) -> Result<Vec<PoolState>> {
+        let mut added = Vec::new();
+        for &(a, b) in pairs {
+            let (token0, token1) = sort_tokens(a, b);
+            for factory in &self.factories {
+                let candidates: Vec<(Option<u32>, Address)> = match factory.kind {
+                    DexKind::UniswapV2 => {
+                        let out = call(provider, factory.address, "getPair(address,address)",
+                            &[Token::Address(token0), Token::Address(token1)], &[ParamType::Address]).await?;
+                        vec![(None, address(&out[0])?)]
+                    }
+                    DexKind::UniswapV3 => {
+                        let mut pools = Vec::new();
+                        for fee in const_and_addr::UNISWAP_V3_FEE_TIERS {
+                            let out = call(provider, factory.address, "getPool(address,address,uint24)",
+                                &[Token::Address(token0), Token::Address(token1), Token::Uint(fee.into())], &[ParamType::Address]).await?;
+                            pools.push((Some(fee), address(&out[0])?));
+                        }
+                        pools
+                    }
+                };
+
+                for (fee, pool_address) in candidates.into_iter().filter(|(_, address)| !address.is_zero()) {
+                    let pool = match fee {
+                        None => PoolState::v2(pool_address, factory.address, token0, token1, factory.fee_bps.unwrap_or(30)),
+                        Some(fee) => PoolState::v3(pool_address, factory.address, token0, token1, fee),
+                    };
+                    if self.add_pool(pool.clone()) {
+                        debug!("🏊 Resolved {} pool {:?}", factory.name, pool_address);
+                        added.push(pool);
+                    }
+                }
+            }
+        }
+        Ok(added)
+    }
+}
+
+/// Decodes a `PairCreated` (V2) or `PoolCreated` (V3) log of `factory`
+pub fn decode_pool_created(log: &Log, factory: &FactoryInfo) -> Option<PoolState> {
+    if log.address != factory.address || log.topics.len() < 3 {
+        return None;
+    }
+    let token0 = Address::from(log.topics[1]);
+    let token1 = Address::from(log.topics[2]);
+
+    match factory.kind {
+        DexKind::UniswapV2 if log.topics[0] == const_and_addr::pair_created_event_signature() && log.data.len() >= 32 => {
+            let pair = word_to_address(&log.data[0..32]);
+            Some(PoolState::v2(pair, factory.address, token0, token1, factory.fee_bps.unwrap_or(30)))
+        }
+        DexKind::UniswapV3 if log.topics[0] == const_and_addr::pool_created_event_signature()
+            && log.topics.len() >= 4 && log.data.len() >= 64 =>
+        {
+            let fee = topic_to_u256(&log.topics[3]).low_u32();
+            let pool = word_to_address(&log.data[32..64]);
+            Some(PoolState::v3(pool, factory.address, token0, token1, fee))
+        }
+        _ => None,
+    }
+}
+
+/// `eth_call` with ABI-encoded arguments, decoded into `outputs`
+pub(crate) async fn call<M: Middleware + 'static>(provider: &M, to: Address, signature: &str, args: &[Token], outputs: &[ParamType]) -> Result<Vec<Token>> {
+    let mut data = id(signature).to_vec();
+    data.extend(abi::encode(args));
+    let tx = TransactionRequest::new().to(to).data(Bytes::from(data)).into();
+
+    let raw = provider.call(&tx, None).await
+        .with_context(|| format!("{} on {:?} failed", signature, to))?;
+    abi::decode(outputs, &raw).with_context(|| format!("Unexpected {} output from {:?}", signature, to))
+}
+
+fn uint(token: &Token) -> Result<U256> {
+    token.clone().into_uint().ok_or_else(|| anyhow!("Expected uint, got {:?}", token))
+}
+
+fn address(token: &Token) -> Result<Address> {
+    token.clone().into_address().ok_or_else(|| anyhow!("Expected address, got {:?}", token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::H256;
+    use crate::address_book::AddressBook;
+
+    fn pool_log(address: Address, topic0: H256, data: Vec<u8>, block_number: u64) -> Log {
+        Log {
+            address,
+            topics: vec![topic0],
+            data: data.into(),
+            block_number: Some(block_number.into()),
+            ..Default::default()
+        }
+    }
+
+    fn word(value: U256) -> Vec<u8> {
+        let mut out = [0u8; 32];
+        value.to_big_endian(&mut out);
+        out.to_vec()
+    }
+
+    #[test]
+    fn test_sync_and_v3_swap_logs_update_watched_pools_only() {
+        let manager = PoolManager::for_chain(&ChainProfile::ethereum());
+        let usdc_weth = Address::from_slice(&hex_literal::hex!("B4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc"));
+        let v3_pool = Address::from_slice(&hex_literal::hex!("88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640"));
+        assert!(manager.is_monitored_pool(usdc_weth));
+        assert!(!manager.is_monitored_pool(Address::repeat_byte(0x42)));
+
+        let sync = const_and_addr::sync_event_signature();
+        let data = [word(1_000.into()), word(2_000.into())].concat();
+        assert!(manager.apply_log(&pool_log(usdc_weth, sync, data.clone(), 10)));
+        assert_eq!(manager.get(usdc_weth).unwrap().v2_reserves(), Some((1_000.into(), 2_000.into())));
+        // Stale and unwatched logs are ignored
+        assert!(!manager.apply_log(&pool_log(usdc_weth, sync, data.clone(), 9)));
+        assert!(!manager.apply_log(&pool_log(Address::repeat_byte(0x42), sync, data, 11)));
+
+        let mut tick = [0xffu8; 32];
+        tick[31] = 0xfe; // -2
+        let swap = [word(0.into()), word(0.into()), word(U256::one() << 96), word(5_000.into()), tick.to_vec()].concat();
+        assert!(manager.apply_log(&pool_log(v3_pool, const_and_addr::v3_swap_event_signature(), swap, 12)));
+        assert_eq!(
+            manager.get(v3_pool).unwrap().reserves,
+            PoolReserves::V3 { sqrt_price_x96: U256::one() << 96, liquidity: 5_000, tick: -2 }
+        );
+    }
+
+    #[test]
+    fn test_mint_and_burn_move_active_liquidity_in_range_only() {
+        use ethers::types::{H256, I256};
+
+        let manager = PoolManager::for_chain(&ChainProfile::ethereum());
+        let v3_pool = Address::from_slice(&hex_literal::hex!("88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640"));
+        let mut tick = [0xffu8; 32];
+        tick[31] = 0xfe; // -2
+        let swap = [word(0.into()), word(0.into()), word(U256::one() << 96), word(5_000.into()), tick.to_vec()].concat();
+        assert!(manager.apply_log(&pool_log(v3_pool, const_and_addr::v3_swap_event_signature(), swap, 12)));
+
+        let position = |topic0, data: Vec<u8>, lower: i32, upper: i32, index: u64| {
+            let mut log = pool_log(v3_pool, topic0, data, 13);
+            log.log_index = Some(index.into());
+            let tick_topic = |tick: i32| H256::from_slice(&word(I256::from(tick).into_raw()));
+            log.topics.extend([H256::repeat_byte(7), tick_topic(lower), tick_topic(upper)]);
+            log
+        };
+        let liquidity = || match manager.get(v3_pool).unwrap().reserves {
+            PoolReserves::V3 { liquidity, .. } => liquidity,
+            other => panic!("unexpected reserves {:?}", other),
+        };
+        let mint = |amount: u64| [word(U256::zero()), word(amount.into()), word(1.into()), word(1.into())].concat();
+        let burn = |amount: u64| [word(amount.into()), word(1.into()), word(1.into())].concat();
+
+        let in_range = position(const_and_addr::v3_mint_event_signature(), mint(1_000), -60, 60, 1);
+        assert!(manager.apply_log(&in_range));
+        assert_eq!(liquidity(), 6_000);
+        // A block processed again does not add its deltas twice
+        assert!(!manager.apply_log(&in_range));
+        assert_eq!(liquidity(), 6_000);
+        // Above the current tick: the tick data changes, the active liquidity does not
+        let above = position(const_and_addr::v3_mint_event_signature(), mint(1_000), 60, 120, 2);
+        assert!(!manager.apply_log(&above));
+        assert_eq!(liquidity(), 6_000);
+        assert!(manager.apply_log(&position(const_and_addr::v3_burn_event_signature(), burn(500), -60, 60, 3)));
+        assert_eq!(liquidity(), 5_500);
+
+        assert_eq!(manager.position_changes([&in_range, &above]), HashSet::from([v3_pool]));
+
+        // Burning more than the tracked liquidity leaves the pool unpriced instead of empty
+        assert!(manager.apply_log(&position(const_and_addr::v3_burn_event_signature(), burn(10_000), -60, 60, 4)));
+        assert_eq!(manager.get(v3_pool).unwrap().reserves, PoolReserves::Unknown);
+    }
+
+    #[test]
+    fn test_logs_covered_by_a_read_are_not_applied() {
+        let mut pool = PoolState::v2(Address::repeat_byte(1), Address::zero(), Address::repeat_byte(2), Address::repeat_byte(3), 30);
+        let reserves = |amount: u64| PoolReserves::V2 { reserve0: amount.into(), reserve1: amount.into() };
+        assert!(pool.set_reserves(reserves(1), 10, None));
+        // A read of block 10 already reflects every log of it
+        assert!(!pool.set_reserves(reserves(2), 10, Some(5)));
+        assert!(pool.set_reserves(reserves(3), 11, Some(0)));
+        assert!(!pool.set_reserves(reserves(4), 11, Some(0)));
+        assert!(pool.set_reserves(reserves(5), 11, Some(1)));
+        assert_eq!(pool.v2_reserves(), Some((5.into(), 5.into())));
+    }
+
+    #[test]
+    fn test_pools_can_be_added_and_removed_at_runtime() {
+        let ethereum = AddressBook::embedded().chain("ethereum").unwrap();
+        let (weth, usdc) = (ethereum.wrapped_native(), ethereum.token("USDC").unwrap().address);
+        let manager = PoolManager::for_chain(&ChainProfile::ethereum());
+        let seeded = manager.pools_for_pair(weth, usdc).len();
+        assert_eq!(seeded, 3);
+
+        let extra = PoolState::v2(Address::repeat_byte(1), Address::repeat_byte(2), usdc, weth, 30);
+        assert!(manager.add_pool(extra.clone()));
+        assert!(!manager.add_pool(extra.clone()));
+        assert_eq!(manager.pools_for_pair(weth, usdc).len(), seeded + 1);
+
+        assert_eq!(manager.remove_pool(extra.address), Some(extra.clone()));
+        assert!(!manager.is_monitored_pool(extra.address));
+        assert_eq!(manager.pools_for_pair(usdc, weth).len(), seeded);
+
+        // Concurrent adds of the same pool index it once
+        let added = std::thread::scope(|scope| {
+            let adds: Vec<_> = (0..8).map(|_| scope.spawn(|| manager.add_pool(extra.clone()))).collect();
+            adds.into_iter().map(|add| add.join().unwrap()).filter(|added| *added).count()
+        });
+        assert_eq!(added, 1);
+        assert_eq!(manager.pools_for_pair(weth, usdc).len(), seeded + 1);
+    }
+
+    #[test]
+    fn test_decode_factory_creation_events() {
+        let ethereum = AddressBook::embedded().chain("ethereum").unwrap();
+        let (token0, token1) = (Address::repeat_byte(0x0a), Address::repeat_byte(0x0b));
+        let pool = Address::repeat_byte(0x0c);
+
+        let v2 = ethereum.factory("uniswap_v2").unwrap();
+        let log = Log {
+            address: v2.address,
+            topics: vec![const_and_addr::pair_created_event_signature(), token0.into(), token1.into()],
+            data: [word(U256::from_big_endian(pool.as_bytes())), word(7.into())].concat().into(),
+            ..Default::default()
+        };
+        let decoded = decode_pool_created(&log, v2).unwrap();
+        assert_eq!((decoded.address, decoded.token0, decoded.token1, decoded.fee), (pool, token0, token1, 3_000));
+
+        let v3 = ethereum.factory("uniswap_v3").unwrap();
+        let log = Log {
+            address: v3.address,
+            topics: vec![const_and_addr::pool_created_event_signature(), token0.into(), token1.into(), H256::from_low_u64_be(500)],
+            data: [word(10.into()), word(U256::from_big_endian(pool.as_bytes()))].concat().into(),
+            ..Default::default()
+        };
+        let decoded = decode_pool_created(&log, v3).unwrap();
+        assert_eq!((decoded.kind, decoded.address, decoded.fee), (DexKind::UniswapV3, pool, 500));
+        // A V2 event from the V3 factory is not a pool
+        assert!(decode_pool_created(&Log { topics: vec![const_and_addr::pair_created_event_signature(), token0.into(), token1.into()], ..log }, v3).is_none());
+    }
+}
@@ -0,0 +1,132 @@
+//! Live state of a single AMM pool
+use ethers::types::{Address, H256, U256};
+use serde::Serialize;
+
+use crate::address_book::{DexKind, FactoryInfo, PoolInfo};
+
+/// Liquidity snapshot, in the shape the pool's AMM design stores it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum PoolReserves {
+    /// Not fetched from the chain yet
+    Unknown,
+    V2 {
+        reserve0: U256,
+        reserve1: U256,
+    },
+    V3 {
+        sqrt_price_x96: U256,
+        liquidity: u128,
+        tick: i32,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PoolState {
+    pub address: Address,
+    pub kind: DexKind,
+    pub factory: Address,
+    pub token0: Address,
+    pub token1: Address,
+    /// Swap fee in hundredths of a basis point (3000 = 0.3%), for V2 and V3 alike
+    pub fee: u32,
+    pub reserves: PoolReserves,
+    /// Block of the last reserve update, 0 until the first one
+    pub last_updated_block: u64,
+    /// Index of the log behind the last update within its block, `None` when the state
+    /// was read after the whole block
+    pub last_log_index: Option<u64>,
+}
+
+impl PoolState {
+    pub fn v2(address: Address, factory: Address, token0: Address, token1: Address, fee_bps: u16) -> Self {
+        Self::new(address, DexKind::UniswapV2, factory, token0, token1, u32::from(fee_bps) * 100)
+    }
+
+    pub fn v3(address: Address, factory: Address, token0: Address, token1: Address, fee: u32) -> Self {
+        Self::new(address, DexKind::UniswapV3, factory, token0, token1, fee)
+    }
+
+    fn new(address: Address, kind: DexKind, factory: Address, token0: Address, token1: Address, fee: u32) -> Self {
+        Self {
+            address,
+            kind,
+            factory,
+            token0,
+            token1,
+            fee,
+            reserves: PoolReserves::Unknown,
+            last_updated_block: 0,
+            last_log_index: None,
+        }
+    }
+
+    /// State for an address book pool, `None` if its factory is not in the book
+    pub fn from_pool_info(pool: &PoolInfo, factories: &[FactoryInfo]) -> Option<Self> {
+        let factory = factories.iter().find(|f| f.name == pool.factory)?;
+        Some(match pool.kind {
+            DexKind::UniswapV2 => Self::v2(pool.address, factory.address, pool.token0, pool.token1, factory.fee_bps.unwrap_or(30)),
+            DexKind::UniswapV3 => Self::v3(pool.address, factory.address, pool.token0, pool.token1, pool.fee.unwrap_or(3_000)),
+        })
+    }
+
+    pub fn has_token(&self, token: Address) -> bool {
+        self.token0 == token || self.token1 == token
+    }
+
+    /// The token on the other side of `token`, `None` if the pool does not hold it
+    pub fn other_token(&self, token: Address) -> Option<Address> {
+        if token == self.token0 {
+            Some(self.token1)
+        } else if token == self.token1 {
+            Some(self.token0)
+        } else {
+            None
+        }
+    }
+
+    /// V2 reserves as `(reserve0, reserve1)`
+    pub fn v2_reserves(&self) -> Option<(U256, U256)> {
+        match self.reserves {
+            PoolReserves::V2 { reserve0, reserve1 } => Some((reserve0, reserve1)),
+            _ => None,
+        }
+    }
+
+    /// Whether the log at `log_index` of `block_number` is not reflected in the state yet.
+    /// A log without an index stands for the end of its block.
+    pub fn is_behind(&self, block_number: u64, log_index: Option<u64>) -> bool {
+        (block_number, log_index.unwrap_or(u64::MAX)) > (self.last_updated_block, self.last_log_index.unwrap_or(u64::MAX))
+    }
+
+    /// Updates are ignored unless they come after the current state, so a log applied
+    /// twice or one already covered by a read counts once
+    pub fn set_reserves(&mut self, reserves: PoolReserves, block_number: u64, log_index: Option<u64>) -> bool {
+        if !self.is_behind(block_number, log_index) {
+            return false;
+        }
+        self.reserves = reserves;
+        self.last_updated_block = block_number;
+        self.last_log_index = log_index;
+        true
+    }
+}
+
+/// Tokens of a pair in pool order (`token0 < token1`)
+pub fn sort_tokens(a: Address, b: Address) -> (Address, Address) {
+    if a < b { (a, b) } else { (b, a) }
+}
+
+/// Reads an ABI word as an address (last 20 bytes)
+pub(crate) fn word_to_address(word: &[u8]) -> Address {
+    Address::from_slice(&word[12..32])
+}
+
+pub(crate) fn topic_to_u256(topic: &H256) -> U256 {
+    U256::from_big_endian(topic.as_bytes())
+}
+
+/// Sign-extends the low 24 bits of an ABI word (`int24`)
+pub(crate) fn word_to_i24(word: &[u8]) -> i32 {
+    let raw = (i32::from(word[29]) << 16) | (i32::from(word[30]) << 8) | i32::from(word[31]);
+    (raw << 8) >> 8
+}
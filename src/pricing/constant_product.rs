@@ -0,0 +1,287 @@
+//! Constant-product (x * y = k) swap math
+//!
+//! `get_amount_out` and `get_amount_in` reproduce `UniswapV2Library` exactly, including
+//! its rounding and revert conditions, for any fee in basis points. Uniswap computes
+//! with `997 / 1000`; scaling both sides to `9970 / 10000` leaves every floor division
+//! unchanged, and also covers forks such as PancakeSwap (`9975 / 10000`).
+use anyhow::{Result, anyhow};
+use ethers::types::{Address, U256};
+
+use crate::{
+    address_book::DexKind,
+    pools::PoolState,
+    storage::{SlotKey, StorageDelta},
+};
+
+const FEE_DENOMINATOR: u64 = 10_000;
+
+/// Storage slots the drift detector reports V2 reserves under
+const RESERVE0_SLOT: u64 = 8;
+const RESERVE1_SLOT: u64 = 9;
+
+/// Output for an exact input, `UniswapV2Library.getAmountOut`
+pub fn get_amount_out(amount_in: U256, reserve_in: U256, reserve_out: U256, fee_bps: u16) -> Result<U256> {
+    if amount_in.is_zero() {
+        return Err(anyhow!("UniswapV2Library: INSUFFICIENT_INPUT_AMOUNT"));
+    }
+    if reserve_in.is_zero() || reserve_out.is_zero() {
+        return Err(anyhow!("UniswapV2Library: INSUFFICIENT_LIQUIDITY"));
+    }
+    let amount_in_with_fee = mul(amount_in, fee_multiplier(fee_bps)?)?;
+    let numerator = mul(amount_in_with_fee, reserve_out)?;
+    let denominator = add(mul(reserve_in, FEE_DENOMINATOR.into())?, amount_in_with_fee)?;
+    Ok(numerator / denominator)
+}
+
+/// Input needed for an exact output, `UniswapV2Library.getAmountIn`
+pub fn get_amount_in(amount_out: U256, reserve_in: U256, reserve_out: U256, fee_bps: u16) -> Result<U256> {
+    if amount_out.is_zero() {
+        return Err(anyhow!("UniswapV2Library: INSUFFICIENT_OUTPUT_AMOUNT"));
+    }
+    if reserve_in.is_zero() || reserve_out.is_zero() {
+        return Err(anyhow!("UniswapV2Library: INSUFFICIENT_LIQUIDITY"));
+    }
+    let numerator = mul(mul(reserve_in, amount_out)?, FEE_DENOMINATOR.into())?;
+    let remaining = reserve_out.checked_sub(amount_out).ok_or_else(|| anyhow!("ds-math-sub-underflow"))?;
+    let denominator = mul(remaining, fee_multiplier(fee_bps)?)?;
+    if denominator.is_zero() {
+        // Buying the whole reserve divides by zero on chain
+        return Err(anyhow!("UniswapV2Library: division by zero"));
+    }
+    add(numerator / denominator, U256::one())
+}
+
+/// Chained `get_amount_out` over `(reserve_in, reserve_out, fee_bps)` hops, like
+/// `getAmountsOut`. Returns the amount after each hop, starting with `amount_in`.
+pub fn get_amounts_out(amount_in: U256, hops: &[(U256, U256, u16)]) -> Result<Vec<U256>> {
+    let mut amounts = vec![amount_in];
+    for &(reserve_in, reserve_out, fee_bps) in hops {
+        let last = *amounts.last().unwrap_or(&amount_in);
+        amounts.push(get_amount_out(last, reserve_in, reserve_out, fee_bps)?);
+    }
+    Ok(amounts)
+}
+
+/// Reserves and fee of one V2-style pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConstantProductPool {
+    pub reserve0: U256,
+    pub reserve1: U256,
+    pub fee_bps: u16,
+}
+
+impl ConstantProductPool {
+    pub fn new(reserve0: U256, reserve1: U256, fee_bps: u16) -> Self {
+        Self { reserve0, reserve1, fee_bps }
+    }
+
+    /// `None` for V3 pools and pools whose reserves are not known yet
+    pub fn from_pool(pool: &PoolState) -> Option<Self> {
+        if pool.kind != DexKind::UniswapV2 {
+            return None;
+        }
+        let (reserve0, reserve1) = pool.v2_reserves()?;
+        Some(Self::new(reserve0, reserve1, (pool.fee / 100) as u16))
+    }
+
+    /// Applies the reserve deltas of `contract`, in order. Returns `true` if any applied.
+    pub fn apply_deltas(&mut self, contract: Address, deltas: &[StorageDelta]) -> bool {
+        let mut applied = false;
+        for delta in deltas.iter().filter(|d| d.contract == contract) {
+            let value = U256::from_big_endian(delta.new_value.as_bytes());
+            match delta.slot_key {
+                SlotKey::Reserves(RESERVE0_SLOT) => self.reserve0 = value,
+                SlotKey::Reserves(RESERVE1_SLOT) => self.reserve1 = value,
+                _ => continue,
+            }
+            applied = true;
+        }
+        applied
+    }
+
+    /// `(reserve_in, reserve_out)` for a swap direction
+    pub fn reserves(&self, zero_for_one: bool) -> (U256, U256) {
+        if zero_for_one { (self.reserve0, self.reserve1) } else { (self.reserve1, self.reserve0) }
+    }
+
+    pub fn amount_out(&self, zero_for_one: bool, amount_in: U256) -> Result<U256> {
+        let (reserve_in, reserve_out) = self.reserves(zero_for_one);
+        get_amount_out(amount_in, reserve_in, reserve_out, self.fee_bps)
+    }
+
+    pub fn amount_in(&self, zero_for_one: bool, amount_out: U256) -> Result<U256> {
+        let (reserve_in, reserve_out) = self.reserves(zero_for_one);
+        get_amount_in(amount_out, reserve_in, reserve_out, self.fee_bps)
+    }
+
+    /// Pool state after swapping `amount_in`, as the pair's `Sync` would report it
+    pub fn after_swap(&self, zero_for_one: bool, amount_in: U256) -> Result<Self> {
+        let amount_out = self.amount_out(zero_for_one, amount_in)?;
+        let (reserve_in, reserve_out) = self.reserves(zero_for_one);
+        let (reserve_in, reserve_out) = (add(reserve_in, amount_in)?, reserve_out - amount_out);
+        Ok(if zero_for_one {
+            Self::new(reserve_in, reserve_out, self.fee_bps)
+        } else {
+            Self::new(reserve_out, reserve_in, self.fee_bps)
+        })
+    }
+
+    /// Marginal price of the input token in output token units, before fees. Raw token
+    /// units; use `spot_price_scaled` for human-readable prices.
+    pub fn spot_price(&self, zero_for_one: bool) -> f64 {
+        let (reserve_in, reserve_out) = self.reserves(zero_for_one);
+        u256_to_f64(reserve_out) / u256_to_f64(reserve_in)
+    }
+
+    /// `spot_price` adjusted for token decimals
+    pub fn spot_price_scaled(&self, zero_for_one: bool, decimals_in: u8, decimals_out: u8) -> f64 {
+        self.spot_price(zero_for_one) * 10f64.powi(i32::from(decimals_in) - i32::from(decimals_out))
+    }
+
+    /// Fraction by which the execution price of a swap falls short of the spot price,
+    /// excluding the LP fee (0.01 = 1%)
+    pub fn price_impact(&self, zero_for_one: bool, amount_in: U256) -> Result<f64> {
+        let amount_out = self.amount_out(zero_for_one, amount_in)?;
+        let amount_in_after_fee = u256_to_f64(amount_in) * (FEE_DENOMINATOR - u64::from(self.fee_bps)) as f64
+            / FEE_DENOMINATOR as f64;
+        let at_spot = amount_in_after_fee * self.spot_price(zero_for_one);
+        Ok((1.0 - u256_to_f64(amount_out) / at_spot).max(0.0))
+    }
+}
+
+/// `10000 - fee_bps`, the on-chain `997` scaled to a 10000 denominator
+fn fee_multiplier(fee_bps: u16) -> Result<U256> {
+    if u64::from(fee_bps) >= FEE_DENOMINATOR {
+        return Err(anyhow!("Fee of {} bps leaves nothing to swap", fee_bps));
+    }
+    Ok((FEE_DENOMINATOR - u64::from(fee_bps)).into())
+}
+
+fn mul(a: U256, b: U256) -> Result<U256> {
+    a.checked_mul(b).ok_or_else(|| anyhow!("ds-math-mul-overflow"))
+}
+
+fn add(a: U256, b: U256) -> Result<U256> {
+    a.checked_add(b).ok_or_else(|| anyhow!("ds-math-add-overflow"))
+}
+
+/// Lossy conversion for prices and ratios
+pub fn u256_to_f64(value: U256) -> f64 {
+    value.0.iter().rev().fold(0.0, |acc, limb| acc * 18_446_744_073_709_551_616.0 + *limb as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::H256;
+
+    #[test]
+    fn test_amounts_match_uniswap_v2_library() {
+        let (r_in, r_out) = (U256::from(1_000u64), U256::from(1_000u64));
+        // 100 * 997 * 1000 / (1000 * 1000 + 100 * 997) = 90.66
+        assert_eq!(get_amount_out(100.into(), r_in, r_out, 30).unwrap(), 90.into());
+        // 1000 * 90 * 1000 / ((1000 - 90) * 997) + 1 = 99.19 + 1
+        assert_eq!(get_amount_in(90.into(), r_in, r_out, 30).unwrap(), 100.into());
+
+        assert!(get_amount_out(U256::zero(), r_in, r_out, 30).unwrap_err().to_string().contains("INSUFFICIENT_INPUT_AMOUNT"));
+        assert!(get_amount_out(1.into(), U256::zero(), r_out, 30).unwrap_err().to_string().contains("INSUFFICIENT_LIQUIDITY"));
+        assert!(get_amount_in(r_out, r_in, r_out, 30).is_err());
+
+        // Round trip: paying get_amount_in always buys at least the requested output
+        let pool = ConstantProductPool::new(U256::exp10(24), U256::from(5u64) * U256::exp10(20), 30);
+        for amount_out in [1u64, 997, 1_000_000, 123_456_789_012_345] {
+            let amount_in = pool.amount_in(false, amount_out.into()).unwrap();
+            assert!(pool.amount_out(false, amount_in).unwrap() >= amount_out.into());
+        }
+    }
+
+    #[test]
+    fn test_spot_price_impact_and_reserve_updates() {
+        // 2000 USDC (6 decimals) per WETH (18 decimals)
+        let mut pool = ConstantProductPool::new(U256::from(2_000_000u64) * U256::exp10(6), U256::exp10(21), 30);
+        assert!((pool.spot_price_scaled(false, 18, 6) - 2_000.0).abs() < 1e-9);
+        assert!((pool.spot_price_scaled(true, 6, 18) - 0.0005).abs() < 1e-15);
+
+        // 1% of the reserve moves the price by about 1%, small trades barely at all
+        let impact = pool.price_impact(false, U256::exp10(19)).unwrap();
+        assert!((impact - 0.00997 / 1.00997).abs() < 1e-9, "{}", impact);
+        assert!(pool.price_impact(false, U256::exp10(15)).unwrap() < 1e-5);
+
+        let after = pool.after_swap(false, U256::exp10(19)).unwrap();
+        assert_eq!(after.reserve1, U256::exp10(21) + U256::exp10(19));
+        assert_eq!(after.reserve0, pool.reserve0 - pool.amount_out(false, U256::exp10(19)).unwrap());
+
+        let contract = Address::repeat_byte(1);
+        let delta = |contract, slot, value: u64| StorageDelta {
+            slot_key: SlotKey::Reserves(slot),
+            old_value: H256::zero(),
+            new_value: H256::from_low_u64_be(value),
+            change_type: crate::storage::StorageChangeType::DirectWrite,
+            impact_score: 0.0,
+            confidence: 1.0,
+            block_number: 1,
+            contract,
+        };
+        assert!(pool.apply_deltas(contract, &[delta(contract, 9, 7), delta(Address::zero(), 8, 1)]));
+        assert_eq!((pool.reserve0, pool.reserve1), (U256::from(2_000_000u64) * U256::exp10(6), 7.into()));
+    }
+
+    /// Differential test against the router's `getAmountsOut`/`getAmountsIn` on a mainnet
+    /// fork. Needs anvil and FORK_URL pointing at an archive-capable mainnet endpoint.
+    #[tokio::test]
+    #[ignore = "requires anvil and FORK_URL"]
+    async fn test_matches_router_on_anvil_fork() -> anyhow::Result<()> {
+        use ethers::{
+            abi::{self, ParamType, Token},
+            providers::{Http, Middleware, Provider},
+            types::TransactionRequest,
+            utils::{Anvil, id},
+        };
+        use crate::chain::ChainProfile;
+
+        // Run explicitly, so a missing endpoint is a failure rather than a silent pass
+        let fork_url = std::env::var("FORK_URL").map_err(|_| anyhow::anyhow!("FORK_URL must point at a mainnet archive endpoint"))?;
+        let anvil = Anvil::new().fork(fork_url).arg("--silent").spawn();
+        let provider = Provider::<Http>::try_from(anvil.endpoint())?;
+
+        let addresses = ChainProfile::ethereum().addresses;
+        let (weth, usdc) = (addresses.wrapped_native(), addresses.token("USDC").unwrap().address);
+        let router = addresses.router("uniswap_v2").unwrap().address;
+        let pair = addresses.pools().iter().find(|p| p.factory == "uniswap_v2" && p.token0 == usdc && p.token1 == weth).unwrap().address;
+
+        let call = |to, signature: &str, args: Vec<Token>, output: ParamType| {
+            let mut data = id(signature).to_vec();
+            data.extend(abi::encode(&args));
+            let provider = provider.clone();
+            async move {
+                let tx = TransactionRequest::new().to(to).data(data).into();
+                let raw = provider.call(&tx, None).await?;
+                anyhow::Ok(abi::decode(&[output], &raw)?.remove(0))
+            }
+        };
+        let reserves = call(pair, "getReserves()", vec![], ParamType::Tuple(vec![ParamType::Uint(112); 2])).await;
+        let (reserve0, reserve1) = match reserves {
+            // getReserves returns three words; decoding the first two as a tuple is enough
+            Ok(Token::Tuple(values)) => (values[0].clone().into_uint().unwrap(), values[1].clone().into_uint().unwrap()),
+            other => anyhow::bail!("Unexpected getReserves output {:?}", other),
+        };
+        let pool = ConstantProductPool::new(reserve0, reserve1, 30);
+
+        let path = Token::Array(vec![Token::Address(weth), Token::Address(usdc)]);
+        let amounts = ParamType::Array(Box::new(ParamType::Uint(256)));
+        for exponent in [0u32, 6, 12, 15, 18, 20, 22] {
+            for amount in [U256::from(10u64).pow(exponent.into()), U256::from(7u64) * U256::from(10u64).pow(exponent.into()) + 3] {
+                let out = call(router, "getAmountsOut(uint256,address[])", vec![Token::Uint(amount), path.clone()], amounts.clone()).await?;
+                let expected = out.into_array().unwrap()[1].clone().into_uint().unwrap();
+                assert_eq!(pool.amount_out(false, amount)?, expected, "getAmountsOut({})", amount);
+
+                if amount < reserve0 {
+                    let out = call(router, "getAmountsIn(uint256,address[])", vec![Token::Uint(amount), path.clone()], amounts.clone()).await?;
+                    let expected = out.into_array().unwrap()[0].clone().into_uint().unwrap();
+                    assert_eq!(pool.amount_in(false, amount)?, expected, "getAmountsIn({})", amount);
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,9 @@
+// ! Pricing module - swap math for the supported AMM designs
+
+mod constant_product;
+mod quoter;
+pub mod v3;
+
+pub use constant_product::{ConstantProductPool, get_amount_in, get_amount_out, get_amounts_out, u256_to_f64};
+pub use quoter::PoolQuoter;
+pub use v3::{V3PoolSnapshot, V3SwapResult};
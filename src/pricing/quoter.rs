@@ -0,0 +1,91 @@
+//! One quoting interface over V2 reserves and V3 snapshots
+use anyhow::Result;
+use ethers::types::{Address, U256};
+
+use crate::{
+    address_book::DexKind,
+    pools::{PoolReserves, PoolState},
+};
+use super::{
+    constant_product::{ConstantProductPool, u256_to_f64},
+    v3::V3PoolSnapshot,
+};
+
+/// 2^96 as f64, for converting sqrt prices
+const Q96: f64 = 79_228_162_514_264_337_593_543_950_336.0;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PoolQuoter {
+    V2(ConstantProductPool),
+    V3(V3PoolSnapshot),
+}
+
+impl PoolQuoter {
+    /// Quoter for a pool's current state. V3 pools need a snapshot with tick data, whose
+    /// price and liquidity are replaced by the pool's latest values.
+    pub fn for_pool(pool: &PoolState, snapshot: Option<&V3PoolSnapshot>) -> Option<Self> {
+        match (pool.kind, &pool.reserves) {
+            (DexKind::UniswapV2, _) => ConstantProductPool::from_pool(pool).map(Self::V2),
+            (DexKind::UniswapV3, PoolReserves::V3 { sqrt_price_x96, liquidity, tick }) => {
+                let mut snapshot = snapshot?.clone();
+                snapshot.sqrt_price_x96 = *sqrt_price_x96;
+                snapshot.liquidity = *liquidity;
+                snapshot.tick = *tick;
+                Some(Self::V3(snapshot))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn amount_out(&self, zero_for_one: bool, amount_in: U256) -> Result<U256> {
+        match self {
+            Self::V2(pool) => pool.amount_out(zero_for_one, amount_in),
+            Self::V3(pool) => Ok(pool.swap(zero_for_one, amount_in, true, None)?.amount_out),
+        }
+    }
+
+    /// Marginal price in raw output units per raw input unit, before fees
+    pub fn spot_price(&self, zero_for_one: bool) -> f64 {
+        match self {
+            Self::V2(pool) => pool.spot_price(zero_for_one),
+            Self::V3(pool) => {
+                let price = (u256_to_f64(pool.sqrt_price_x96) / Q96).powi(2);
+                if zero_for_one { price } else { 1.0 / price }
+            }
+        }
+    }
+
+    /// LP fee as a fraction of the input
+    pub fn fee(&self) -> f64 {
+        match self {
+            Self::V2(pool) => f64::from(pool.fee_bps) / 10_000.0,
+            Self::V3(pool) => f64::from(pool.fee) / 1_000_000.0,
+        }
+    }
+
+    /// Shortfall of the execution price against the spot price, excluding the fee
+    pub fn price_impact(&self, zero_for_one: bool, amount_in: U256) -> Result<f64> {
+        let amount_out = self.amount_out(zero_for_one, amount_in)?;
+        let at_spot = u256_to_f64(amount_in) * (1.0 - self.fee()) * self.spot_price(zero_for_one);
+        Ok((1.0 - u256_to_f64(amount_out) / at_spot).max(0.0))
+    }
+
+    /// Relative drop of the marginal price a swap of `amount_in` causes. Unlike
+    /// `price_impact` it is free of output rounding, so it grows steadily with size.
+    pub fn price_movement(&self, zero_for_one: bool, amount_in: U256) -> Result<f64> {
+        let after = match self {
+            Self::V2(pool) => Self::V2(pool.after_swap(zero_for_one, amount_in)?),
+            Self::V3(pool) => {
+                let mut after = pool.clone();
+                after.apply_swap(&pool.swap(zero_for_one, amount_in, true, None)?);
+                Self::V3(after)
+            }
+        };
+        Ok(1.0 - after.spot_price(zero_for_one) / self.spot_price(zero_for_one))
+    }
+
+    /// Swap direction for selling `token_in` into the pool
+    pub fn zero_for_one(pool: &PoolState, token_in: Address) -> bool {
+        token_in == pool.token0
+    }
+}
@@ -0,0 +1,27 @@
+//! 512-bit intermediate multiplication, `FullMath` and `UnsafeMath`
+use anyhow::{Result, anyhow};
+use ethers::types::{U256, U512};
+
+/// `floor(a * b / denominator)` without intermediate overflow
+pub fn mul_div(a: U256, b: U256, denominator: U256) -> Result<U256> {
+    if denominator.is_zero() {
+        return Err(anyhow!("FullMath: division by zero"));
+    }
+    let result = a.full_mul(b) / U512::from(denominator);
+    U256::try_from(result).map_err(|_| anyhow!("FullMath: result overflows uint256"))
+}
+
+/// `ceil(a * b / denominator)` without intermediate overflow
+pub fn mul_div_rounding_up(a: U256, b: U256, denominator: U256) -> Result<U256> {
+    let result = mul_div(a, b, denominator)?;
+    if (a.full_mul(b) % U512::from(denominator)).is_zero() {
+        return Ok(result);
+    }
+    result.checked_add(U256::one()).ok_or_else(|| anyhow!("FullMath: result overflows uint256"))
+}
+
+/// `ceil(x / y)`, `y` must be non-zero
+pub fn div_rounding_up(x: U256, y: U256) -> U256 {
+    let quotient = x / y;
+    if (x % y).is_zero() { quotient } else { quotient + 1 }
+}
@@ -0,0 +1,11 @@
+//! Uniswap V3 concentrated-liquidity math, ported from the core contracts' libraries
+
+pub mod full_math;
+pub mod tick_math;
+pub mod sqrt_price_math;
+pub mod swap_math;
+pub mod tick_bitmap;
+mod pool;
+
+pub use pool::{V3PoolSnapshot, V3SwapResult, default_fee_tiers, fee_from_bps, tick_spacing_for_fee};
+pub use tick_bitmap::TickBitmap;
@@ -0,0 +1,373 @@
+//! Off-chain copy of a V3 pool's swap state
+//!
+//! A snapshot holds `slot0`, the active liquidity and the tick bitmap words around the
+//! current tick, with `liquidityNet` for every initialized tick in them. `swap` runs the
+//! loop of `UniswapV3Pool.swap` over that data, so quotes match the pool (and QuoterV2)
+//! exactly as long as the swap stays inside the loaded words.
+use std::collections::BTreeMap;
+use anyhow::{Result, anyhow};
+use ethers::{
+    abi::{ParamType, Token},
+    providers::Middleware,
+    types::{Address, I256, U256},
+};
+use futures::stream::{self, StreamExt, TryStreamExt};
+
+use crate::{
+    address_book::DexKind,
+    const_and_addr,
+    pools::{PoolReserves, PoolState, pool_manager::call},
+};
+use super::{
+    swap_math::compute_swap_step,
+    tick_bitmap::{TickBitmap, compress, position},
+    tick_math::{MAX_SQRT_RATIO, MAX_TICK, MIN_SQRT_RATIO, MIN_TICK, get_sqrt_ratio_at_tick, get_tick_at_sqrt_ratio},
+};
+
+/// Concurrent `ticks()` calls while fetching a snapshot
+const TICK_FETCH_CONCURRENCY: usize = 16;
+
+/// Tick spacing the V3 factory enables for a fee tier (hundredths of a basis point)
+pub fn tick_spacing_for_fee(fee: u32) -> Option<i32> {
+    match fee {
+        100 => Some(1),
+        500 => Some(10),
+        3_000 => Some(60),
+        10_000 => Some(200),
+        _ => None,
+    }
+}
+
+/// Fee in hundredths of a basis point for a tier given in basis points, as in
+/// `const_and_addr::UNISWAP_V3_FEE_*`
+pub fn fee_from_bps(fee_bps: u16) -> u32 {
+    u32::from(fee_bps) * 100
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct V3SwapResult {
+    pub amount_in: U256,
+    pub amount_out: U256,
+    /// LP fees, included in `amount_in`
+    pub fee_amount: U256,
+    pub sqrt_price_x96_after: U256,
+    pub tick_after: i32,
+    pub liquidity_after: u128,
+    pub initialized_ticks_crossed: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct V3PoolSnapshot {
+    pub address: Address,
+    pub token0: Address,
+    pub token1: Address,
+    pub fee: u32,
+    pub tick_spacing: i32,
+    pub sqrt_price_x96: U256,
+    pub tick: i32,
+    pub liquidity: u128,
+    bitmap: TickBitmap,
+    liquidity_net: BTreeMap<i32, i128>,
+}
+
+impl V3PoolSnapshot {
+    /// Snapshot of a pool's current price without any tick data yet
+    pub fn new(pool: &PoolState, tick_spacing: i32) -> Result<Self> {
+        let PoolReserves::V3 { sqrt_price_x96, liquidity, tick } = pool.reserves else {
+            return Err(anyhow!("Pool {:?} has no V3 state", pool.address));
+        };
+        Ok(Self {
+            address: pool.address,
+            token0: pool.token0,
+            token1: pool.token1,
+            fee: pool.fee,
+            tick_spacing,
+            sqrt_price_x96,
+            tick,
+            liquidity,
+            bitmap: TickBitmap::default(),
+            liquidity_net: BTreeMap::new(),
+        })
+    }
+
+    /// Records a bitmap word; the ticks it marks need `set_liquidity_net` too
+    pub fn insert_word(&mut self, word_position: i16, word: U256) {
+        self.bitmap.insert_word(word_position, word);
+    }
+
+    pub fn set_liquidity_net(&mut self, tick: i32, liquidity_net: i128) {
+        self.liquidity_net.insert(tick, liquidity_net);
+    }
+
+    /// Fetches `slot0`, liquidity and `word_radius` bitmap words on either side of the
+    /// current one, plus `liquidityNet` of every initialized tick in them
+    pub async fn fetch<M: Middleware + 'static>(provider: &M, pool: &PoolState, word_radius: i16) -> Result<Self> {
+        if pool.kind != DexKind::UniswapV3 {
+            return Err(anyhow!("Pool {:?} is not a V3 pool", pool.address));
+        }
+        let address = pool.address;
+        let slot0 = call(provider, address, "slot0()", &[], &[
+            ParamType::Uint(160), ParamType::Int(24), ParamType::Uint(16), ParamType::Uint(16),
+            ParamType::Uint(16), ParamType::Uint(8), ParamType::Bool,
+        ]).await?;
+        let liquidity = call(provider, address, "liquidity()", &[], &[ParamType::Uint(128)]).await?;
+        let spacing = call(provider, address, "tickSpacing()", &[], &[ParamType::Int(24)]).await?;
+
+        let mut state = pool.clone();
+        state.reserves = PoolReserves::V3 {
+            sqrt_price_x96: uint(&slot0[0])?,
+            liquidity: uint(&liquidity[0])?.low_u128(),
+            tick: int(&slot0[1])?.low_i32(),
+        };
+        let mut snapshot = Self::new(&state, int(&spacing[0])?.low_i32())?;
+
+        let (center, _) = position(compress(snapshot.tick, snapshot.tick_spacing));
+        for word_position in center.saturating_sub(word_radius)..=center.saturating_add(word_radius) {
+            let word = call(provider, address, "tickBitmap(int16)", &[Token::Int(I256::from(word_position).into_raw())], &[ParamType::Uint(256)]).await?;
+            snapshot.insert_word(word_position, uint(&word[0])?);
+        }
+
+        let ticks = snapshot.bitmap.initialized_ticks(snapshot.tick_spacing);
+        let nets: Vec<(i32, i128)> = stream::iter(ticks)
+            .map(|tick| async move {
+                let info = call(provider, address, "ticks(int24)", &[Token::Int(I256::from(tick).into_raw())], &[
+                    ParamType::Uint(128), ParamType::Int(128), ParamType::Uint(256), ParamType::Uint(256),
+                    ParamType::Int(56), ParamType::Uint(160), ParamType::Uint(32), ParamType::Bool,
+                ]).await?;
+                anyhow::Ok((tick, int(&info[1])?.low_i128()))
+            })
+            .buffer_unordered(TICK_FETCH_CONCURRENCY)
+            .try_collect()
+            .await?;
+        for (tick, net) in nets {
+            snapshot.set_liquidity_net(tick, net);
+        }
+        Ok(snapshot)
+    }
+
+    /// `UniswapV3Pool.swap` without transfers. `amount` is the exact input, or the exact
+    /// output when `exact_input` is false. Without a limit the swap may move the price
+    /// all the way to the end of the curve, as QuoterV2 does with a zero limit.
+    pub fn swap(&self, zero_for_one: bool, amount: U256, exact_input: bool, sqrt_price_limit_x96: Option<U256>) -> Result<V3SwapResult> {
+        if amount.is_zero() {
+            return Err(anyhow!("AS: amount specified is zero"));
+        }
+        let limit = sqrt_price_limit_x96.unwrap_or(if zero_for_one { *MIN_SQRT_RATIO + 1 } else { *MAX_SQRT_RATIO - 1 });
+        let valid_limit = if zero_for_one {
+            limit < self.sqrt_price_x96 && limit > *MIN_SQRT_RATIO
+        } else {
+            limit > self.sqrt_price_x96 && limit < *MAX_SQRT_RATIO
+        };
+        if !valid_limit {
+            return Err(anyhow!("SPL: sqrt price limit {} is on the wrong side of the price", limit));
+        }
+
+        let mut remaining = amount;
+        let mut calculated = U256::zero();
+        let mut fee_amount = U256::zero();
+        let mut sqrt_price_x96 = self.sqrt_price_x96;
+        let mut tick = self.tick;
+        let mut liquidity = self.liquidity;
+        let mut initialized_ticks_crossed = 0;
+
+        while !remaining.is_zero() && sqrt_price_x96 != limit {
+            let sqrt_price_start_x96 = sqrt_price_x96;
+            let (tick_next, initialized) = self.bitmap.next_initialized_tick_within_one_word(tick, self.tick_spacing, zero_for_one)?;
+            let tick_next = tick_next.clamp(MIN_TICK, MAX_TICK);
+            let sqrt_price_next_x96 = get_sqrt_ratio_at_tick(tick_next)?;
+
+            let beyond_limit = if zero_for_one { sqrt_price_next_x96 < limit } else { sqrt_price_next_x96 > limit };
+            let target = if beyond_limit { limit } else { sqrt_price_next_x96 };
+            let step = compute_swap_step(sqrt_price_x96, target, liquidity, remaining, exact_input, self.fee)?;
+            sqrt_price_x96 = step.sqrt_price_next_x96;
+            fee_amount += step.fee_amount;
+
+            if exact_input {
+                remaining -= step.amount_in + step.fee_amount;
+                calculated += step.amount_out;
+            } else {
+                remaining -= step.amount_out;
+                calculated += step.amount_in + step.fee_amount;
+            }
+
+            if sqrt_price_x96 == sqrt_price_next_x96 {
+                if initialized {
+                    let net = *self.liquidity_net.get(&tick_next)
+                        .ok_or_else(|| anyhow!("liquidityNet of tick {} was not loaded", tick_next))?;
+                    liquidity = add_delta(liquidity, if zero_for_one { -net } else { net })?;
+                    initialized_ticks_crossed += 1;
+                }
+                tick = if zero_for_one { tick_next - 1 } else { tick_next };
+            } else if sqrt_price_x96 != sqrt_price_start_x96 {
+                tick = get_tick_at_sqrt_ratio(sqrt_price_x96)?;
+            }
+        }
+
+        let (amount_in, amount_out) = if exact_input {
+            (amount - remaining, calculated)
+        } else {
+            (calculated, amount - remaining)
+        };
+        Ok(V3SwapResult {
+            amount_in,
+            amount_out,
+            fee_amount,
+            sqrt_price_x96_after: sqrt_price_x96,
+            tick_after: tick,
+            liquidity_after: liquidity,
+            initialized_ticks_crossed,
+        })
+    }
+
+    /// `quoteExactInputSingle`
+    pub fn quote_exact_input(&self, token_in: Address, amount_in: U256) -> Result<V3SwapResult> {
+        self.swap(self.zero_for_one(token_in)?, amount_in, true, None)
+    }
+
+    /// `quoteExactOutputSingle`; fails if the pool can not deliver the whole amount
+    pub fn quote_exact_output(&self, token_in: Address, amount_out: U256) -> Result<V3SwapResult> {
+        let result = self.swap(self.zero_for_one(token_in)?, amount_out, false, None)?;
+        if result.amount_out != amount_out {
+            return Err(anyhow!("Pool {:?} can only deliver {} of {}", self.address, result.amount_out, amount_out));
+        }
+        Ok(result)
+    }
+
+    /// Moves the snapshot to the state after a swap
+    pub fn apply_swap(&mut self, result: &V3SwapResult) {
+        self.sqrt_price_x96 = result.sqrt_price_x96_after;
+        self.tick = result.tick_after;
+        self.liquidity = result.liquidity_after;
+    }
+
+    fn zero_for_one(&self, token_in: Address) -> Result<bool> {
+        if token_in == self.token0 {
+            Ok(true)
+        } else if token_in == self.token1 {
+            Ok(false)
+        } else {
+            Err(anyhow!("Token {:?} is not in pool {:?}", token_in, self.address))
+        }
+    }
+}
+
+/// `LiquidityMath.addDelta`
+fn add_delta(liquidity: u128, delta: i128) -> Result<u128> {
+    if delta < 0 {
+        liquidity.checked_sub(delta.unsigned_abs()).ok_or_else(|| anyhow!("LS: liquidity underflow"))
+    } else {
+        liquidity.checked_add(delta as u128).ok_or_else(|| anyhow!("LA: liquidity overflow"))
+    }
+}
+
+fn uint(token: &Token) -> Result<U256> {
+    token.clone().into_uint().ok_or_else(|| anyhow!("Expected uint, got {:?}", token))
+}
+
+fn int(token: &Token) -> Result<I256> {
+    token.clone().into_int().map(I256::from_raw).ok_or_else(|| anyhow!("Expected int, got {:?}", token))
+}
+
+/// Default fee tiers, as the `const_and_addr` basis point constants
+pub fn default_fee_tiers() -> [u32; 3] {
+    [
+        fee_from_bps(const_and_addr::UNISWAP_V3_FEE_LOW),
+        fee_from_bps(const_and_addr::UNISWAP_V3_FEE_MEDIUM),
+        fee_from_bps(const_and_addr::UNISWAP_V3_FEE_HIGH),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pricing::v3::sqrt_price_math::{get_amount0_delta, get_amount1_delta};
+
+    /// One position over [-600, 600] with 10^18 liquidity, price at tick 0
+    fn snapshot() -> V3PoolSnapshot {
+        let pool = PoolState {
+            reserves: PoolReserves::V3 { sqrt_price_x96: U256::one() << 96, liquidity: 10u128.pow(18), tick: 0 },
+            ..PoolState::v3(Address::repeat_byte(1), Address::zero(), Address::repeat_byte(2), Address::repeat_byte(3), 3_000)
+        };
+        let mut snapshot = V3PoolSnapshot::new(&pool, 60).unwrap();
+        // Compressed ticks -10 and 10 live in words -1 (bit 246) and 0 (bit 10)
+        snapshot.insert_word(-1, U256::one() << 246);
+        snapshot.insert_word(0, U256::one() << 10);
+        snapshot.set_liquidity_net(-600, 10i128.pow(18));
+        snapshot.set_liquidity_net(600, -(10i128.pow(18)));
+        snapshot
+    }
+
+    #[test]
+    fn test_swap_within_range_and_across_the_last_tick() {
+        let pool = snapshot();
+        assert_eq!(default_fee_tiers().map(tick_spacing_for_fee), [Some(10), Some(60), Some(200)]);
+        // Searching down from tick 0 stops at its own word boundary first
+        assert_eq!(pool.bitmap.next_initialized_tick_within_one_word(0, 60, true).unwrap(), (0, false));
+        assert_eq!(pool.bitmap.next_initialized_tick_within_one_word(-1, 60, true).unwrap(), (-600, true));
+        assert_eq!(pool.bitmap.next_initialized_tick_within_one_word(0, 60, false).unwrap(), (600, true));
+
+        // Small exact input stays inside the range
+        let result = pool.quote_exact_input(pool.token0, U256::exp10(15)).unwrap();
+        assert_eq!(result.amount_in, U256::exp10(15));
+        assert_eq!(result.fee_amount, U256::from(3u64) * U256::exp10(12));
+        assert_eq!(result.amount_out, get_amount1_delta(result.sqrt_price_x96_after, pool.sqrt_price_x96, pool.liquidity, false).unwrap());
+        assert_eq!(result.initialized_ticks_crossed, 0);
+
+        // Exact output mirrors exact input: paying its quote buys at least the output
+        let exact_out = pool.quote_exact_output(pool.token1, U256::exp10(15)).unwrap();
+        let back = pool.quote_exact_input(pool.token1, exact_out.amount_in).unwrap();
+        assert!(back.amount_out >= U256::exp10(15));
+
+        // A huge input drains the range, crosses tick -600 and leaves no liquidity
+        let limit = get_sqrt_ratio_at_tick(-15_000).unwrap();
+        let drained = pool.swap(true, U256::exp10(30), true, Some(limit)).unwrap();
+        let range_amount0 = get_amount0_delta(get_sqrt_ratio_at_tick(-600).unwrap(), pool.sqrt_price_x96, pool.liquidity, true).unwrap();
+        assert_eq!(drained.initialized_ticks_crossed, 1);
+        assert_eq!((drained.liquidity_after, drained.sqrt_price_x96_after), (0, limit));
+        assert_eq!(drained.amount_in, range_amount0 + drained.fee_amount);
+        // Walking past the loaded words is an error, not an empty range
+        assert!(pool.swap(true, U256::exp10(30), true, None).unwrap_err().to_string().contains("not loaded"));
+        assert!(pool.quote_exact_input(Address::repeat_byte(9), U256::one()).is_err());
+    }
+
+    /// Differential test against QuoterV2 on a mainnet fork. Needs anvil and FORK_URL.
+    #[tokio::test]
+    #[ignore = "requires anvil and FORK_URL"]
+    async fn test_matches_quoter_v2_on_anvil_fork() -> anyhow::Result<()> {
+        use ethers::{providers::{Http, Provider}, utils::Anvil};
+        use crate::{chain::ChainProfile, pools::PoolManager};
+
+        let fork_url = std::env::var("FORK_URL").map_err(|_| anyhow::anyhow!("FORK_URL must point at a mainnet archive endpoint"))?;
+        let anvil = Anvil::new().fork(fork_url).arg("--silent").spawn();
+        let provider = Provider::<Http>::try_from(anvil.endpoint())?;
+        let quoter: Address = "0x61fFE014bA17989E743c5F6cB21bF9697530B21e".parse()?;
+
+        let profile = ChainProfile::ethereum();
+        let manager = PoolManager::for_chain(&profile);
+        let pool = manager.all().into_iter().find(|p| p.kind == DexKind::UniswapV3).expect("a V3 pool in the address book");
+        let snapshot = V3PoolSnapshot::fetch(&provider, &pool, 4).await?;
+
+        let params = |token_in, token_out, amount: U256| Token::Tuple(vec![
+            Token::Address(token_in), Token::Address(token_out), Token::Uint(amount), Token::Uint(pool.fee.into()), Token::Uint(U256::zero()),
+        ]);
+        let outputs = [ParamType::Uint(256), ParamType::Uint(160), ParamType::Uint(32), ParamType::Uint(256)];
+        for (token_in, token_out) in [(pool.token0, pool.token1), (pool.token1, pool.token0)] {
+            let decimals = profile.addresses.token_by_address(token_in).map_or(18, |t| t.decimals);
+            for units in [1u64, 1_000, 250_000] {
+                let amount = U256::from(units) * U256::exp10(decimals.into());
+
+                let quoted = call(&provider, quoter, "quoteExactInputSingle((address,address,uint256,uint24,uint160))",
+                    &[params(token_in, token_out, amount)], &outputs).await?;
+                let simulated = snapshot.quote_exact_input(token_in, amount)?;
+                assert_eq!(simulated.amount_out, uint(&quoted[0])?, "exact input {} of {:?}", amount, token_in);
+                assert_eq!(simulated.sqrt_price_x96_after, uint(&quoted[1])?);
+
+                let quoted = call(&provider, quoter, "quoteExactOutputSingle((address,address,uint256,uint24,uint160))",
+                    &[params(token_in, token_out, simulated.amount_out)], &outputs).await?;
+                let simulated = snapshot.quote_exact_output(token_in, simulated.amount_out)?;
+                assert_eq!(simulated.amount_in, uint(&quoted[0])?, "exact output of {:?}", token_out);
+            }
+        }
+        Ok(())
+    }
+}
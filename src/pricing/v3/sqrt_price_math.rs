@@ -0,0 +1,122 @@
+//! Price movement for a given liquidity and token amount, `SqrtPriceMath`
+use anyhow::{Result, anyhow};
+use ethers::types::U256;
+use once_cell::sync::Lazy;
+
+use super::full_math::{div_rounding_up, mul_div, mul_div_rounding_up};
+
+const RESOLUTION: usize = 96;
+static Q96: Lazy<U256> = Lazy::new(|| U256::one() << RESOLUTION);
+static MAX_U160: Lazy<U256> = Lazy::new(|| (U256::one() << 160) - 1);
+
+fn to_u160(value: U256) -> Result<U256> {
+    if value > *MAX_U160 {
+        return Err(anyhow!("SqrtPriceMath: sqrt price overflows uint160"));
+    }
+    Ok(value)
+}
+
+/// Next price after adding or removing `amount` of token0, rounded up
+pub fn get_next_sqrt_price_from_amount0_rounding_up(sqrt_price_x96: U256, liquidity: u128, amount: U256, add: bool) -> Result<U256> {
+    if amount.is_zero() {
+        return Ok(sqrt_price_x96);
+    }
+    let numerator1 = U256::from(liquidity) << RESOLUTION;
+    let (product, overflowed) = amount.overflowing_mul(sqrt_price_x96);
+
+    if add {
+        if !overflowed {
+            let (denominator, overflowed) = numerator1.overflowing_add(product);
+            if !overflowed {
+                return mul_div_rounding_up(numerator1, sqrt_price_x96, denominator);
+            }
+        }
+        let denominator = (numerator1 / sqrt_price_x96).overflowing_add(amount).0;
+        Ok(div_rounding_up(numerator1, denominator))
+    } else {
+        if overflowed || numerator1 <= product {
+            return Err(anyhow!("SqrtPriceMath: not enough liquidity for token0 output"));
+        }
+        to_u160(mul_div_rounding_up(numerator1, sqrt_price_x96, numerator1 - product)?)
+    }
+}
+
+/// Next price after adding or removing `amount` of token1, rounded down
+pub fn get_next_sqrt_price_from_amount1_rounding_down(sqrt_price_x96: U256, liquidity: u128, amount: U256, add: bool) -> Result<U256> {
+    let liquidity = U256::from(liquidity);
+    if add {
+        let quotient = if amount <= *MAX_U160 {
+            (amount << RESOLUTION) / liquidity
+        } else {
+            mul_div(amount, *Q96, liquidity)?
+        };
+        let next = sqrt_price_x96.checked_add(quotient).ok_or_else(|| anyhow!("SqrtPriceMath: price overflow"))?;
+        to_u160(next)
+    } else {
+        let quotient = if amount <= *MAX_U160 {
+            div_rounding_up(amount << RESOLUTION, liquidity)
+        } else {
+            mul_div_rounding_up(amount, *Q96, liquidity)?
+        };
+        if sqrt_price_x96 <= quotient {
+            return Err(anyhow!("SqrtPriceMath: not enough liquidity for token1 output"));
+        }
+        Ok(sqrt_price_x96 - quotient)
+    }
+}
+
+pub fn get_next_sqrt_price_from_input(sqrt_price_x96: U256, liquidity: u128, amount_in: U256, zero_for_one: bool) -> Result<U256> {
+    if sqrt_price_x96.is_zero() || liquidity == 0 {
+        return Err(anyhow!("SqrtPriceMath: zero price or liquidity"));
+    }
+    if zero_for_one {
+        get_next_sqrt_price_from_amount0_rounding_up(sqrt_price_x96, liquidity, amount_in, true)
+    } else {
+        get_next_sqrt_price_from_amount1_rounding_down(sqrt_price_x96, liquidity, amount_in, true)
+    }
+}
+
+pub fn get_next_sqrt_price_from_output(sqrt_price_x96: U256, liquidity: u128, amount_out: U256, zero_for_one: bool) -> Result<U256> {
+    if sqrt_price_x96.is_zero() || liquidity == 0 {
+        return Err(anyhow!("SqrtPriceMath: zero price or liquidity"));
+    }
+    if zero_for_one {
+        get_next_sqrt_price_from_amount1_rounding_down(sqrt_price_x96, liquidity, amount_out, false)
+    } else {
+        get_next_sqrt_price_from_amount0_rounding_up(sqrt_price_x96, liquidity, amount_out, false)
+    }
+}
+
+/// Token0 between two prices for `liquidity`
+pub fn get_amount0_delta(sqrt_ratio_a_x96: U256, sqrt_ratio_b_x96: U256, liquidity: u128, round_up: bool) -> Result<U256> {
+    let (lower, upper) = if sqrt_ratio_a_x96 > sqrt_ratio_b_x96 {
+        (sqrt_ratio_b_x96, sqrt_ratio_a_x96)
+    } else {
+        (sqrt_ratio_a_x96, sqrt_ratio_b_x96)
+    };
+    if lower.is_zero() {
+        return Err(anyhow!("SqrtPriceMath: zero sqrt price"));
+    }
+    let numerator1 = U256::from(liquidity) << RESOLUTION;
+    let numerator2 = upper - lower;
+
+    if round_up {
+        Ok(div_rounding_up(mul_div_rounding_up(numerator1, numerator2, upper)?, lower))
+    } else {
+        Ok(mul_div(numerator1, numerator2, upper)? / lower)
+    }
+}
+
+/// Token1 between two prices for `liquidity`
+pub fn get_amount1_delta(sqrt_ratio_a_x96: U256, sqrt_ratio_b_x96: U256, liquidity: u128, round_up: bool) -> Result<U256> {
+    let (lower, upper) = if sqrt_ratio_a_x96 > sqrt_ratio_b_x96 {
+        (sqrt_ratio_b_x96, sqrt_ratio_a_x96)
+    } else {
+        (sqrt_ratio_a_x96, sqrt_ratio_b_x96)
+    };
+    if round_up {
+        mul_div_rounding_up(U256::from(liquidity), upper - lower, *Q96)
+    } else {
+        mul_div(U256::from(liquidity), upper - lower, *Q96)
+    }
+}
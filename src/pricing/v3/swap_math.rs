@@ -0,0 +1,95 @@
+//! A single swap step within one liquidity range, `SwapMath`
+use anyhow::Result;
+use ethers::types::U256;
+
+use super::full_math::{mul_div, mul_div_rounding_up};
+use super::sqrt_price_math::{
+    get_amount0_delta, get_amount1_delta, get_next_sqrt_price_from_input, get_next_sqrt_price_from_output,
+};
+
+/// Fee denominator, fees are in hundredths of a basis point
+pub const FEE_PIPS_DENOMINATOR: u32 = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapStep {
+    pub sqrt_price_next_x96: U256,
+    pub amount_in: U256,
+    pub amount_out: U256,
+    pub fee_amount: U256,
+}
+
+/// Swaps towards `sqrt_price_target_x96` until it is reached or `amount_remaining` is
+/// used up. `amount_remaining` is the input left for exact-input swaps and the output
+/// still owed for exact-output swaps, `computeSwapStep` takes it as a signed value.
+pub fn compute_swap_step(
+    sqrt_price_current_x96: U256,
+    sqrt_price_target_x96: U256,
+    liquidity: u128,
+    amount_remaining: U256,
+    exact_input: bool,
+    fee_pips: u32,
+) -> Result<SwapStep> {
+    let zero_for_one = sqrt_price_current_x96 >= sqrt_price_target_x96;
+    let fee_complement = U256::from(FEE_PIPS_DENOMINATOR - fee_pips);
+    let mut amount_in = U256::zero();
+    let mut amount_out = U256::zero();
+
+    let sqrt_price_next_x96 = if exact_input {
+        let amount_remaining_less_fee = mul_div(amount_remaining, fee_complement, FEE_PIPS_DENOMINATOR.into())?;
+        amount_in = if zero_for_one {
+            get_amount0_delta(sqrt_price_target_x96, sqrt_price_current_x96, liquidity, true)?
+        } else {
+            get_amount1_delta(sqrt_price_current_x96, sqrt_price_target_x96, liquidity, true)?
+        };
+        if amount_remaining_less_fee >= amount_in {
+            sqrt_price_target_x96
+        } else {
+            get_next_sqrt_price_from_input(sqrt_price_current_x96, liquidity, amount_remaining_less_fee, zero_for_one)?
+        }
+    } else {
+        amount_out = if zero_for_one {
+            get_amount1_delta(sqrt_price_target_x96, sqrt_price_current_x96, liquidity, false)?
+        } else {
+            get_amount0_delta(sqrt_price_current_x96, sqrt_price_target_x96, liquidity, false)?
+        };
+        if amount_remaining >= amount_out {
+            sqrt_price_target_x96
+        } else {
+            get_next_sqrt_price_from_output(sqrt_price_current_x96, liquidity, amount_remaining, zero_for_one)?
+        }
+    };
+
+    // Amounts computed against the target stay valid when the target was reached
+    let max = sqrt_price_target_x96 == sqrt_price_next_x96;
+    let keep_in = max && exact_input;
+    let keep_out = max && !exact_input;
+    if zero_for_one {
+        if !keep_in {
+            amount_in = get_amount0_delta(sqrt_price_next_x96, sqrt_price_current_x96, liquidity, true)?;
+        }
+        if !keep_out {
+            amount_out = get_amount1_delta(sqrt_price_next_x96, sqrt_price_current_x96, liquidity, false)?;
+        }
+    } else {
+        if !keep_in {
+            amount_in = get_amount1_delta(sqrt_price_current_x96, sqrt_price_next_x96, liquidity, true)?;
+        }
+        if !keep_out {
+            amount_out = get_amount0_delta(sqrt_price_current_x96, sqrt_price_next_x96, liquidity, false)?;
+        }
+    }
+
+    // The output can not exceed what was asked for
+    if !exact_input && amount_out > amount_remaining {
+        amount_out = amount_remaining;
+    }
+
+    let fee_amount = if exact_input && sqrt_price_next_x96 != sqrt_price_target_x96 {
+        // The target was not reached, so the rest of the input is the fee
+        amount_remaining - amount_in
+    } else {
+        mul_div_rounding_up(amount_in, fee_pips.into(), fee_complement)?
+    };
+
+    Ok(SwapStep { sqrt_price_next_x96, amount_in, amount_out, fee_amount })
+}
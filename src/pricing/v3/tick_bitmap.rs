@@ -0,0 +1,89 @@
+//! Initialized-tick lookup, `TickBitmap`
+//!
+//! Only words fetched from the pool are known. Walking into a word that was not loaded
+//! is an error rather than an empty word, so a quote never silently skips liquidity.
+use std::collections::BTreeMap;
+use anyhow::{Result, anyhow};
+use ethers::types::U256;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TickBitmap {
+    words: BTreeMap<i16, U256>,
+}
+
+impl TickBitmap {
+    pub fn insert_word(&mut self, word_position: i16, word: U256) {
+        self.words.insert(word_position, word);
+    }
+
+    pub fn is_loaded(&self, word_position: i16) -> bool {
+        self.words.contains_key(&word_position)
+    }
+
+    /// Every initialized tick in the loaded words
+    pub fn initialized_ticks(&self, tick_spacing: i32) -> Vec<i32> {
+        self.words
+            .iter()
+            .flat_map(|(&word_position, word)| {
+                (0..256).filter(move |bit| word.bit(*bit)).map(move |bit| (i32::from(word_position) * 256 + bit as i32) * tick_spacing)
+            })
+            .collect()
+    }
+
+    /// `nextInitializedTickWithinOneWord`: the next initialized tick at or below
+    /// (`lte`) or above `tick`, or the word boundary if there is none in the word
+    pub fn next_initialized_tick_within_one_word(&self, tick: i32, tick_spacing: i32, lte: bool) -> Result<(i32, bool)> {
+        let compressed = compress(tick, tick_spacing);
+
+        if lte {
+            let (word_position, bit_position) = position(compressed);
+            let word = self.word(word_position)?;
+            let mask = (U256::one() << bit_position) - 1 + (U256::one() << bit_position);
+            let masked = word & mask;
+            let initialized = !masked.is_zero();
+            let next = if initialized {
+                (compressed - (i32::from(bit_position) - most_significant_bit(masked))) * tick_spacing
+            } else {
+                (compressed - i32::from(bit_position)) * tick_spacing
+            };
+            Ok((next, initialized))
+        } else {
+            let (word_position, bit_position) = position(compressed + 1);
+            let word = self.word(word_position)?;
+            let mask = !((U256::one() << bit_position) - 1);
+            let masked = word & mask;
+            let initialized = !masked.is_zero();
+            let next = if initialized {
+                (compressed + 1 + (least_significant_bit(masked) - i32::from(bit_position))) * tick_spacing
+            } else {
+                (compressed + 1 + (255 - i32::from(bit_position))) * tick_spacing
+            };
+            Ok((next, initialized))
+        }
+    }
+
+    fn word(&self, word_position: i16) -> Result<U256> {
+        self.words
+            .get(&word_position)
+            .copied()
+            .ok_or_else(|| anyhow!("Tick bitmap word {} was not loaded", word_position))
+    }
+}
+
+/// Tick divided by the spacing, rounded towards negative infinity
+pub fn compress(tick: i32, tick_spacing: i32) -> i32 {
+    tick.div_euclid(tick_spacing)
+}
+
+/// Word and bit of a compressed tick, `TickBitmap.position`
+pub fn position(compressed: i32) -> (i16, u8) {
+    ((compressed >> 8) as i16, (compressed & 0xff) as u8)
+}
+
+fn most_significant_bit(x: U256) -> i32 {
+    x.bits() as i32 - 1
+}
+
+fn least_significant_bit(x: U256) -> i32 {
+    x.trailing_zeros() as i32
+}
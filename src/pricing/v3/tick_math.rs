@@ -0,0 +1,129 @@
+//! Conversions between ticks and sqrt prices, `TickMath`
+use anyhow::{Result, anyhow};
+use ethers::types::{I256, U256};
+use once_cell::sync::Lazy;
+
+pub const MIN_TICK: i32 = -887_272;
+pub const MAX_TICK: i32 = 887_272;
+
+/// `getSqrtRatioAtTick(MIN_TICK)`
+pub static MIN_SQRT_RATIO: Lazy<U256> = Lazy::new(|| U256::from(4_295_128_739u64));
+/// `getSqrtRatioAtTick(MAX_TICK)`
+pub static MAX_SQRT_RATIO: Lazy<U256> = Lazy::new(|| {
+    U256::from_dec_str("1461446703485210103287273052203988822378723970342").expect("valid constant")
+});
+
+/// `sqrt(1.0001^-2^i) * 2^128` for bit `i` of the absolute tick, bits 1 and up
+const TICK_FACTORS: [(u32, &str); 19] = [
+    (0x2, "fff97272373d413259a46990580e213a"),
+    (0x4, "fff2e50f5f656932ef12357cf3c7fdcc"),
+    (0x8, "ffe5caca7e10e4e61c3624eaa0941cd0"),
+    (0x10, "ffcb9843d60f6159c9db58835c926644"),
+    (0x20, "ff973b41fa98c081472e6896dfb254c0"),
+    (0x40, "ff2ea16466c96a3843ec78b326b52861"),
+    (0x80, "fe5dee046a99a2a811c461f1969c3053"),
+    (0x100, "fcbe86c7900a88aedcffc83b479aa3a4"),
+    (0x200, "f987a7253ac413176f2b074cf7815e54"),
+    (0x400, "f3392b0822b70005940c7a398e4b70f3"),
+    (0x800, "e7159475a2c29b7443b29c7fa6e889d9"),
+    (0x1000, "d097f3bdfd2022b8845ad8f792aa5825"),
+    (0x2000, "a9f746462d870fdf8a65dc1f90e061e5"),
+    (0x4000, "70d869a156d2a1b890bb3df62baf32f7"),
+    (0x8000, "31be135f97d08fd981231505542fcfa6"),
+    (0x10000, "9aa508b5b7a84e1c677de54f3e99bc9"),
+    (0x20000, "5d6af8dedb81196699c329225ee604"),
+    (0x40000, "2216e584f5fa1ea926041bedfe98"),
+    (0x80000, "48a170391f7dc42444e8fa2"),
+];
+
+static FACTORS: Lazy<Vec<(u32, U256)>> = Lazy::new(|| {
+    TICK_FACTORS
+        .iter()
+        .map(|(bit, hex)| (*bit, U256::from_str_radix(hex, 16).expect("valid constant")))
+        .collect()
+});
+
+static LOG_SQRT10001_FACTOR: Lazy<I256> = Lazy::new(|| I256::from_dec_str("255738958999603826347141").expect("valid constant"));
+static TICK_LOW_ERROR: Lazy<I256> = Lazy::new(|| I256::from_dec_str("3402992956809132418596140100660247210").expect("valid constant"));
+static TICK_HIGH_ERROR: Lazy<I256> = Lazy::new(|| I256::from_dec_str("291339464771989622907027621153398088495").expect("valid constant"));
+
+/// `sqrt(1.0001^tick) * 2^96`, `getSqrtRatioAtTick`
+pub fn get_sqrt_ratio_at_tick(tick: i32) -> Result<U256> {
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        return Err(anyhow!("TickMath: tick {} out of range", tick));
+    }
+    let abs_tick = tick.unsigned_abs();
+
+    let mut ratio = if abs_tick & 0x1 != 0 {
+        U256::from_str_radix("fffcb933bd6fad37aa2d162d1a594001", 16).expect("valid constant")
+    } else {
+        U256::one() << 128
+    };
+    for (bit, factor) in FACTORS.iter() {
+        if abs_tick & bit != 0 {
+            ratio = (ratio * factor) >> 128;
+        }
+    }
+    if tick > 0 {
+        ratio = U256::MAX / ratio;
+    }
+
+    // Q128.128 to Q64.96, rounding up so the result is never below the tick's price
+    let rounding = if (ratio & U256::from(u32::MAX)).is_zero() { 0 } else { 1 };
+    Ok((ratio >> 32) + rounding)
+}
+
+/// Greatest tick whose sqrt ratio is at most `sqrt_price_x96`, `getTickAtSqrtRatio`
+pub fn get_tick_at_sqrt_ratio(sqrt_price_x96: U256) -> Result<i32> {
+    if sqrt_price_x96 < *MIN_SQRT_RATIO || sqrt_price_x96 >= *MAX_SQRT_RATIO {
+        return Err(anyhow!("TickMath: sqrt price {} out of range", sqrt_price_x96));
+    }
+    let ratio = sqrt_price_x96 << 32;
+    let msb = ratio.bits() - 1;
+    let mut r = if msb >= 128 { ratio >> (msb - 127) } else { ratio << (127 - msb) };
+
+    // Integer part of log2 in the top bits, then 14 fractional bits by repeated squaring
+    let mut log_2 = (I256::from(msb as i64) - I256::from(128)) << 64;
+    for shift in (50..=63).rev() {
+        r = (r * r) >> 127;
+        let f = r >> 128;
+        log_2 |= I256::from_raw(f << shift);
+        r >>= f.as_usize();
+    }
+
+    // log_sqrt(1.0001) as Q128.128, with the error bounds of the approximation
+    let log_sqrt10001: I256 = log_2 * *LOG_SQRT10001_FACTOR;
+    let tick_low = (log_sqrt10001 - *TICK_LOW_ERROR).asr(128).low_i32();
+    let tick_high = (log_sqrt10001 + *TICK_HIGH_ERROR).asr(128).low_i32();
+
+    if tick_low == tick_high {
+        Ok(tick_low)
+    } else if get_sqrt_ratio_at_tick(tick_high)? <= sqrt_price_x96 {
+        Ok(tick_high)
+    } else {
+        Ok(tick_low)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tick_math_bounds_and_round_trip() {
+        assert_eq!(get_sqrt_ratio_at_tick(0).unwrap(), U256::one() << 96);
+        assert_eq!(get_sqrt_ratio_at_tick(MIN_TICK).unwrap(), *MIN_SQRT_RATIO);
+        assert_eq!(get_sqrt_ratio_at_tick(MAX_TICK).unwrap(), *MAX_SQRT_RATIO);
+        assert!(get_sqrt_ratio_at_tick(MAX_TICK + 1).is_err());
+        assert!(get_tick_at_sqrt_ratio(*MAX_SQRT_RATIO).is_err());
+
+        assert_eq!(get_tick_at_sqrt_ratio(*MIN_SQRT_RATIO).unwrap(), MIN_TICK);
+        assert_eq!(get_tick_at_sqrt_ratio(*MAX_SQRT_RATIO - 1).unwrap(), MAX_TICK - 1);
+        for tick in [-887_000, -200_000, -60, -1, 0, 1, 59, 60, 201_234, 887_271] {
+            let ratio = get_sqrt_ratio_at_tick(tick).unwrap();
+            assert_eq!(get_tick_at_sqrt_ratio(ratio).unwrap(), tick);
+            assert_eq!(get_tick_at_sqrt_ratio(ratio - 1).unwrap(), tick - 1);
+            assert_eq!(get_tick_at_sqrt_ratio(ratio + 1).unwrap(), tick);
+        }
+    }
+}
@@ -1,3 +1,755 @@
-pub struct BloomFilter{
+//! Bloom filters for deduplicating transactions and logs
+//!
+//! The same block can reach the scanner more than once: a WS/HTTP fallback replays the
+//! last few heads, and reorg handling re-processes blocks that were already analyzed.
+//! Feeding those receipts to the drift detector twice would double-count every delta,
+//! so the scanner remembers what it has seen in a bloom filter. A false positive skips
+//! an item that was never analyzed, which is why the filter is sized for a very low rate.
+//!
+//! `BloomFilter` is a fixed-size, lock-free filter. `RotatingBloomFilter` chains a few
+//! generations of them so memory stays bounded on a scanner that runs for weeks: once
+//! the newest generation reaches its capacity a fresh one is started and the oldest
+//! one is dropped. `ScalableBloomFilter` instead keeps every generation and makes each
+//! new one larger and stricter, for sets that must never forget.
+//!
+//! All three can be written to a compact binary snapshot (magic, format version, body,
+//! CRC32 trailer) so the seen-set survives a restart.
+//!
+//! The same type also reads the 2048-bit `logsBloom` of block headers and receipts,
+//! which uses Ethereum's fixed keccak-based scheme instead of the tunable one.
+use std::{
+    collections::VecDeque,
+    sync::{
+        Arc, RwLock,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+};
+use anyhow::{anyhow, Result};
+use ethers::{types::{Bloom, H256}, utils::keccak256};
 
-}
\ No newline at end of file
+const SEED_PRIMARY: u64 = 0x9E37_79B9_7F4A_7C15;
+const SEED_SECONDARY: u64 = 0xC2B2_AE3D_27D4_EB4F;
+const MAX_HASHES: u32 = 32;
+
+/// Ethereum logs bloom: 2048 bits, 3 bits per item taken from its keccak hash
+const ETH_BLOOM_BITS: u64 = 2048;
+const ETH_BLOOM_HASHES: usize = 3;
+
+/// Each scalable layer holds twice the items of the previous one at half its
+/// false-positive rate, which keeps the compound rate below the target
+const SCALABLE_GROWTH: usize = 2;
+const SCALABLE_TIGHTENING: f64 = 0.5;
+
+const SNAPSHOT_MAGIC: &[u8; 4] = b"MVBF";
+const SNAPSHOT_VERSION: u16 = 1;
+/// Refuses snapshots claiming absurd sizes before allocating for them
+const MAX_SNAPSHOT_BITS: u64 = 1 << 36;
+
+/// Key for a transaction hash
+pub fn tx_key(hash: H256) -> [u8; 32] {
+    hash.0
+}
+
+/// Key for a log, identified by the block it was emitted in and its index in that block.
+/// Using the block hash rather than the number keeps logs of a reorged block distinct.
+pub fn log_key(block_hash: H256, log_index: u64) -> [u8; 40] {
+    let mut key = [0u8; 40];
+    key[..32].copy_from_slice(block_hash.as_bytes());
+    key[32..].copy_from_slice(&log_index.to_be_bytes());
+    key
+}
+
+/// Bit positions of an item in an Ethereum logs bloom. Hashing is the expensive part,
+/// so callers checking many blooms against the same addresses compute these once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EthBloomBits([u64; ETH_BLOOM_HASHES]);
+
+impl EthBloomBits {
+    pub fn new(item: &[u8]) -> Self {
+        let hash = keccak256(item);
+        let mut bits = [0u64; ETH_BLOOM_HASHES];
+        for (i, bit) in bits.iter_mut().enumerate() {
+            *bit = u64::from(u16::from_be_bytes([hash[2 * i], hash[2 * i + 1]])) % ETH_BLOOM_BITS;
+        }
+        Self(bits)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HashScheme {
+    /// Double hashing with `num_hashes` probes, for filters sized by the scanner
+    DoubleHash,
+    /// keccak-based 3-probe scheme of `logsBloom`
+    Ethereum,
+}
+
+/// What a snapshot holds, checked on load so a file cannot be read as the wrong type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum SnapshotKind {
+    Single = 1,
+    Rotating = 2,
+    Scalable = 3,
+}
+
+/// Fixed-size, thread-safe bloom filter
+pub struct BloomFilter {
+    bits: Box<[AtomicU64]>,
+    num_bits: u64,
+    num_hashes: u32,
+    scheme: HashScheme,
+    capacity: usize,
+    items: AtomicUsize,
+}
+
+impl BloomFilter {
+    /// Sizes the filter so that `expected_items` insertions keep the false-positive
+    /// rate at or below `false_positive_rate`
+    pub fn new(expected_items: usize, false_positive_rate: f64) -> Self {
+        let expected_items = expected_items.max(1);
+        let rate = false_positive_rate.clamp(f64::MIN_POSITIVE, 0.5);
+        let ln2 = std::f64::consts::LN_2;
+
+        let num_bits = (-(expected_items as f64) * rate.ln() / (ln2 * ln2)).ceil() as u64;
+        let num_hashes = ((num_bits as f64 / expected_items as f64) * ln2).round() as u32;
+        Self::with_params(num_bits, num_hashes.clamp(1, MAX_HASHES), HashScheme::DoubleHash, expected_items)
+    }
+
+    /// Empty filter using the Ethereum logs bloom layout
+    pub fn ethereum() -> Self {
+        Self::with_params(ETH_BLOOM_BITS, ETH_BLOOM_HASHES as u32, HashScheme::Ethereum, usize::MAX)
+    }
+
+    /// Reads a header or receipt `logsBloom`. Bit `i` of the bloom lives in byte
+    /// `255 - i / 8`, so the big-endian bytes are read back to front.
+    pub fn from_ethereum_bloom(bloom: &Bloom) -> Self {
+        let filter = Self::ethereum();
+        for (word, chunk) in filter.bits.iter().zip(bloom.as_bytes().rchunks_exact(8)) {
+            word.store(u64::from_be_bytes(chunk.try_into().unwrap()), Ordering::Relaxed);
+        }
+        filter
+    }
+
+    /// Checks precomputed logs bloom positions, only meaningful for Ethereum filters
+    pub fn contains_bits(&self, bits: &EthBloomBits) -> bool {
+        debug_assert_eq!(self.scheme, HashScheme::Ethereum);
+        bits.0.iter().all(|index| self.bit_is_set(*index))
+    }
+
+    fn with_params(num_bits: u64, num_hashes: u32, scheme: HashScheme, capacity: usize) -> Self {
+        let words = num_bits.div_ceil(64).max(1);
+        Self {
+            bits: (0..words).map(|_| AtomicU64::new(0)).collect(),
+            num_bits: words * 64,
+            num_hashes,
+            scheme,
+            capacity,
+            items: AtomicUsize::new(0),
+        }
+    }
+
+    /// Adds an item. Returns `true` if it was not in the filter before.
+    pub fn insert<T: AsRef<[u8]> + ?Sized>(&self, item: &T) -> bool {
+        let mut newly_set = false;
+        for index in self.bit_indexes(item.as_ref()) {
+            let mask = 1u64 << (index % 64);
+            let previous = self.bits[(index / 64) as usize].fetch_or(mask, Ordering::Relaxed);
+            newly_set |= previous & mask == 0;
+        }
+        if newly_set {
+            self.items.fetch_add(1, Ordering::Relaxed);
+        }
+        newly_set
+    }
+
+    /// `false` means definitely not inserted, `true` means probably inserted
+    pub fn contains<T: AsRef<[u8]> + ?Sized>(&self, item: &T) -> bool {
+        self.bit_indexes(item.as_ref()).all(|index| self.bit_is_set(index))
+    }
+
+    /// Number of distinct items inserted (approximate, false positives are not counted)
+    pub fn len(&self) -> usize {
+        self.items.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Items the filter was sized for
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Past capacity the false-positive rate climbs above the target
+    pub fn is_full(&self) -> bool {
+        self.len() >= self.capacity
+    }
+
+    pub fn num_bits(&self) -> u64 {
+        self.num_bits
+    }
+
+    pub fn num_hashes(&self) -> u32 {
+        self.num_hashes
+    }
+
+    /// False-positive rate implied by the current fill ratio
+    pub fn estimated_false_positive_rate(&self) -> f64 {
+        let set_bits: u64 = self.bits.iter().map(|word| word.load(Ordering::Relaxed).count_ones() as u64).sum();
+        (set_bits as f64 / self.num_bits as f64).powi(self.num_hashes as i32)
+    }
+
+    pub fn clear(&self) {
+        for word in self.bits.iter() {
+            word.store(0, Ordering::Relaxed);
+        }
+        self.items.store(0, Ordering::Relaxed);
+    }
+
+    /// Binary snapshot of the filter
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = snapshot_header(SnapshotKind::Single);
+        self.write_body(&mut out);
+        seal_snapshot(out)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = open_snapshot(bytes, SnapshotKind::Single)?;
+        let filter = Self::read_body(&mut reader)?;
+        reader.finish()?;
+        Ok(filter)
+    }
+
+    fn write_body(&self, out: &mut Vec<u8>) {
+        out.push(match self.scheme {
+            HashScheme::DoubleHash => 0,
+            HashScheme::Ethereum => 1,
+        });
+        out.extend_from_slice(&self.num_hashes.to_le_bytes());
+        out.extend_from_slice(&self.num_bits.to_le_bytes());
+        out.extend_from_slice(&(self.capacity as u64).to_le_bytes());
+        out.extend_from_slice(&(self.len() as u64).to_le_bytes());
+        for word in self.bits.iter() {
+            out.extend_from_slice(&word.load(Ordering::Relaxed).to_le_bytes());
+        }
+    }
+
+    fn read_body(reader: &mut SnapshotReader<'_>) -> Result<Self> {
+        let scheme = match reader.u8()? {
+            0 => HashScheme::DoubleHash,
+            1 => HashScheme::Ethereum,
+            other => return Err(anyhow!("Unknown bloom hash scheme {}", other)),
+        };
+        let num_hashes = reader.u32()?;
+        let num_bits = reader.u64()?;
+        if num_bits == 0 || num_bits % 64 != 0 || num_bits > MAX_SNAPSHOT_BITS {
+            return Err(anyhow!("Invalid bloom size of {} bits", num_bits));
+        }
+        if !(1..=MAX_HASHES).contains(&num_hashes) {
+            return Err(anyhow!("Invalid bloom hash count {}", num_hashes));
+        }
+        let capacity = usize::try_from(reader.u64()?).unwrap_or(usize::MAX);
+        let items = reader.u64()? as usize;
+
+        let filter = Self::with_params(num_bits, num_hashes, scheme, capacity);
+        for word in filter.bits.iter() {
+            word.store(reader.u64()?, Ordering::Relaxed);
+        }
+        filter.items.store(items, Ordering::Relaxed);
+        Ok(filter)
+    }
+
+    fn bit_is_set(&self, index: u64) -> bool {
+        self.bits[(index / 64) as usize].load(Ordering::Relaxed) & (1u64 << (index % 64)) != 0
+    }
+
+    fn bit_indexes(&self, item: &[u8]) -> BitIndexes {
+        match self.scheme {
+            HashScheme::DoubleHash => BitIndexes::Double {
+                h1: hash64(item, SEED_PRIMARY),
+                h2: hash64(item, SEED_SECONDARY) | 1,
+                probe: 0,
+                num_hashes: self.num_hashes as u64,
+                num_bits: self.num_bits,
+            },
+            HashScheme::Ethereum => BitIndexes::Ethereum(EthBloomBits::new(item).0.into_iter()),
+        }
+    }
+}
+
+enum BitIndexes {
+    /// Kirsch-Mitzenmacher double hashing: `h1 + i * h2` for each of the k probes
+    Double { h1: u64, h2: u64, probe: u64, num_hashes: u64, num_bits: u64 },
+    Ethereum(std::array::IntoIter<u64, ETH_BLOOM_HASHES>),
+}
+
+impl Iterator for BitIndexes {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        match self {
+            Self::Double { h1, h2, probe, num_hashes, num_bits } => {
+                if *probe == *num_hashes {
+                    return None;
+                }
+                let index = h1.wrapping_add(probe.wrapping_mul(*h2)) % *num_bits;
+                *probe += 1;
+                Some(index)
+            }
+            Self::Ethereum(indexes) => indexes.next(),
+        }
+    }
+}
+
+/// Bloom filter that forgets old entries by rotating generations
+pub struct RotatingBloomFilter {
+    generations: RwLock<VecDeque<Arc<BloomFilter>>>,
+    max_generations: usize,
+    items_per_generation: usize,
+    false_positive_rate: f64,
+}
+
+impl RotatingBloomFilter {
+    /// Remembers roughly the last `items_per_generation * (max_generations - 1)` items
+    /// and at most `items_per_generation * max_generations`
+    pub fn new(items_per_generation: usize, false_positive_rate: f64, max_generations: usize) -> Self {
+        let mut generations = VecDeque::new();
+        generations.push_front(Arc::new(BloomFilter::new(items_per_generation, false_positive_rate)));
+        Self {
+            generations: RwLock::new(generations),
+            max_generations: max_generations.max(1),
+            items_per_generation,
+            false_positive_rate,
+        }
+    }
+
+    pub fn contains<T: AsRef<[u8]> + ?Sized>(&self, item: &T) -> bool {
+        self.generations.read().unwrap().iter().any(|generation| generation.contains(item))
+    }
+
+    /// Adds an item to the newest generation. Returns `true` if it was not seen before.
+    pub fn insert<T: AsRef<[u8]> + ?Sized>(&self, item: &T) -> bool {
+        let current = {
+            let generations = self.generations.read().unwrap();
+            if generations.iter().skip(1).any(|generation| generation.contains(item)) {
+                return false;
+            }
+            generations[0].clone()
+        };
+
+        let inserted = current.insert(item);
+        if inserted && current.is_full() {
+            self.rotate(&current);
+        }
+        inserted
+    }
+
+    pub fn seen_tx(&self, hash: H256) -> bool {
+        self.contains(&tx_key(hash))
+    }
+
+    pub fn mark_tx(&self, hash: H256) -> bool {
+        self.insert(&tx_key(hash))
+    }
+
+    pub fn seen_log(&self, block_hash: H256, log_index: u64) -> bool {
+        self.contains(&log_key(block_hash, log_index))
+    }
+
+    pub fn mark_log(&self, block_hash: H256, log_index: u64) -> bool {
+        self.insert(&log_key(block_hash, log_index))
+    }
+
+    pub fn generation_count(&self) -> usize {
+        self.generations.read().unwrap().len()
+    }
+
+    /// Items held across all generations
+    pub fn len(&self) -> usize {
+        self.generations.read().unwrap().iter().map(|generation| generation.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Starts a new generation, unless another thread already did since `full` filled up
+    fn rotate(&self, full: &Arc<BloomFilter>) {
+        let mut generations = self.generations.write().unwrap();
+        if !Arc::ptr_eq(&generations[0], full) {
+            return;
+        }
+        generations.push_front(Arc::new(BloomFilter::new(self.items_per_generation, self.false_positive_rate)));
+        generations.truncate(self.max_generations);
+    }
+
+    /// Binary snapshot of all generations, newest first
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = snapshot_header(SnapshotKind::Rotating);
+        out.extend_from_slice(&(self.max_generations as u32).to_le_bytes());
+        out.extend_from_slice(&(self.items_per_generation as u64).to_le_bytes());
+        out.extend_from_slice(&self.false_positive_rate.to_le_bytes());
+        write_layers(&mut out, self.generations.read().unwrap().iter());
+        seal_snapshot(out)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = open_snapshot(bytes, SnapshotKind::Rotating)?;
+        let max_generations = reader.u32()? as usize;
+        let items_per_generation = reader.u64()? as usize;
+        let false_positive_rate = reader.f64()?;
+        let generations = read_layers(&mut reader)?;
+        reader.finish()?;
+
+        if generations.is_empty() || max_generations == 0 || generations.len() > max_generations {
+            return Err(anyhow!("Invalid rotating bloom snapshot with {} of {} generations",
+                generations.len(), max_generations));
+        }
+        Ok(Self {
+            generations: RwLock::new(generations.into_iter().collect()),
+            max_generations,
+            items_per_generation,
+            false_positive_rate,
+        })
+    }
+}
+
+/// Bloom filter that grows instead of forgetting: when the newest layer is full, a
+/// larger and stricter one is added (Almeida et al., "Scalable Bloom Filters")
+pub struct ScalableBloomFilter {
+    layers: RwLock<Vec<Arc<BloomFilter>>>,
+    initial_capacity: usize,
+    false_positive_rate: f64,
+}
+
+impl ScalableBloomFilter {
+    /// `false_positive_rate` bounds the compound rate across all layers
+    pub fn new(initial_capacity: usize, false_positive_rate: f64) -> Self {
+        let filter = Self {
+            layers: RwLock::new(Vec::new()),
+            initial_capacity: initial_capacity.max(1),
+            false_positive_rate,
+        };
+        filter.layers.write().unwrap().push(Arc::new(filter.layer(0)));
+        filter
+    }
+
+    pub fn contains<T: AsRef<[u8]> + ?Sized>(&self, item: &T) -> bool {
+        self.layers.read().unwrap().iter().any(|layer| layer.contains(item))
+    }
+
+    /// Adds an item to the newest layer. Returns `true` if it was not seen before.
+    pub fn insert<T: AsRef<[u8]> + ?Sized>(&self, item: &T) -> bool {
+        let current = {
+            let layers = self.layers.read().unwrap();
+            let (newest, older) = layers.split_last().unwrap();
+            if older.iter().any(|layer| layer.contains(item)) {
+                return false;
+            }
+            newest.clone()
+        };
+
+        let inserted = current.insert(item);
+        if inserted && current.is_full() {
+            let mut layers = self.layers.write().unwrap();
+            if Arc::ptr_eq(layers.last().unwrap(), &current) {
+                let next = self.layer(layers.len());
+                layers.push(Arc::new(next));
+            }
+        }
+        inserted
+    }
+
+    pub fn layer_count(&self) -> usize {
+        self.layers.read().unwrap().len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.layers.read().unwrap().iter().map(|layer| layer.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Memory used by the bit arrays of all layers
+    pub fn size_in_bytes(&self) -> u64 {
+        self.layers.read().unwrap().iter().map(|layer| layer.num_bits() / 8).sum()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = snapshot_header(SnapshotKind::Scalable);
+        out.extend_from_slice(&(self.initial_capacity as u64).to_le_bytes());
+        out.extend_from_slice(&self.false_positive_rate.to_le_bytes());
+        write_layers(&mut out, self.layers.read().unwrap().iter());
+        seal_snapshot(out)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = open_snapshot(bytes, SnapshotKind::Scalable)?;
+        let initial_capacity = reader.u64()? as usize;
+        let false_positive_rate = reader.f64()?;
+        let layers = read_layers(&mut reader)?;
+        reader.finish()?;
+
+        if layers.is_empty() {
+            return Err(anyhow!("Scalable bloom snapshot has no layers"));
+        }
+        Ok(Self {
+            layers: RwLock::new(layers),
+            initial_capacity: initial_capacity.max(1),
+            false_positive_rate,
+        })
+    }
+
+    fn layer(&self, index: usize) -> BloomFilter {
+        let capacity = self.initial_capacity.saturating_mul(SCALABLE_GROWTH.saturating_pow(index as u32));
+        let rate = self.false_positive_rate * (1.0 - SCALABLE_TIGHTENING) * SCALABLE_TIGHTENING.powi(index as i32);
+        BloomFilter::new(capacity, rate)
+    }
+}
+
+fn snapshot_header(kind: SnapshotKind) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(SNAPSHOT_MAGIC);
+    out.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+    out.push(kind as u8);
+    out
+}
+
+/// Appends the CRC32 of everything written so far
+fn seal_snapshot(mut out: Vec<u8>) -> Vec<u8> {
+    let checksum = crc32fast::hash(&out);
+    out.extend_from_slice(&checksum.to_le_bytes());
+    out
+}
+
+/// Verifies magic, version, kind and checksum, and returns a reader over the body
+fn open_snapshot(bytes: &[u8], kind: SnapshotKind) -> Result<SnapshotReader<'_>> {
+    let header_len = SNAPSHOT_MAGIC.len() + 3;
+    if bytes.len() < header_len + 4 {
+        return Err(anyhow!("Bloom snapshot truncated ({} bytes)", bytes.len()));
+    }
+    let (content, trailer) = bytes.split_at(bytes.len() - 4);
+    let expected = u32::from_le_bytes(trailer.try_into().unwrap());
+    let actual = crc32fast::hash(content);
+    if expected != actual {
+        return Err(anyhow!("Bloom snapshot checksum mismatch (expected {:08x}, got {:08x})", expected, actual));
+    }
+    if &content[..4] != SNAPSHOT_MAGIC {
+        return Err(anyhow!("Not a bloom filter snapshot"));
+    }
+    let version = u16::from_le_bytes([content[4], content[5]]);
+    if version != SNAPSHOT_VERSION {
+        return Err(anyhow!("Unsupported bloom snapshot version {} (expected {})", version, SNAPSHOT_VERSION));
+    }
+    if content[6] != kind as u8 {
+        return Err(anyhow!("Bloom snapshot holds kind {}, expected {:?}", content[6], kind));
+    }
+    Ok(SnapshotReader { bytes: &content[header_len..] })
+}
+
+fn write_layers<'a>(out: &mut Vec<u8>, layers: impl ExactSizeIterator<Item = &'a Arc<BloomFilter>>) {
+    out.extend_from_slice(&(layers.len() as u32).to_le_bytes());
+    for layer in layers {
+        layer.write_body(out);
+    }
+}
+
+fn read_layers(reader: &mut SnapshotReader<'_>) -> Result<Vec<Arc<BloomFilter>>> {
+    let count = reader.u32()?;
+    (0..count).map(|_| BloomFilter::read_body(reader).map(Arc::new)).collect()
+}
+
+struct SnapshotReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl SnapshotReader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        if self.bytes.len() < N {
+            return Err(anyhow!("Bloom snapshot body truncated"));
+        }
+        let (head, rest) = self.bytes.split_at(N);
+        self.bytes = rest;
+        Ok(head.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    fn f64(&mut self) -> Result<f64> {
+        Ok(f64::from_le_bytes(self.take()?))
+    }
+
+    fn finish(self) -> Result<()> {
+        if !self.bytes.is_empty() {
+            return Err(anyhow!("{} unexpected trailing bytes in bloom snapshot", self.bytes.len()));
+        }
+        Ok(())
+    }
+}
+
+/// Stable 64-bit hash (murmur-style mixing). `std`'s hasher is not guaranteed to stay
+/// the same across releases, which would break filters written to disk.
+fn hash64(bytes: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xFF51_AFD7_ED55_8CCD;
+    let mut hash = seed ^ (bytes.len() as u64).wrapping_mul(M);
+
+    let mut chunks = bytes.chunks_exact(8);
+    for chunk in &mut chunks {
+        let word = u64::from_le_bytes(chunk.try_into().unwrap());
+        hash = (hash ^ mix(word)).rotate_left(27).wrapping_mul(5).wrapping_add(0x52DC_E729);
+    }
+    let remainder = chunks.remainder();
+    if !remainder.is_empty() {
+        let mut tail = [0u8; 8];
+        tail[..remainder.len()].copy_from_slice(remainder);
+        hash ^= mix(u64::from_le_bytes(tail));
+    }
+    mix(hash)
+}
+
+fn mix(mut x: u64) -> u64 {
+    x ^= x >> 33;
+    x = x.wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+    x ^= x >> 33;
+    x = x.wrapping_mul(0xC4CE_B9FE_1A85_EC53);
+    x ^ (x >> 33)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_false_positive_rate_stays_near_target() {
+        let target = 0.01;
+        let filter = BloomFilter::new(10_000, target);
+        for i in 0..10_000u64 {
+            filter.insert(&i.to_be_bytes());
+        }
+        for i in 0..10_000u64 {
+            assert!(filter.contains(&i.to_be_bytes()), "no false negatives");
+        }
+
+        let probes = 100_000u64;
+        let false_positives = (10_000..10_000 + probes)
+            .filter(|i| filter.contains(&i.to_be_bytes()))
+            .count();
+        let rate = false_positives as f64 / probes as f64;
+        assert!(rate < target * 1.5, "false-positive rate {} above target {}", rate, target);
+        assert!(filter.estimated_false_positive_rate() < target * 1.5);
+    }
+
+    #[test]
+    fn test_insert_reports_new_items() {
+        let filter = BloomFilter::new(100, 0.001);
+        let hash = H256::repeat_byte(7);
+        assert!(filter.insert(&tx_key(hash)));
+        assert!(!filter.insert(&tx_key(hash)));
+        assert_eq!(filter.len(), 1);
+
+        // Same log index in a different block is a different log
+        assert!(filter.insert(&log_key(H256::repeat_byte(1), 3)));
+        assert!(filter.insert(&log_key(H256::repeat_byte(2), 3)));
+    }
+
+    #[test]
+    fn test_ethereum_bloom_matches_header_encoding() {
+        use ethers::{abi::ethereum_types::BloomInput, types::Address};
+
+        let pool = Address::repeat_byte(0x11);
+        let topic = H256::repeat_byte(0x22);
+        let mut bloom = Bloom::default();
+        bloom.accrue(BloomInput::Raw(pool.as_bytes()));
+        bloom.accrue(BloomInput::Raw(topic.as_bytes()));
+
+        let filter = BloomFilter::from_ethereum_bloom(&bloom);
+        assert!(filter.contains(&pool));
+        assert!(filter.contains_bits(&EthBloomBits::new(topic.as_bytes())));
+        assert!(!filter.contains(&Address::repeat_byte(0x33)));
+
+        // Inserting through the filter sets the same bits as the node would
+        let built = BloomFilter::ethereum();
+        built.insert(&pool);
+        built.insert(&topic);
+        for (a, b) in built.bits.iter().zip(filter.bits.iter()) {
+            assert_eq!(a.load(Ordering::Relaxed), b.load(Ordering::Relaxed));
+        }
+    }
+
+    #[test]
+    fn test_scalable_filter_grows_and_keeps_rate() {
+        let target = 0.01;
+        let filter = ScalableBloomFilter::new(1_000, target);
+        for i in 0..20_000u64 {
+            filter.insert(&i.to_be_bytes());
+        }
+        // 1k + 2k + 4k + 8k + 16k layers cover 20k items
+        assert_eq!(filter.layer_count(), 5);
+        assert!((0..20_000u64).all(|i| filter.contains(&i.to_be_bytes())));
+
+        let false_positives = (100_000..200_000u64).filter(|i| filter.contains(&i.to_be_bytes())).count();
+        assert!((false_positives as f64 / 100_000.0) < target);
+    }
+
+    #[test]
+    fn test_snapshot_round_trip_and_corruption() {
+        let filter = RotatingBloomFilter::new(100, 0.001, 3);
+        for i in 0..150u64 {
+            filter.mark_tx(H256::from_low_u64_be(i));
+        }
+        let bytes = filter.to_bytes();
+
+        let restored = RotatingBloomFilter::from_bytes(&bytes).unwrap();
+        assert_eq!(restored.generation_count(), 2);
+        assert_eq!(restored.len(), filter.len());
+        assert!((0..150u64).all(|i| restored.seen_tx(H256::from_low_u64_be(i))));
+
+        let mut corrupted = bytes.clone();
+        corrupted[20] ^= 0xFF;
+        let error = RotatingBloomFilter::from_bytes(&corrupted).err().expect("corruption detected");
+        assert!(error.to_string().contains("checksum"));
+        assert!(RotatingBloomFilter::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        // A rotating snapshot is not a scalable one
+        assert!(ScalableBloomFilter::from_bytes(&bytes).is_err());
+
+        let scalable = ScalableBloomFilter::new(10, 0.01);
+        scalable.insert(b"pair");
+        let restored = ScalableBloomFilter::from_bytes(&scalable.to_bytes()).unwrap();
+        assert!(restored.contains(b"pair"));
+        assert!(!restored.insert(b"pair"));
+    }
+
+    #[test]
+    fn test_rotation_forgets_oldest_generation() {
+        let filter = RotatingBloomFilter::new(100, 0.0001, 2);
+        let first = H256::repeat_byte(0xAA);
+        assert!(filter.mark_tx(first));
+
+        for i in 0..99u64 {
+            filter.insert(&i.to_be_bytes());
+        }
+        // The first generation is full, a second one started and still remembers it
+        assert_eq!(filter.generation_count(), 2);
+        assert!(filter.seen_tx(first));
+        assert!(!filter.mark_tx(first));
+
+        for i in 1_000..1_100u64 {
+            filter.insert(&i.to_be_bytes());
+        }
+        assert_eq!(filter.generation_count(), 2);
+        assert!(!filter.seen_tx(first));
+    }
+}
@@ -0,0 +1,115 @@
+//! On-disk scanner state
+//!
+//! With a state directory configured the scanner periodically writes its last processed
+//! block, the blocks still waiting for a retry and its seen-set of transactions and
+//! logs, and restores them at startup. Files
+//! are written to a temporary name and renamed, so a crash mid-write leaves the previous
+//! snapshot intact.
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::bloom_filter::RotatingBloomFilter;
+
+const CHECKPOINT_FILE: &str = "checkpoint.json";
+const SEEN_FILTER_FILE: &str = "seen.bloom";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScannerCheckpoint {
+    pub last_block: u64,
+    /// Blocks the scan moved past that still wait for a retry
+    #[serde(default)]
+    pub retry_blocks: Vec<u64>,
+    pub saved_at: DateTime<Utc>,
+}
+
+pub struct StateStore {
+    dir: PathBuf,
+}
+
+impl StateStore {
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create state directory {}", dir.display()))?;
+        Ok(Self { dir })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn load_checkpoint(&self) -> Result<Option<ScannerCheckpoint>> {
+        let Some(bytes) = self.read(CHECKPOINT_FILE)? else {
+            return Ok(None);
+        };
+        let checkpoint = serde_json::from_slice(&bytes)
+            .with_context(|| format!("Corrupt checkpoint in {}", self.dir.display()))?;
+        Ok(Some(checkpoint))
+    }
+
+    pub fn save_checkpoint(&self, checkpoint: &ScannerCheckpoint) -> Result<()> {
+        self.write_atomic(CHECKPOINT_FILE, &serde_json::to_vec_pretty(checkpoint)?)
+    }
+
+    pub fn load_seen_filter(&self) -> Result<Option<RotatingBloomFilter>> {
+        let Some(bytes) = self.read(SEEN_FILTER_FILE)? else {
+            return Ok(None);
+        };
+        RotatingBloomFilter::from_bytes(&bytes)
+            .with_context(|| format!("Invalid seen-set snapshot in {}", self.dir.display()))
+            .map(Some)
+    }
+
+    pub fn save_seen_filter(&self, filter: &RotatingBloomFilter) -> Result<()> {
+        self.write_atomic(SEEN_FILTER_FILE, &filter.to_bytes())
+    }
+
+    fn read(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        let path = self.dir.join(name);
+        match fs::read(&path) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+
+    fn write_atomic(&self, name: &str, bytes: &[u8]) -> Result<()> {
+        let path = self.dir.join(name);
+        let tmp = self.dir.join(format!("{}.tmp", name));
+        fs::write(&tmp, bytes).with_context(|| format!("Failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, &path).with_context(|| format!("Failed to replace {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::H256;
+
+    #[test]
+    fn test_state_round_trip() {
+        let dir = std::env::temp_dir().join(format!("scanner-state-{}", uuid::Uuid::new_v4()));
+        let store = StateStore::open(&dir).unwrap();
+        assert!(store.load_checkpoint().unwrap().is_none());
+        assert!(store.load_seen_filter().unwrap().is_none());
+
+        let seen = RotatingBloomFilter::new(100, 0.001, 2);
+        seen.mark_tx(H256::repeat_byte(1));
+        store.save_seen_filter(&seen).unwrap();
+        store.save_checkpoint(&ScannerCheckpoint { last_block: 42, retry_blocks: vec![40], saved_at: Utc::now() }).unwrap();
+
+        let checkpoint = store.load_checkpoint().unwrap().unwrap();
+        assert_eq!((checkpoint.last_block, checkpoint.retry_blocks), (42, vec![40]));
+        assert!(store.load_seen_filter().unwrap().unwrap().seen_tx(H256::repeat_byte(1)));
+
+        fs::write(dir.join(SEEN_FILTER_FILE), b"garbage").unwrap();
+        assert!(store.load_seen_filter().is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
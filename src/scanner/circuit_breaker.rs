@@ -1,65 +1,531 @@
-
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, broadcast};
+use serde::Serialize;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use tracing::{info, warn};
 use std::{
-    sync::atomic::{AtomicUsize,AtomicBool,Ordering},
+    collections::VecDeque,
+    str::FromStr,
+    sync::{Arc, RwLock, atomic::{AtomicUsize,AtomicBool,Ordering}},
     time::{Duration,Instant},
 };
 
+use crate::const_and_addr;
+
+/// Breaker states: `Closed` lets everything through, `Open` rejects everything until the
+/// cool-down elapses, `HalfOpen` lets a few probe calls decide whether to close or re-open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+/// Sliding window over which the failure rate is measured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureWindow {
+    /// Calls recorded within the last `Duration`
+    Time(Duration),
+    /// The last N recorded calls
+    Calls(usize),
+}
+
+impl FromStr for FailureWindow {
+    type Err = anyhow::Error;
+
+    /// Accepts `"60s"` for a time window and `"100"` / `"100calls"` for a call-count window
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if let Some(seconds) = s.strip_suffix('s').filter(|rest| !rest.ends_with("call")) {
+            let seconds: u64 = seconds.parse().map_err(|_| anyhow!("Invalid time window '{}'", s))?;
+            return Ok(FailureWindow::Time(Duration::from_secs(seconds)));
+        }
+        let calls: usize = s.trim_end_matches("calls")
+            .parse()
+            .map_err(|_| anyhow!("Invalid failure window '{}', expected e.g. '60s' or '100calls'", s))?;
+        Ok(FailureWindow::Calls(calls.max(1)))
+    }
+}
+
+/// When a closed breaker opens
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TripPolicy {
+    /// After `threshold` consecutive failures
+    ConsecutiveFailures,
+    /// When the failure rate within the window reaches `max_failure_rate`,
+    /// once at least `min_calls` calls are in the window
+    FailureRate {
+        window: FailureWindow,
+        max_failure_rate: f64,
+        min_calls: usize,
+    },
+}
+
+/// Why a breaker changed state
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum TransitionReason {
+    FailureThreshold,
+    FailureRate(f64),
+    CoolDownElapsed,
+    ProbeFailed,
+    ProbesSucceeded,
+    ManualReset,
+}
+
+/// A single state change, published to subscribers and listeners
+#[derive(Debug, Clone, Serialize)]
+pub struct BreakerTransition {
+    pub breaker: String,
+    pub from: CircuitState,
+    pub to: CircuitState,
+    pub reason: TransitionReason,
+    pub error_count: usize,
+    pub timestamp: DateTime<Utc>,
+}
+
+pub type TransitionListener = Arc<dyn Fn(&BreakerTransition) + Send + Sync>;
+
+const TRANSITION_CHANNEL_CAPACITY: usize = 256;
+
+/// Broadcast channel plus callback list. Cloning shares the same hub, so a registry can
+/// hand one hub to all of its breakers.
+#[derive(Clone)]
+pub struct TransitionHub {
+    sender: broadcast::Sender<BreakerTransition>,
+    listeners: Arc<RwLock<Vec<TransitionListener>>>,
+}
+
+impl Default for TransitionHub {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(TRANSITION_CHANNEL_CAPACITY);
+        Self {
+            sender,
+            listeners: Arc::new(RwLock::new(Vec::new())),
+        }
+    }
+}
+
+impl TransitionHub {
+    pub fn subscribe(&self) -> broadcast::Receiver<BreakerTransition> {
+        self.sender.subscribe()
+    }
+
+    /// Registers a callback run on every transition. Callbacks run inline and must not block.
+    pub fn on_transition<F>(&self, listener: F)
+    where
+        F: Fn(&BreakerTransition) + Send + Sync + 'static,
+    {
+        self.listeners.write().unwrap().push(Arc::new(listener));
+    }
+
+    fn publish(&self, transition: BreakerTransition) {
+        match transition.to {
+            CircuitState::Open => warn!("⚠️ Circuit breaker {} opened ({:?}, {} errors)",
+                transition.breaker, transition.reason, transition.error_count),
+            _ => info!("🔌 Circuit breaker {} {:?} -> {:?} ({:?})",
+                transition.breaker, transition.from, transition.to, transition.reason),
+        }
+
+        let listeners = self.listeners.read().unwrap().clone();
+        for listener in listeners {
+            listener(&transition);
+        }
+        // No subscribers is fine
+        let _ = self.sender.send(transition);
+    }
+}
+
+struct BreakerInner {
+    state: CircuitState,
+    opened_at: Option<Instant>,
+    probes_in_flight: usize,
+    probe_successes: usize,
+    /// Recent call outcomes (`true` = failure), only kept in failure-rate mode
+    outcomes: VecDeque<(Instant, bool)>,
+}
+
 pub struct CircuitBreaker {
+    name: String,
+    hub: TransitionHub,
     error_count: AtomicUsize,
     circuit_breaker_threshold: usize,
     cool_down: Duration,
-    last_tripped: Mutex<Option<Instant>>,
+    half_open_max_probes: usize,
+    policy: TripPolicy,
+    inner: Mutex<BreakerInner>,
     auto_reset: AtomicBool,
 }
 
 impl CircuitBreaker {
     pub fn new(max_errors: usize, cool_down: Duration, auto_reset: bool) -> Self {
         Self {
+            name: "scanner".to_string(),
+            hub: TransitionHub::default(),
             error_count: AtomicUsize::new(0),
             circuit_breaker_threshold: max_errors,
             cool_down,
-            last_tripped: Mutex::new(None),
+            half_open_max_probes: const_and_addr::HALF_OPEN_MAX_PROBES,
+            policy: TripPolicy::ConsecutiveFailures,
+            inner: Mutex::new(BreakerInner {
+                state: CircuitState::Closed,
+                opened_at: None,
+                probes_in_flight: 0,
+                probe_successes: 0,
+                outcomes: VecDeque::new(),
+            }),
             auto_reset: AtomicBool::new(auto_reset),
 
 
         }
     }
 
-    pub async fn is_tripped(&self) -> bool {
-        let count = self.error_count.load(Ordering::Relaxed);
-        if count < self.circuit_breaker_threshold {
-            return false;
-        }
-
-        let guard = self.last_tripped.lock().await;
-        match *guard {
-            Some(last) if last.elapsed() < self.cool_down => true,
-            Some (_) if self.auto_reset.load(Ordering::Relaxed) => {
-                drop(guard); // ---------------------
-                self.reset().await;
-                false
+    /// Number of probe calls allowed in half-open state, and successes needed to close
+    pub fn with_half_open_probes(mut self, probes: usize) -> Self {
+        self.half_open_max_probes = probes.max(1);
+        self
+    }
+
+    pub fn with_policy(mut self, policy: TripPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Name carried by published transitions
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Publishes transitions through a shared hub instead of a private one
+    pub fn with_hub(mut self, hub: TransitionHub) -> Self {
+        self.hub = hub;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<BreakerTransition> {
+        self.hub.subscribe()
+    }
+
+    pub fn on_transition<F>(&self, listener: F)
+    where
+        F: Fn(&BreakerTransition) + Send + Sync + 'static,
+    {
+        self.hub.on_transition(listener);
+    }
+
+    /// Current state. An open breaker with `auto_reset` moves to half-open once the
+    /// cool-down has elapsed.
+    pub async fn state(&self) -> CircuitState {
+        let mut inner = self.inner.lock().await;
+        self.refresh(&mut inner);
+        inner.state
+    }
+
+    /// Asks for permission to make a call. In half-open state this hands out one of the
+    /// limited probe slots; the outcome must be reported with `record_success`/`record_failure`.
+    pub async fn allow_request(&self) -> bool {
+        let mut inner = self.inner.lock().await;
+        self.refresh(&mut inner);
+        match inner.state {
+            CircuitState::Closed => true,
+            CircuitState::Open => false,
+            CircuitState::HalfOpen if inner.probes_in_flight < self.half_open_max_probes => {
+                inner.probes_in_flight += 1;
+                true
+            }
+            CircuitState::HalfOpen => false,
+        }
+    }
+
+    pub async fn record_success(&self) {
+        let mut inner = self.inner.lock().await;
+        self.refresh(&mut inner);
+        match inner.state {
+            CircuitState::Closed => match self.policy {
+                // Failures are counted as a consecutive streak
+                TripPolicy::ConsecutiveFailures => self.error_count.store(0, Ordering::Relaxed),
+                TripPolicy::FailureRate { .. } => self.record_outcome(&mut inner, false),
             },
-            Some(_) => true,
-            None => false,
+            CircuitState::HalfOpen => {
+                inner.probes_in_flight = inner.probes_in_flight.saturating_sub(1);
+                inner.probe_successes += 1;
+                if inner.probe_successes >= self.half_open_max_probes {
+                    self.close(&mut inner, TransitionReason::ProbesSucceeded);
+                }
+            }
+            CircuitState::Open => {}
         }
     }
 
-    pub async fn trip(&self) {
+    pub async fn record_failure(&self) {
         let new_count = self.error_count.fetch_add(1, Ordering::Relaxed) +1;
-        if new_count >= self.circuit_breaker_threshold{
-            let mut guard = self.last_tripped.lock().await;
-            *guard = Some(Instant::now());
+        let mut inner = self.inner.lock().await;
+        self.refresh(&mut inner);
+        match inner.state {
+            CircuitState::Closed => {
+                let reason = match self.policy {
+                    TripPolicy::ConsecutiveFailures => (new_count >= self.circuit_breaker_threshold)
+                        .then_some(TransitionReason::FailureThreshold),
+                    TripPolicy::FailureRate { .. } => {
+                        self.record_outcome(&mut inner, true);
+                        self.exceeded_failure_rate(&inner).map(TransitionReason::FailureRate)
+                    }
+                };
+                if let Some(reason) = reason {
+                    self.open(&mut inner, reason);
+                }
+            }
+            // A single failed probe is enough to re-open
+            CircuitState::HalfOpen => self.open(&mut inner, TransitionReason::ProbeFailed),
+            _ => {}
+        }
+    }
+
+    /// Returns a half-open probe slot for a call that says nothing about the endpoint's
+    /// health (e.g. a permanent error), without counting a success or failure
+    pub async fn release_probe(&self) {
+        let mut inner = self.inner.lock().await;
+        self.refresh(&mut inner);
+        if inner.state == CircuitState::HalfOpen {
+            inner.probes_in_flight = inner.probes_in_flight.saturating_sub(1);
+        }
+    }
+
+    /// Compatibility check: `true` while calls are rejected
+    pub async fn is_tripped(&self) -> bool {
+        let mut inner = self.inner.lock().await;
+        self.refresh(&mut inner);
+        match inner.state {
+            CircuitState::Closed => false,
+            CircuitState::Open => true,
+            CircuitState::HalfOpen => inner.probes_in_flight >= self.half_open_max_probes,
         }
     }
 
+    /// Compatibility alias for `record_failure`
+    pub async fn trip(&self) {
+        self.record_failure().await;
+    }
+
+    /// Forces the breaker back to closed
     pub async fn reset(&self) {
-        self.error_count.store(0,Ordering::Relaxed);
-        let mut guard = self.last_tripped.lock().await;
-        *guard = None;
+        let mut inner = self.inner.lock().await;
+        self.close(&mut inner, TransitionReason::ManualReset);
     }
 
     pub fn error_count(&self) -> usize {
         self.error_count.load(Ordering::Relaxed)
     }
+
+    /// Failure rate over the current window, `None` while below the minimum call volume
+    pub async fn failure_rate(&self) -> Option<f64> {
+        let mut inner = self.inner.lock().await;
+        self.prune_window(&mut inner);
+        self.window_failure_rate(&inner)
+    }
+
+    fn record_outcome(&self, inner: &mut BreakerInner, failed: bool) {
+        inner.outcomes.push_back((Instant::now(), failed));
+        self.prune_window(inner);
+    }
+
+    fn prune_window(&self, inner: &mut BreakerInner) {
+        let TripPolicy::FailureRate { window, .. } = self.policy else {
+            return;
+        };
+        match window {
+            FailureWindow::Time(span) => {
+                while inner.outcomes.front().is_some_and(|(at, _)| at.elapsed() > span) {
+                    inner.outcomes.pop_front();
+                }
+            }
+            FailureWindow::Calls(max_calls) => {
+                while inner.outcomes.len() > max_calls {
+                    inner.outcomes.pop_front();
+                }
+            }
+        }
+    }
+
+    fn window_failure_rate(&self, inner: &BreakerInner) -> Option<f64> {
+        let TripPolicy::FailureRate { min_calls, .. } = self.policy else {
+            return None;
+        };
+        let calls = inner.outcomes.len();
+        if calls == 0 || calls < min_calls {
+            return None;
+        }
+        let failures = inner.outcomes.iter().filter(|(_, failed)| *failed).count();
+        Some(failures as f64 / calls as f64)
+    }
+
+    /// The current failure rate if it reached the configured maximum
+    fn exceeded_failure_rate(&self, inner: &BreakerInner) -> Option<f64> {
+        let TripPolicy::FailureRate { max_failure_rate, .. } = self.policy else {
+            return None;
+        };
+        self.window_failure_rate(inner).filter(|rate| *rate >= max_failure_rate)
+    }
+
+    fn refresh(&self, inner: &mut BreakerInner) {
+        if inner.state != CircuitState::Open || !self.auto_reset.load(Ordering::Relaxed) {
+            return;
+        }
+        if inner.opened_at.is_some_and(|opened| opened.elapsed() >= self.cool_down) {
+            inner.probes_in_flight = 0;
+            inner.probe_successes = 0;
+            self.transition(inner, CircuitState::HalfOpen, TransitionReason::CoolDownElapsed);
+        }
+    }
+
+    fn open(&self, inner: &mut BreakerInner, reason: TransitionReason) {
+        inner.opened_at = Some(Instant::now());
+        inner.probes_in_flight = 0;
+        inner.probe_successes = 0;
+        inner.outcomes.clear();
+        self.transition(inner, CircuitState::Open, reason);
+    }
+
+    fn close(&self, inner: &mut BreakerInner, reason: TransitionReason) {
+        inner.opened_at = None;
+        inner.probes_in_flight = 0;
+        inner.probe_successes = 0;
+        inner.outcomes.clear();
+        self.transition(inner, CircuitState::Closed, reason);
+        self.error_count.store(0,Ordering::Relaxed);
+    }
+
+    fn transition(&self, inner: &mut BreakerInner, to: CircuitState, reason: TransitionReason) {
+        let from = inner.state;
+        inner.state = to;
+        if from == to {
+            return;
+        }
+        self.hub.publish(BreakerTransition {
+            breaker: self.name.clone(),
+            from,
+            to,
+            reason,
+            error_count: self.error_count.load(Ordering::Relaxed),
+            timestamp: Utc::now(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_half_open_probes_close_breaker() {
+        let breaker = CircuitBreaker::new(2, Duration::from_millis(20), true).with_half_open_probes(2);
+        breaker.record_failure().await;
+        assert_eq!(breaker.state().await, CircuitState::Closed);
+        breaker.record_failure().await;
+        assert_eq!(breaker.state().await, CircuitState::Open);
+        assert!(breaker.is_tripped().await);
+        assert!(!breaker.allow_request().await);
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(breaker.state().await, CircuitState::HalfOpen);
+
+        // Only the configured number of probes gets through
+        assert!(breaker.allow_request().await);
+        assert!(breaker.allow_request().await);
+        assert!(!breaker.allow_request().await);
+
+        breaker.record_success().await;
+        assert_eq!(breaker.state().await, CircuitState::HalfOpen);
+        breaker.record_success().await;
+        assert_eq!(breaker.state().await, CircuitState::Closed);
+        assert_eq!(breaker.error_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_failed_probe_reopens_breaker() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(20), true);
+        breaker.trip().await;
+        tokio::time::sleep(Duration::from_millis(30)).await;
+
+        assert!(breaker.allow_request().await);
+        breaker.record_failure().await;
+        assert_eq!(breaker.state().await, CircuitState::Open);
+
+        breaker.reset().await;
+        assert_eq!(breaker.state().await, CircuitState::Closed);
+        assert!(!breaker.is_tripped().await);
+    }
+
+    #[tokio::test]
+    async fn test_failure_rate_respects_min_calls_and_window() {
+        let breaker = CircuitBreaker::new(usize::MAX, Duration::from_secs(60), true)
+            .with_policy(TripPolicy::FailureRate {
+                window: FailureWindow::Calls(4),
+                max_failure_rate: 0.6,
+                min_calls: 4,
+            });
+
+        // Intermittent failures interleaved with successes never reach 60% of 4 calls
+        for _ in 0..10 {
+            breaker.record_failure().await;
+            breaker.record_success().await;
+            breaker.record_success().await;
+        }
+        assert_eq!(breaker.state().await, CircuitState::Closed);
+
+        // Below the minimum call volume nothing trips, even at 100% failures
+        breaker.reset().await;
+        for _ in 0..3 {
+            breaker.record_failure().await;
+        }
+        assert_eq!(breaker.state().await, CircuitState::Closed);
+        assert_eq!(breaker.failure_rate().await, None);
+
+        breaker.record_failure().await;
+        assert_eq!(breaker.state().await, CircuitState::Open);
+    }
+
+    #[tokio::test]
+    async fn test_transitions_are_published_to_subscribers_and_listeners() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(20), true).with_name("test");
+        let mut events = breaker.subscribe();
+        let seen = Arc::new(AtomicUsize::new(0));
+        let counter = seen.clone();
+        breaker.on_transition(move |_| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+
+        breaker.record_failure().await;
+        let opened = events.recv().await.unwrap();
+        assert_eq!(opened.breaker, "test");
+        assert_eq!((opened.from, opened.to), (CircuitState::Closed, CircuitState::Open));
+        assert_eq!(opened.reason, TransitionReason::FailureThreshold);
+        assert_eq!(opened.error_count, 1);
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(breaker.state().await, CircuitState::HalfOpen);
+        assert_eq!(events.recv().await.unwrap().reason, TransitionReason::CoolDownElapsed);
+
+        breaker.reset().await;
+        let closed = events.recv().await.unwrap();
+        assert_eq!(closed.to, CircuitState::Closed);
+        assert_eq!(closed.reason, TransitionReason::ManualReset);
+
+        // Resetting an already closed breaker is not a transition
+        breaker.reset().await;
+        assert!(events.try_recv().is_err());
+        assert_eq!(seen.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn test_failure_window_parsing() {
+        assert_eq!("60s".parse::<FailureWindow>().unwrap(), FailureWindow::Time(Duration::from_secs(60)));
+        assert_eq!("100".parse::<FailureWindow>().unwrap(), FailureWindow::Calls(100));
+        assert_eq!("20calls".parse::<FailureWindow>().unwrap(), FailureWindow::Calls(20));
+        assert!("soon".parse::<FailureWindow>().is_err());
+    }
 }
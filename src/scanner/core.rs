@@ -3,48 +3,60 @@
 //! The MevScanner orchestrates all components to detect arbitrage opportunities
 //! by monitoring blockchain state changes and anaylyzing price differences
 use std::{
+    collections::HashSet,
+    pin::pin,
     sync::{Arc, atomic::{AtomicU64, Ordering}}, 
     time::{Duration, Instant},
 };
 use tokio::{
-    time::{sleep, timeout},
+    time::{sleep, timeout, MissedTickBehavior},
     sync::{Notify, Mutex,Semaphore, RwLock},
 };
 use ethers::{
-    providers::{Http, Middleware, Provider, StreamExt, Ws, JsonRpcClient},
-    types::{transaction, Address, Block, Log, Transaction, TransactionReceipt, H256, U256},
+    providers::{Http, Middleware, Provider, ProviderError, StreamExt, Ws, JsonRpcClient},
+    signers::{LocalWallet, Signer},
+    types::{transaction, Address, Block, Log, Transaction, TransactionReceipt, H256, I256, U256},
 };
 use futures::stream::{self, StreamExt as FuturesStreamExt};
 use anyhow::{anyhow, Result, Context};
 use tracing::{info,debug,warn,error};
-use once_cell::sync::Lazy;
+use chrono::Utc;
 
 
 
 use crate::{
     
-    // arbitrage::{ArbitrageDetector,ArbitrageOpporutnity},
+    address_book::DexKind,
+    arbitrage::{ArbitrageDetector, ArbitrageOpportunity},
     // cache::StateCache, 
-    config::ScannerConfig, 
-    const_and_addr::{self, MAX_RECEIPT_CONCURRENCY}, 
-    // mempool::MempoolWatcher, 
-    // pools::{PoolManger, PoolState}, 
+    config::{ScannerConfig, ScanMode}, 
+    mempool::{MempoolWatcher, PendingProjection, PendingSwap},
+    pools::{PoolManager, PoolState},
+    pricing::V3PoolSnapshot,
+    simulation::{Bundle, SimulationBackend, Simulator},
+    const_and_addr,
     // providers::ProviderManager,
     storage::{
-        StorageDriftDetector, SlotDriftEvent,  SlotKey, StorageDelta,
+        StorageDriftDetector, SlotDriftEvent,  SlotKey, StorageDelta, BlockAnalysis, StorageLayout,
         StorageChangeType, SlotSemantic, CriticalLevel,
     },
 }; 
-use super::{BloomFilter, CircuitBreaker};
+use super::{ReplayBlock, RotatingBloomFilter, ScannerCheckpoint, StateStore, BlockRetryQueue, BreakerTransition, DeadLetterBlock, WsWatchdog};
+use super::breaker_registry::{BreakerRegistry, BreakerStatus, RpcOperation, redact_endpoint};
+use super::circuit_breaker::CircuitState;
+use super::retry_queue::ReceiptProgress;
+use super::log_filter::{self, LogBatcher, MonitoredContracts};
 
 
 const WS_TIMEOUT: Duration = Duration::from_secs(30);
-const BLOCK_PROCESSING_TIMEOUT: Duration= Duration::from_secs(10);
-const MAX_BLOCK_BATCH_SIZE: usize = 5;
+const WS_PING_INTERVAL: Duration = Duration::from_secs(15);
+const WS_STALE_HEAD_MULTIPLIER: u32 = 3;
+const MAX_WS_HEAD_LAG: u64 = 3;
 const RECONNECT_DELAY: Duration = Duration::from_secs(1);
 const MAX_CONSECUTIVE_ERRORS: usize = 3;
 const MAX_ERRORS_BEFORE_TRIP: usize = 5;
-const HTTP_POLL_INTERVAL: Duration = Duration::from_millis(200);
+const LOG_BATCH_IDLE_FLUSH: Duration = Duration::from_millis(250);
+const RETRY_POLL_INTERVAL: Duration = Duration::from_secs(1);
 const SLOT_CACHE_SIZE: usize =  10_000;
 const DRIFT_CONFIDENCE_THRESHOLD: f64 = 0.8;
 /// Main MEV scanner that coordinates all components
@@ -62,14 +74,17 @@ pub struct MevScanner {
     ///Manages RPC providers with failover
     // provider_manager: ProviderManager,
 
-    /// Handles pool state and reserver data 
-    // pool_manager: PoolManger,
+    /// Handles pool state and reserve data
+    pool_manager: Arc<PoolManager>,
 
     ///Detect arbtirage opportunities
-    // arbitrage_detector: ArbitrageDetector,
+    arbitrage_detector: Arc<ArbitrageDetector>,
 
-    /// Monitors mempool for relevant transactions
-    // mempool_watcher: Arc<Mutex<MempoolWatcher>>,
+    /// Recent arbitrage opportunities, newest last
+    recent_opportunities: Arc<RwLock<Vec<ArbitrageOpportunity>>>,
+
+    /// Decodes pending swaps through watched routers and pools, when enabled
+    mempool_watcher: Option<Arc<MempoolWatcher>>,
 
     /// Caches blockchain state
     // state_cache: StateCache,
@@ -78,14 +93,14 @@ pub struct MevScanner {
     /// Storage drift detector - SINGLE SOURCE OF TRUTH for all storage data
     storage_drift_detector: Arc<StorageDriftDetector>,
 
-    /// Circuit breaker for fault tolerance
-    circuit_breaker: CircuitBreaker,
+    /// Circuit breakers per endpoint and operation for fault tolerance
+    breakers: BreakerRegistry,
 
     /// Scanner configuration
     config: ScannerConfig,
 
     /// Last processed block number
-    last_block: AtomicU64,
+    last_block: Arc<AtomicU64>,
 
     /// Connection state
     connection_state: Arc<Mutex<ConnectionState>>,
@@ -95,6 +110,30 @@ pub struct MevScanner {
 
     /// Recent drift events for analysis (lightweight storage)
     recent_drift_events:  Arc<RwLock<Vec<SlotDriftEvent>>>,
+
+    /// Timed-out blocks waiting for another attempt, plus the dead-letter list
+    retry_queue: Arc<BlockRetryQueue>,
+
+    /// Expected time between heads, drives the WS stale-head watchdog
+    expected_block_time: Duration,
+
+    /// Contracts watched in log-filter mode
+    monitored_contracts: Arc<MonitoredContracts>,
+
+    /// Transactions and logs already analyzed, so replays are not counted twice
+    seen: Arc<RotatingBloomFilter>,
+
+    /// Persists the block checkpoint and seen-set when a state directory is configured
+    state_store: Option<Arc<StateStore>>,
+
+    /// Bounds in-flight receipt requests across WS and HTTP
+    receipt_permits: Arc<Semaphore>,
+
+    /// Transaction signer, only loaded when submission is enabled
+    signer: Option<LocalWallet>,
+
+    /// Validates arbitrage bundles before they are kept, when configured
+    simulator: Option<Arc<Simulator>>,
 }
 
 struct ConnectionState {
@@ -118,6 +157,16 @@ impl ConnectionState {
 impl MevScanner {
     /// Creates a new MEV scanner with the given configuration
     pub async fn new(config: ScannerConfig) -> Result<Self> {
+        Self::build(config, true).await
+    }
+
+    /// Scanner on the HTTP endpoint alone, for one-off work such as backfills. It never
+    /// opens a WebSocket, so `endpoints.ws_url` may be unset.
+    pub async fn http_only(config: ScannerConfig) -> Result<Self> {
+        Self::build(config, false).await
+    }
+
+    async fn build(config: ScannerConfig, connect_ws: bool) -> Result<Self> {
    
         let ws_endpoint = config.primary_rpc_url();
             
@@ -131,13 +180,15 @@ impl MevScanner {
         );
 
         // Try to initalize WebSocket provider
-        let primary_provider = match Provider::<Ws>::connect(ws_endpoint).await{
-            Ok(ws_provider) => {
+        let ws_connection = if connect_ws { Some(Provider::<Ws>::connect(ws_endpoint).await) } else { None };
+        let primary_provider = match ws_connection {
+            None => Arc::new(Mutex::new(None)),
+            Some(Ok(ws_provider)) => {
                 info!("✅ WebSocket provider connected successfully....");
                 initial_connection_state.ws_connected = true;
                 Arc::new(Mutex::new(Some(Arc::new(ws_provider))))
             }
-            Err(e) => {
+            Some(Err(e)) => {
                 warn!("⚠️ WebSocket connection failed, will retry: {}", e);
                 Arc::new(Mutex::new(None))
            
@@ -151,14 +202,7 @@ impl MevScanner {
 
         let ws_endpoint = &config.primary_rpc_url();
 
-        // let pool_manager = PoolManger::new();
-        // let arbitrage_detector = ArbitrageDetector::new(config.min_profit_threshold());
 
-        
-        // let mempool_watcher = Arc::new(
-        //     Mutex::new(MempoolWatcher::new(ws_endpoint).await?
-        // ));
-       
 
         // let state_cache = StateCache::new(
         //     const_and_addr::DEFAULT_CACHE_SIZE,
@@ -167,86 +211,532 @@ impl MevScanner {
 
         // let slot_cache = SlotCache::new(const_and_addr::SLOT_CACHE_SIZE);
 
-        let storage_drift_detector = Arc::new(StorageDriftDetector::new());
-
-        let circuit_breaker = CircuitBreaker::new(
-            const_and_addr::CIRCUIT_BREAKER_THRESHOLD,
-            const_and_addr::COOL_DOWN_PERIOD,
-            true,
+        // Refuse to scan a node that serves a different chain than the profile describes
+        let chain = config.chain();
+        let chain_id = fallback_provider.get_chainid().await
+            .with_context(|| format!("Failed to query chain id from {}", redact_endpoint(http_endpoint)))?;
+        chain.verify_chain_id(chain_id.as_u64(), &redact_endpoint(http_endpoint))?;
+        if let Some(ws_provider) = primary_provider.lock().await.as_ref() {
+            match ws_provider.get_chainid().await {
+                Ok(ws_chain_id) => chain.verify_chain_id(ws_chain_id.as_u64(), &redact_endpoint(ws_endpoint))?,
+                Err(e) => warn!("⚠️ Could not query chain id over WebSocket: {}", e),
+            }
+        }
+        info!("⛓️ Scanning {} (chain id {})", chain.name, chain.chain_id);
+        let expected_block_time = chain.block_time;
+
+        let storage_drift_detector = Arc::new(StorageDriftDetector::for_chain(chain));
+        let pool_manager = Arc::new(PoolManager::for_chain(chain));
+        let mempool_watcher = config.strategies().mempool
+            .then(|| Arc::new(MempoolWatcher::new(chain, pool_manager.clone())));
+        let arbitrage_detector = Arc::new(ArbitrageDetector::from_config(&config, pool_manager.clone()));
+
+        let breakers = BreakerRegistry::new(
+            config.circuit_breaker_threshold(),
+            *config.circuit_breaker_cooldown_seconds(),
+            config.circuit_breaker_policy(),
         );
+        let state_store = config.state_dir()
+            .as_ref()
+            .map(StateStore::open)
+            .transpose()?
+            .map(Arc::new);
+        let (last_block, retry_blocks, seen) = Self::restore_state(&config, state_store.as_deref());
+
+        let monitored_contracts = Arc::new(MonitoredContracts::default());
+        for contract in config.monitored_contracts() {
+            monitored_contracts.add(contract.address, contract.needs_traces).await;
+        }
+        for address in pool_manager.addresses() {
+            monitored_contracts.add(address, false).await;
+        }
+        let receipt_permits = Arc::new(Semaphore::new(config.max_receipt_concurrency()));
+        let retry_queue = Arc::new(BlockRetryQueue::new(config.max_block_retries(), config.retry_base_delay()));
+        if !retry_blocks.is_empty() {
+            info!("📍 Re-queued {} blocks that were waiting for a retry", retry_blocks.len());
+            retry_queue.requeue(retry_blocks).await;
+        }
+
+        // Keystore decryption is slow and may prompt for a password
+        let source = config.signer().clone();
+        let signer = tokio::task::spawn_blocking(move || source.load()).await??;
+        match &signer {
+            Some(wallet) => info!("🔑 Signer {:?} loaded ({})", wallet.address(), config.signer().describe()),
+            None => info!("👀 Running read-only, transaction submission disabled"),
+        }
+
+        let simulator = match config.simulation_backend() {
+            None => None,
+            Some(SimulationBackend::EthCall) => Some(Simulator::eth_call(fallback_provider.clone())),
+            Some(SimulationBackend::Fork) => {
+                let fork_url = config.simulation_fork_url().clone().unwrap_or_else(|| http_endpoint.to_string());
+                Some(Simulator::fork(fork_url).context("Failed to start the simulation fork")?)
+            }
+        };
+        if let Some(simulator) = &simulator {
+            info!("🧪 Simulating opportunities with the {} backend", simulator.backend().as_str());
+        }
+
         let ws_url = ws_endpoint.to_string();
         Ok(Self {
             ws_endpoint: ws_url,
             primary_provider,
             fallback_provider,
             // provider_manager,
-            // pool_manager,
-            // arbitrage_detector,
-            // mempool_watcher,
+            pool_manager,
+            arbitrage_detector,
+            recent_opportunities: Arc::new(RwLock::new(Vec::new())),
+            mempool_watcher,
             // state_cache,
             // slot_cache,
             storage_drift_detector,
-            circuit_breaker,
+            breakers,
             config,
-            last_block: AtomicU64::new(0),
+            last_block: Arc::new(AtomicU64::new(last_block)),
             connection_state: Arc::new(Mutex::new(initial_connection_state)),
             ws_reconnected: Arc::new(Notify::new()),
             recent_drift_events:  Arc::new(RwLock::new(Vec::new())),
+            retry_queue,
+            expected_block_time,
+            monitored_contracts,
+            seen: Arc::new(seen),
+            state_store,
+            receipt_permits,
+            signer,
+            simulator: simulator.map(Arc::new),
         })
     }
 
+    /// Address transactions would be sent from, `None` when read-only
+    pub fn signer_address(&self) -> Option<Address> {
+        self.signer.as_ref().map(|wallet| wallet.address())
+    }
+
+    /// Loads the checkpoint, with the blocks that were waiting for a retry, and the
+    /// seen-set from the state directory. Missing or unreadable state is not fatal, the
+    /// scanner then starts fresh.
+    fn restore_state(config: &ScannerConfig, store: Option<&StateStore>) -> (u64, Vec<u64>, RotatingBloomFilter) {
+        let fresh_filter = || RotatingBloomFilter::new(
+            config.seen_filter_items(),
+            config.seen_filter_false_positive_rate(),
+            config.seen_filter_generations(),
+        );
+        let Some(store) = store else {
+            return (0, Vec::new(), fresh_filter());
+        };
+
+        let (last_block, retry_blocks) = match store.load_checkpoint() {
+            Ok(Some(checkpoint)) => {
+                info!("📍 Resuming from block {} (checkpoint saved {})", checkpoint.last_block, checkpoint.saved_at);
+                (checkpoint.last_block, checkpoint.retry_blocks)
+            }
+            Ok(None) => (0, Vec::new()),
+            Err(e) => {
+                warn!("⚠️ Ignoring unreadable checkpoint: {:#}", e);
+                (0, Vec::new())
+            }
+        };
+        let seen = match store.load_seen_filter() {
+            Ok(Some(seen)) => {
+                info!("📍 Restored seen-set with {} entries", seen.len());
+                seen
+            }
+            Ok(None) => fresh_filter(),
+            Err(e) => {
+                warn!("⚠️ Ignoring unreadable seen-set: {:#}", e);
+                fresh_filter()
+            }
+        };
+        (last_block, retry_blocks, seen)
+    }
+
+    /// Writes the block checkpoint and seen-set to the state directory, if configured
+    pub async fn save_state(&self) -> Result<()> {
+        let Some(store) = self.state_store.clone() else {
+            return Ok(());
+        };
+        Self::persist_state(store, self.seen.clone(), self.last_block.clone(), &self.retry_queue).await?;
+        Ok(())
+    }
+
+    /// Returns the block the checkpoint was saved at
+    async fn persist_state(
+        store: Arc<StateStore>,
+        seen: Arc<RotatingBloomFilter>,
+        last_block: Arc<AtomicU64>,
+        retry_queue: &BlockRetryQueue,
+    ) -> Result<u64> {
+        // The scan moves past blocks waiting for a retry, so they are saved with it
+        let retry_blocks = retry_queue.pending_blocks().await;
+        // The position is read after the seen-set is written: blocks after the checkpoint
+        // are scanned again on restart, their transactions must not be marked as seen
+        tokio::task::spawn_blocking(move || {
+            store.save_seen_filter(&seen)?;
+            let last_block = last_block.load(Ordering::Relaxed);
+            store.save_checkpoint(&ScannerCheckpoint { last_block, retry_blocks, saved_at: Utc::now() })?;
+            Ok(last_block)
+        })
+        .await?
+    }
+
+    fn start_checkpointing(&self) -> Option<tokio::task::JoinHandle<()>> {
+        let store = self.state_store.clone()?;
+        let seen = self.seen.clone();
+        let last_block = self.last_block.clone();
+        let retry_queue = self.retry_queue.clone();
+        let period = self.config.checkpoint_interval();
+
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                match Self::persist_state(store.clone(), seen.clone(), last_block.clone(), &retry_queue).await {
+                    Ok(block) => debug!("💾 Checkpoint saved at block {}", block),
+                    Err(e) => warn!("⚠️ Failed to save checkpoint: {:#}", e),
+                }
+            }
+        }))
+    }
+
+    /// Last block whose analysis completed
+    pub fn last_processed_block(&self) -> u64 {
+        self.last_block.load(Ordering::Relaxed)
+    }
+
+    /// Fetches and analyzes a single block without advancing the scan position.
+    /// The returned block and receipts can be saved as a replay file.
+    pub async fn inspect_block(&self, number: u64) -> Result<(ReplayBlock, BlockAnalysis)> {
+        let (block, receipts) = self.fetch_block_data(number).await?;
+        self.retry_queue.discard_progress(number);
+
+        let analysis = self.storage_drift_detector
+            .analyze_block_with_deltas(&block, receipts.clone())
+            .await?;
+        Ok((ReplayBlock { block, receipts }, analysis))
+    }
+
+    /// Storage layout the drift detector uses for a contract
+    pub async fn storage_layout(&self, contract: Address) -> StorageLayout {
+        self.storage_drift_detector.get_storage_layout(contract).await
+    }
+
+
+    /// Pools watched by this scanner
+    pub fn pool_manager(&self) -> Arc<PoolManager> {
+        self.pool_manager.clone()
+    }
+
+    /// Adds pools to monitor for arbitrage opportunities and fetches their reserves
+    pub async fn add_pools(&self, pools: Vec<PoolState>) -> Result<usize> {
+        let mut added = 0;
+        for pool in pools {
+            let address = pool.address;
+            if self.pool_manager.add_pool(pool) {
+                self.monitored_contracts.add(address, false).await;
+                let pool = self.read_state(self.pool_manager.refresh(self.fallback_provider.as_ref(), address)).await?;
+                self.arbitrage_detector.sync_pool(address);
+                if self.config.strategies().arbitrage {
+                    self.load_v3_snapshot(&pool).await?;
+                }
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    /// Stops watching a pool, returns `false` if it was not watched
+    pub async fn remove_pool(&self, address: Address) -> bool {
+        if self.pool_manager.remove_pool(address).is_none() {
+            return false;
+        }
+        self.arbitrage_detector.remove_pool(address);
+        self.monitored_contracts.remove(address).await;
+        true
+    }
+
+    /// Resolves and discovers pools as configured, then fetches every pool's reserves.
+    /// `run_cycle` does this itself, one-off commands call it before processing blocks.
+    pub async fn initialize_pools(&self) -> Result<()> {
+        let provider = self.fallback_provider.as_ref();
+        let chain = self.config.chain();
+        let mut added = Vec::new();
+
+        if self.config.resolve_top_pairs() {
+            added.extend(self.pool_manager.resolve_pairs(provider, chain.addresses.top_pairs()).await?);
+        }
+        if let Some(from_block) = self.config.discover_pools_from_block() {
+            let latest = provider.get_block_number().await
+                .context("Failed to fetch block number")?
+                .as_u64();
+            let known_tokens: HashSet<Address> = chain.addresses.tokens().iter().map(|t| t.address).collect();
+            added.extend(self.pool_manager.discover_from_factories(provider, from_block, latest, Some(&known_tokens)).await?);
+        }
+        for pool in &added {
+            self.monitored_contracts.add(pool.address, false).await;
+        }
+
+        let http_endpoint = self.config.fallback_rpc_url();
+        if self.breakers.allow(http_endpoint, RpcOperation::StorageReads).await {
+            let failed = self.pool_manager.refresh_all(provider).await;
+            for (address, e) in &failed {
+                warn!("⚠️ Failed to fetch state of pool {:?}: {:#}", address, e);
+            }
+            // One outcome for the batch: only when every read failed is the endpoint to blame
+            match failed.first() {
+                Some((_, e)) if failed.len() == self.pool_manager.len() => {
+                    self.breakers.record_error(http_endpoint, RpcOperation::StorageReads, e).await;
+                }
+                _ => self.breakers.record_success(http_endpoint, RpcOperation::StorageReads).await,
+            }
+        } else {
+            warn!("⚠️ Storage read breaker open, pools keep their address book state");
+        }
+        self.arbitrage_detector.sync_graph();
+        info!("🏊 Watching {} pools ({} added at startup), {} priced in the token graph",
+            self.pool_manager.len(), added.len(), self.arbitrage_detector.graph().len());
+
+        if self.config.strategies().arbitrage {
+            for pool in self.pool_manager.all() {
+                if let Err(e) = self.load_v3_snapshot(&pool).await {
+                    warn!("⚠️ Failed to load tick data of pool {:?}: {:#}", pool.address, e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads the tick data the arbitrage detector quotes a V3 pool with, V2 pools need none
+    async fn load_v3_snapshot(&self, pool: &PoolState) -> Result<()> {
+        if pool.kind != DexKind::UniswapV3 {
+            return Ok(());
+        }
+        let snapshot = self.read_state(V3PoolSnapshot::fetch(self.fallback_provider.as_ref(), pool, const_and_addr::V3_TICK_WORD_RADIUS)).await?;
+        self.arbitrage_detector.set_v3_snapshot(snapshot);
+        Ok(())
+    }
 
-    /// Adds pools to monitor for arbitrage opportunities 
+    /// Reloads the tick data of V3 pools whose positions changed. A pool whose reload
+    /// fails loses its tick data, so it is skipped instead of quoted on stale ticks.
+    async fn reload_tick_data(&self, pools: HashSet<Address>) {
+        if !self.config.strategies().arbitrage {
+            return;
+        }
+        for pool in pools.into_iter().filter_map(|address| self.pool_manager.get(address)) {
+            if let Err(e) = self.load_v3_snapshot(&pool).await {
+                warn!("⚠️ Failed to reload tick data of pool {:?}, not quoting it: {:#}", pool.address, e);
+                self.arbitrage_detector.clear_v3_snapshot(pool.address);
+            }
+        }
+    }
+
+    /// Runs a pool state read behind the storage read breaker of the HTTP endpoint
+    async fn read_state<T>(&self, read: impl Future<Output = Result<T>>) -> Result<T> {
+        self.http_call(RpcOperation::StorageReads, read).await
+    }
+
+    /// Runs a call on the HTTP endpoint only if its breaker for `operation` lets it
+    /// through, and records the outcome
+    async fn http_call<T, E>(&self, operation: RpcOperation, call: impl Future<Output = Result<T, E>>) -> Result<T>
+    where
+        E: Into<anyhow::Error>,
+    {
+        let http_endpoint = self.config.fallback_rpc_url();
+        if !self.breakers.allow(http_endpoint, operation).await {
+            return Err(anyhow!("{:?} breaker open on {}", operation, redact_endpoint(http_endpoint)));
+        }
+        self.breakers.observe(http_endpoint, operation, call.await).await
+    }
+
+    /// Looks for arbitrage around pools whose price just changed
+    async fn detect_arbitrage(&self, block_number: u64, changed: HashSet<Address>) {
+        if !self.config.strategies().arbitrage || changed.is_empty() {
+            return;
+        }
+        // Block headers keep the base fee current, the fee history fills gaps. Log mode
+        // can get a block's logs before its header, or no header at all over HTTP.
+        let gas_oracle = self.arbitrage_detector.gas_oracle();
+        if gas_oracle.is_stale(block_number) && gas_oracle.estimate().is_some() {
+            match self.http_call(RpcOperation::BlockFetch, self.fallback_provider.get_block(block_number)).await {
+                Ok(Some(block)) => {
+                    gas_oracle.observe_block(&block);
+                }
+                Ok(None) => debug!("Block {} header not available yet for the gas estimate", block_number),
+                Err(e) => warn!("⚠️ Failed to fetch block {} header for the gas estimate: {:#}", block_number, e),
+            }
+        }
+        if gas_oracle.is_stale(block_number)
+            && let Err(e) = gas_oracle.refresh(self.fallback_provider.as_ref()).await
+        {
+            warn!("⚠️ Failed to refresh gas estimate, using the last one: {:#}", e);
+        }
+
+        let opportunities = self.arbitrage_detector.check_pools(changed, block_number);
+        let opportunities = self.simulate_opportunities(opportunities).await;
+        if opportunities.is_empty() {
+            return;
+        }
+        for opportunity in &opportunities {
+            info!("💰 Arbitrage in block {}: {} hops, buy on {:?}, sell on {:?}, {} in -> {} profit, {} after gas",
+                block_number, opportunity.hops(), opportunity.buy_pool, opportunity.sell_pool,
+                opportunity.amount_in, opportunity.profit, opportunity.net_profit);
+        }
+
+        let mut recent = self.recent_opportunities.write().await;
+        recent.extend(opportunities);
+        if recent.len() > const_and_addr::MAX_RECENT_OPPORTUNITIES {
+            let excess = recent.len() - const_and_addr::MAX_RECENT_OPPORTUNITIES;
+            recent.drain(0..excess);
+        }
+    }
+
+    /// Keeps the opportunities whose bundle succeeds in simulation and still clears the
+    /// profit threshold after the simulated gas, all of them without a simulator. Bundles
+    /// are sent from the signer, or a stand-in account when read-only.
+    async fn simulate_opportunities(&self, opportunities: Vec<ArbitrageOpportunity>) -> Vec<ArbitrageOpportunity> {
+        let Some(simulator) = &self.simulator else {
+            return opportunities;
+        };
+        let searcher = self.signer_address().unwrap_or(const_and_addr::SIMULATION_SEARCHER);
+        let endpoint = simulator.endpoint();
+        let mut validated = Vec::with_capacity(opportunities.len());
+        for mut opportunity in opportunities {
+            let simulation = match Bundle::from_opportunity(&opportunity, searcher, self.config.chain(), &self.pool_manager) {
+                // Unvalidated opportunities are not kept, so an open breaker drops them too
+                Ok(_) if !self.breakers.allow(endpoint, RpcOperation::Tracing).await => {
+                    Err(anyhow!("Tracing breaker open on {}", redact_endpoint(endpoint)))
+                }
+                Ok(bundle) => {
+                    let result = simulator.simulate(&bundle, opportunity.block_number).await;
+                    self.breakers.observe(endpoint, RpcOperation::Tracing, result).await
+                }
+                Err(e) => Err(e),
+            };
+            match simulation {
+                Ok(result) if result.success() => {
+                    let profit = result.balance_delta(opportunity.profit_token) - I256::from_raw(opportunity.amount_in);
+                    if !self.arbitrage_detector.profitable_after_gas(profit, result.gas_used()) {
+                        warn!("🧪 Arbitrage via {:?} made {} using {} gas in simulation, below threshold",
+                            opportunity.buy_pool, profit, result.gas_used());
+                        continue;
+                    }
+                    opportunity.simulated_profit = Some(profit);
+                    opportunity.simulated_gas = Some(result.gas_used());
+                    validated.push(opportunity);
+                }
+                Ok(result) => warn!("🧪 Arbitrage via {:?} reverted in simulation: {}",
+                    opportunity.buy_pool, result.revert_reason().unwrap_or("no reason")),
+                Err(e) => warn!("⚠️ Failed to simulate arbitrage via {:?}: {:#}", opportunity.buy_pool, e),
+            }
+        }
+        validated
+    }
+
+    /// Pending swaps through watched routers and pools, highest gas price first. Empty
+    /// unless the mempool strategy is enabled.
+    pub fn pending_swaps(&self) -> Vec<PendingSwap> {
+        self.mempool_watcher.as_ref().map(|watcher| watcher.pending()).unwrap_or_default()
+    }
+
+    /// Arbitrage opportunities found recently, newest last
+    pub async fn recent_opportunities(&self) -> Vec<ArbitrageOpportunity> {
+        self.recent_opportunities.read().await.clone()
+    }
+
+    /// Pools among the drift events' contracts plus pools updated from their own logs.
+    /// Reserve deltas reprice the arbitrage graph's edges along the way.
+    fn changed_pools(&self, analysis: &BlockAnalysis, mut updated_pools: HashSet<Address>) -> HashSet<Address> {
+        let drift_events = &analysis.drift_events;
+        updated_pools.extend(self.arbitrage_detector.apply_deltas(&analysis.deltas));
+        updated_pools.extend(
+            drift_events.iter()
+                .map(|event| event.contract)
+                .filter(|contract| self.pool_manager.is_monitored_pool(*contract)),
+        );
+        updated_pools
+    }
+
+    /// Runs the scanner until a shutdown signal arrives
     pub async fn run_cycle(
         &self, 
         mut shutdown_rx: tokio::sync::mpsc::Receiver<()>,
 ) -> Result<()> {
         info!("Starting MEV Scanner main loop .................");
 
-        // Start mempool monitoring task 
-        // let mempool_task =self.start_mempool_monitoring();
+        if let Err(e) = self.initialize_pools().await {
+            warn!("⚠️ Pool initialization failed, continuing with the pools known so far: {:#}", e);
+        }
+
+        let mempool_task = self.start_mempool_monitoring();
 
         // Start storage drift monitoring task 
         let drift_task = self.start_drift_monitoring();
+        let checkpoint_task = self.start_checkpointing();
+        // Polled alongside the head loops below, so a slow retry never holds up new heads
+        let mut retries = pin!(self.retry_loop());
 
         loop{
             tokio::select!{
+                _ = retries.as_mut() => {}
                 _ = shutdown_rx.recv() => {
                     info!("🛑 Shutdown signal received. Exiting run cycle loop....");
-                    // mempool_task.abort();
+                    if let Some(task) = mempool_task {
+                        task.abort();
+                    }
+                    if let Some(task) = checkpoint_task {
+                        task.abort();
+                    }
+                    if let Err(e) = self.save_state().await {
+                        error!("❌ Failed to save state on shutdown: {:#}", e);
+                    }
                     return Ok(());
                 }
                 _ = async {
-                    if self.circuit_breaker.is_tripped().await {
-                        warn!("⚠️ Circuit breaker tripped, cooling down.....");
-                        sleep(const_and_addr::COOL_DOWN_PERIOD).await;
-                        return;
+                    let ws_connected = self.connection_state.lock().await.ws_connected;
+                    let ws_allowed = ws_connected
+                        && self.breakers.allow(&self.ws_endpoint, RpcOperation::Subscribe).await;
+                    if ws_connected && !ws_allowed {
+                        // Let the regular reconnect cadence bring WS back once the breaker half-opens
+                        warn!("⚠️ WebSocket subscribe breaker open, using HTTP polling.....");
+                        self.connection_state.lock().await.ws_connected = false;
                     }
-                    if self.connection_state.lock().await.ws_connected{
-                        match self.process_ws_blocks().await{
+
+                    if ws_allowed {
+                        let ws_result = match self.config.scan_mode() {
+                            ScanMode::FullReceipts => self.process_ws_blocks().await,
+                            ScanMode::LogFilter => self.process_ws_logs().await,
+                        };
+                        match ws_result {
                             Ok(_) => {
                                 let mut state = self.connection_state.lock().await;
                                 state.last_success= Instant::now();
                                 state.consecutive_errors = 0;
-                // reset on success to clear consecutive error streak
-                self.circuit_breaker.reset().await;
+                                drop(state);
+                                self.breakers.record_success(&self.ws_endpoint, RpcOperation::Subscribe).await;
                             }
                             Err(e) => {
                                 warn!("❌ WS processing error: {:?}",e);
                                 self.connection_state.lock().await.ws_connected = false;
-                                self.circuit_breaker.trip().await;
+                                self.breakers.record_error(&self.ws_endpoint, RpcOperation::Subscribe, &e).await;
                             }
                         }
                         
                     } else {
+                        let http_endpoint = self.config.fallback_rpc_url();
+                        if !self.breakers.allow(http_endpoint, RpcOperation::Polling).await {
+                            warn!("⚠️ HTTP polling breaker open, cooling down.....");
+                            sleep(RECONNECT_DELAY).await;
+                            return;
+                        }
                         match self.process_http_polling().await {
                             Ok(_) => {
                                 self.connection_state.lock().await.last_success = Instant::now();
+                                self.breakers.record_success(http_endpoint, RpcOperation::Polling).await;
                                 self.try_reconnect_ws().await;
                             }
                             Err(e) => {
                                 error! ("HTTP process error: {:?}", e);
-                                self.circuit_breaker.trip().await;
+                                self.breakers.record_error(http_endpoint, RpcOperation::Polling, &e).await;
                                 sleep(RECONNECT_DELAY).await;
                             }
                         }
@@ -258,25 +748,25 @@ impl MevScanner {
         Ok(())
     }
 
-    // fn start_mempool_monitoring(&self) -> tokio::task::JoinHandle<()>{
-    //     let mempool_watcher = self.mempool_watcher.clone();
-    //     let arbitrage_detector = self.arbitrage_detector.clone();
-
-    //     tokio::spawn(async move{
-    //         loop {
-    //             if let Ok(mut watcher) = mempool_watcher.try_lock() {
-    //                 if let Ok(pending_txs) = watcher.get_pending_transactions().await {
-    //                     for tx in pending_txs {
-    //                         if let Ok(opportunities) = arbitrage_detector.analyze_transaction(&tx).await {
-    //                             info!("📊 Mempool arbitrage opportunity");
-    //                             self.handle_opportunities(opportunities).await;
-    //                         }
-    //                     }
-    //                 }
-    //             }
-    //         }
-    //     })
-    // }
+    /// Follows pending transactions on a WebSocket connection of its own, so block
+    /// subscriptions and their reconnects are not held up by mempool traffic
+    fn start_mempool_monitoring(&self) -> Option<tokio::task::JoinHandle<()>> {
+        let watcher = self.mempool_watcher.clone()?;
+        let ws_endpoint = self.ws_endpoint.clone();
+
+        Some(tokio::spawn(async move {
+            loop {
+                let result = match Provider::<Ws>::connect(&ws_endpoint).await {
+                    Ok(provider) => watcher.watch(&provider).await,
+                    Err(e) => Err(anyhow!("Failed to connect mempool WebSocket: {}", e)),
+                };
+                if let Err(e) = result {
+                    warn!("⚠️ Mempool monitoring interrupted, reconnecting: {:#}", e);
+                }
+                sleep(RECONNECT_DELAY).await;
+            }
+        }))
+    }
 
     fn start_drift_monitoring(&self) -> tokio::task::JoinHandle<()>{
         let drift_detector = self.storage_drift_detector.clone();
@@ -323,15 +813,33 @@ impl MevScanner {
             .context("Failed to subscribe to blocks")?;
         info!("📡 WebSocket block subscription established");
 
-        while let Some(block) = stream.next().await{
-            self.update_connection_success().await;
+        let mut watchdog = WsWatchdog::new(self.expected_block_time, WS_STALE_HEAD_MULTIPLIER, MAX_WS_HEAD_LAG);
+        let mut ping = tokio::time::interval_at(tokio::time::Instant::now() + WS_PING_INTERVAL, WS_PING_INTERVAL);
 
-            if let Err(e) = self.process_block_immediately(block).await {
-                error!("❌ Block processing error: {:?}", e);
-                self.handle_connection_error().await;
+        loop {
+            tokio::select! {
+                next = stream.next() => {
+                    let Some(block) = next else { break };
+                    if let Some(number) = block.number {
+                        watchdog.record_head(number.as_u64());
+                    }
+                    self.update_connection_success().await;
 
-                if self.connection_state.lock().await.consecutive_errors >= MAX_CONSECUTIVE_ERRORS {
-                    return Err(e);
+                    if let Err(e) = self.process_block_immediately(block).await {
+                        error!("❌ Block processing error: {:?}", e);
+                        self.handle_connection_error().await;
+
+                        if self.connection_state.lock().await.consecutive_errors >= MAX_CONSECUTIVE_ERRORS {
+                            return Err(e);
+                        }
+                    }
+                }
+                _ = sleep(watchdog.time_until_stale()) => {
+                    warn!("🐶 No new head for {}s, WebSocket subscription considered stale", watchdog.head_timeout().as_secs());
+                    return Err(anyhow!("WebSocket subscription stale: no new head within {:?}", watchdog.head_timeout()));
+                }
+                _ = ping.tick() => {
+                    self.check_ws_liveness(&provider, &watchdog).await?;
                 }
             }
         }
@@ -340,25 +848,250 @@ impl MevScanner {
         Err(anyhow!("WebSocket stream terminated"))
     }
 
+    /// Log-filter mode over WebSocket: subscribes to logs of monitored contracts and
+    /// tracked topics, and to new heads when some contracts still need full receipts or
+    /// the arbitrage strategy prices gas from them.
+    /// Returns `Ok` when the monitored set changes so the caller resubscribes.
+    pub async fn process_ws_logs(&self) -> Result<()> {
+        let provider_opt = self.primary_provider.lock().await.clone();
+        let Some(provider) = provider_opt else {
+            return Err(anyhow!("WebSocket provider not available"));
+        };
+
+        // Listen for changes before reading the set, so none slips in between
+        let mut contracts_changed = pin!(self.monitored_contracts.changed());
+        contracts_changed.as_mut().enable();
+        let filter = log_filter::build_log_filter(
+            self.monitored_contracts.log_addresses().await,
+            self.config.chain().events.tracked(),
+        );
+        let trace_contracts = self.monitored_contracts.trace_addresses().await;
+
+        let mut logs = provider
+            .subscribe_logs(&filter)
+            .await
+            .context("Failed to subscribe to logs")?;
+        let mut heads = if trace_contracts.is_empty() && !self.config.strategies().arbitrage {
+            None
+        } else {
+            Some(provider.subscribe_blocks().await.context("Failed to subscribe to blocks")?)
+        };
+        info!("📡 WebSocket log subscription established ({} trace contracts on full receipts)", trace_contracts.len());
+
+        // Quiet contracts can go many blocks without a log, so only the ping/lag check applies here
+        let watchdog = WsWatchdog::new(self.expected_block_time, WS_STALE_HEAD_MULTIPLIER, MAX_WS_HEAD_LAG);
+        let mut ping = tokio::time::interval_at(tokio::time::Instant::now() + WS_PING_INTERVAL, WS_PING_INTERVAL);
+        let mut batcher = LogBatcher::default();
+
+        loop {
+            tokio::select! {
+                next = logs.next() => {
+                    let Some(log) = next else { break };
+                    self.update_connection_success().await;
+                    if let Some((block_number, batch)) = batcher.push(log) {
+                        self.process_log_batch_or_disconnect(block_number, batch).await?;
+                    }
+                }
+                _ = sleep(LOG_BATCH_IDLE_FLUSH), if batcher.has_pending() => {
+                    if let Some((block_number, batch)) = batcher.flush() {
+                        self.process_log_batch_or_disconnect(block_number, batch).await?;
+                    }
+                }
+                head = async { heads.as_mut()?.next().await }, if heads.is_some() => {
+                    let Some(block) = head else { break };
+                    let Some(number) = block.number else { continue };
+                    self.arbitrage_detector.gas_oracle().observe_block(&block);
+                    if !trace_contracts.is_empty()
+                        && let Err(e) = self.process_trace_contracts(number.as_u64(), &trace_contracts).await
+                    {
+                        error!("❌ Trace contract processing failed for block {}: {:?}", number, e);
+                        self.retry_queue.record_failure(number.as_u64(), format!("{:?}", e)).await;
+                    }
+                }
+                _ = contracts_changed.as_mut() => {
+                    info!("🔃 Monitored contracts changed, rebuilding log subscription");
+                    if let Some((block_number, batch)) = batcher.flush() {
+                        self.process_log_batch_or_disconnect(block_number, batch).await?;
+                    }
+                    return Ok(());
+                }
+                _ = ping.tick() => {
+                    self.check_ws_liveness(&provider, &watchdog).await?;
+                }
+            }
+        }
+
+        warn!("⚠️ WebSocket log stream ended");
+        Err(anyhow!("WebSocket log stream terminated"))
+    }
+
+    async fn process_log_batch_or_disconnect(&self, block_number: u64, logs: Vec<Log>) -> Result<()> {
+        if let Err(e) = self.process_log_batch(block_number, logs).await {
+            error!("❌ Log batch processing error for block {}: {:?}", block_number, e);
+            // The retry goes over the block's full receipts, which hold the batch's logs
+            self.retry_queue.record_failure(block_number, format!("{:?}", e)).await;
+            self.handle_connection_error().await;
+
+            if self.connection_state.lock().await.consecutive_errors >= MAX_CONSECUTIVE_ERRORS {
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Feeds one block's filtered logs straight into the drift detector
+    async fn process_log_batch(&self, block_number: u64, logs: Vec<Log>) -> Result<()> {
+        let total = logs.len();
+        let logs: Vec<Log> = logs
+            .into_iter()
+            .filter(|log| !Self::log_seen_key(log).is_some_and(|(hash, index)| self.seen.seen_log(hash, index)))
+            .collect();
+        if logs.len() < total {
+            debug!("⏭️ Skipping {} already analyzed logs in block {}", total - logs.len(), block_number);
+        }
+
+        // Without the block's transaction list, the swaps behind its logs are the included ones
+        let updated_pools = self.pool_manager.apply_logs(&logs);
+        self.reload_tick_data(self.pool_manager.position_changes(&logs)).await;
+        let included: Vec<H256> = logs.iter().filter_map(|log| log.transaction_hash).collect();
+        self.project_pending_state(block_number, &included).await;
+        let analysis = if self.config.strategies().storage_drift {
+            self.storage_drift_detector.analyze_logs_with_deltas(block_number, &logs).await?
+        } else {
+            BlockAnalysis::empty(block_number)
+        };
+        for (hash, index) in logs.iter().filter_map(Self::log_seen_key) {
+            self.seen.mark_log(hash, index);
+        }
+        let changed = self.changed_pools(&analysis, updated_pools);
+        self.detect_arbitrage(block_number, changed).await;
+        self.record_drift_events(block_number, analysis.drift_events).await;
+        self.last_block.fetch_max(block_number, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Full-receipt fallback for contracts whose analysis needs trace data
+    async fn process_trace_contracts(&self, block_number: u64, trace_contracts: &HashSet<Address>) -> Result<()> {
+        let (block, receipts) = self.fetch_block_data(block_number).await?;
+        let receipts: Vec<TransactionReceipt> = receipts
+            .into_iter()
+            .filter(|receipt| receipt.to.is_some_and(|to| trace_contracts.contains(&to)))
+            .collect();
+        self.retry_queue.discard_progress(block_number);
+
+        self.analyze_unseen_receipts(&block, block_number, receipts).await
+    }
+
+    /// Identity of a log for deduplication, `None` for pending logs
+    fn log_seen_key(log: &Log) -> Option<(H256, u64)> {
+        Some((log.block_hash?, log.log_index?.as_u64()))
+    }
+
+    /// Log-filter mode over HTTP: `eth_getLogs` in chunks of the range, grouped per block.
+    /// A chunk the node rejects is split until single blocks fail, which then go to the
+    /// retry queue like any other failed block.
+    async fn backfill_logs(&self, from: u64, to: u64) -> u64 {
+        let addresses = self.monitored_contracts.log_addresses().await;
+        let trace_contracts = self.monitored_contracts.trace_addresses().await;
+        let http_endpoint = self.config.fallback_rpc_url();
+
+        let mut processed = 0;
+        let mut range = const_and_addr::LOG_FILTER_BLOCK_RANGE;
+        let mut start = from;
+        while start <= to {
+            // Splitting is no use while the breaker holds every request back
+            if self.breakers.get(http_endpoint, RpcOperation::Logs).state().await == CircuitState::Open {
+                warn!("⚠️ Log breaker open, blocks {}..={} wait for the next poll", start, to);
+                return processed;
+            }
+            let end = to.min(start + range - 1);
+            let filter = log_filter::build_log_filter(addresses.clone(), self.config.chain().events.tracked())
+                .from_block(start)
+                .to_block(end);
+            let mut grouped = match self.http_call(RpcOperation::Logs, self.fallback_provider.get_logs(&filter)).await {
+                Ok(logs) => log_filter::group_logs_by_block(logs),
+                Err(e) if end > start => {
+                    range = (end - start).div_ceil(2);
+                    warn!("⚠️ HTTP log range {}..={} fetch failed, retrying {} blocks at a time: {:#}", start, end, range, e);
+                    continue;
+                }
+                Err(e) => {
+                    error!("HTTP block {} log fetch failed: {:?}", start, e);
+                    self.retry_queue.record_failure(start, format!("{:?}", e)).await;
+                    self.last_block.fetch_max(start, Ordering::Relaxed);
+                    start += 1;
+                    continue;
+                }
+            };
+
+            for number in start..=end {
+                if !trace_contracts.is_empty()
+                    && let Err(e) = self.process_trace_contracts(number, &trace_contracts).await
+                {
+                    error!("HTTP block {} trace contract processing failed: {:?}", number, e);
+                    self.retry_queue.record_failure(number, format!("{:?}", e)).await;
+                } else if let Some(logs) = grouped.remove(&number)
+                    && let Err(e) = self.process_log_batch(number, logs).await
+                {
+                    error!("HTTP block {} log processing failed: {:?}", number, e);
+                    self.retry_queue.record_failure(number, format!("{:?}", e)).await;
+                } else {
+                    processed += 1;
+                }
+                // The scan moves past failed blocks, the retry queue owns them now
+                self.last_block.fetch_max(number, Ordering::Relaxed);
+            }
+            start = end + 1;
+        }
+
+        processed
+    }
+
+    /// Adds a contract to monitor. Contracts that need trace data are always processed
+    /// from full receipts, even in log-filter mode.
+    pub async fn monitor_contract(&self, address: Address, needs_traces: bool) {
+        self.monitored_contracts.add(address, needs_traces).await;
+    }
+
+    pub async fn unmonitor_contract(&self, address: Address) -> bool {
+        self.monitored_contracts.remove(address).await
+    }
+
+    /// Pings the WS node and cross-checks its head against the HTTP provider
+    async fn check_ws_liveness(&self, provider: &Provider<Ws>, watchdog: &WsWatchdog) -> Result<()> {
+        let ws_head = timeout(WS_TIMEOUT, provider.get_block_number())
+            .await
+            .map_err(|_| anyhow!("WebSocket ping timed out after {}s", WS_TIMEOUT.as_secs()))?
+            .context("WebSocket ping failed")?
+            .as_u64();
+
+        match self.fallback_provider.get_block_number().await {
+            Ok(http_head) => {
+                debug!("🐶 WS head {} / HTTP head {} / last streamed {:?}", ws_head, http_head.as_u64(), watchdog.last_head_number());
+                watchdog.check_lag(ws_head, http_head.as_u64())
+            }
+            Err(e) => {
+                // An unhealthy HTTP provider says nothing about the WS node
+                debug!("HTTP head check skipped: {}", e);
+                Ok(())
+            }
+        }
+    }
+
     async fn process_http_polling(&self) -> Result<()> {
         info!("📊 Falling back to HTTP polling mode");
 
         loop {  
             tokio::select!{
-                _ = sleep(HTTP_POLL_INTERVAL) => {
+                _ = sleep(self.config.http_poll_interval()) => {
                     let latest_block = self.fallback_provider.get_block_number().await?.as_u64();
                     let last_processed = self.last_block.load(Ordering::Relaxed);
 
                     if latest_block > last_processed {
-                        // Process all missed blocks individually for maximum speed 
-                        for block_num in last_processed+1..=latest_block {
-                            if let Err(e) = self.process_single_block_by_number(block_num).await {
-                                error!("HTTP block {} processing failed: {:?}", block_num, e);
-                                continue;
-                            }
-                            self.last_block.store(block_num, Ordering::Relaxed);
-                        }
+                        // Fetch missed blocks ahead in parallel, analyze them in order
+                        self.backfill(last_processed + 1, latest_block).await;
                     }
+
                     // Try to reconnect WebSocket periodically
                     if self.should_attempt_ws_reconnect().await {
                         info!("🔃 Time to attempt WebSocket reconnect, exiting HTTP fallback");
@@ -379,20 +1112,28 @@ impl MevScanner {
 
     async fn process_block_immediately(&self, block: Block<H256>) -> Result<()>{
         let start_time = Instant::now();
+        let processing_timeout = self.config.block_processing_timeout();
         if let Some(number) = block.number.map(|n| n.as_u64()) {
             debug!("⚡️ Processing block {} immediately", number);
 
-            match timeout(BLOCK_PROCESSING_TIMEOUT, self.process_single_block(block.clone())).await {
+            match timeout(processing_timeout, self.process_single_block(block.clone())).await {
                 Ok(Ok(())) => {
                     info!("✅ Block {} processed ({}ms)", number, start_time.elapsed().as_millis());
                     self.last_block.store(number,Ordering::Relaxed);
+                    self.retry_queue.complete(number).await;
+                    // Processed blocks are what closes a half-open breaker
+                    self.breakers.record_success(&self.ws_endpoint, RpcOperation::Subscribe).await;
                 }
                 Ok(Err(e))=> {
                     error!("❌ Block {} processing failed: {:?}", number, e);
+                    self.retry_queue.record_failure(number, format!("{:?}", e)).await;
                     return Err(e);
                 }
                 Err(_) => {
-                    warn!("⏱️ Block {} processing timed out after {}ms", number, BLOCK_PROCESSING_TIMEOUT.as_millis());
+                    warn!("⏱️ Block {} processing timed out after {}ms", number, processing_timeout.as_millis());
+                    self.retry_queue
+                        .record_failure(number, format!("timed out after {}ms", processing_timeout.as_millis()))
+                        .await;
                 }
             }
         }
@@ -400,31 +1141,226 @@ impl MevScanner {
         Ok(())
     }
 
+    /// Processes the inclusive block range `from..=to` in the configured scan mode.
+    /// Returns the number of blocks that were fully processed.
+    pub async fn backfill(&self, from: u64, to: u64) -> u64 {
+        match self.config.scan_mode() {
+            ScanMode::FullReceipts => self.backfill_receipts(from, to).await,
+            ScanMode::LogFilter => self.backfill_logs(from, to).await,
+        }
+    }
+
+    /// Processes the inclusive block range `from..=to` as a pipeline.
+    ///
+    /// Blocks and receipts for up to `block_pipeline_depth` blocks are fetched ahead in
+    /// parallel, while drift analysis applies them to the state cache in strict block order.
+    async fn backfill_receipts(&self, from: u64, to: u64) -> u64 {
+        let depth = self.config.block_pipeline_depth().max(1);
+        let processing_timeout = self.config.block_processing_timeout();
+        let mut processed = 0;
+
+        let mut fetched = stream::iter(from..=to)
+            .map(|number| async move {
+                (number, timeout(processing_timeout, self.fetch_block_data(number)).await)
+            })
+            .buffered(depth);
+
+        while let Some((number, fetch_result)) = fetched.next().await {
+            match fetch_result {
+                Ok(Ok((block, receipts))) => {
+                    if let Err(e) = self.analyze_block_data(&block, receipts).await {
+                        error!("HTTP block {} processing failed: {:?}", number, e);
+                        self.retry_queue.record_failure(number, format!("{:?}", e)).await;
+                        continue;
+                    }
+                    self.last_block.store(number, Ordering::Relaxed);
+                    self.retry_queue.complete(number).await;
+                    processed += 1;
+                }
+                // The scan moves past failed blocks, so each one is retried or dead-lettered
+                Ok(Err(e)) => {
+                    error!("HTTP block {} processing failed: {:?}", number, e);
+                    self.retry_queue.record_failure(number, format!("{:?}", e)).await;
+                }
+                Err(_) => {
+                    warn!("⏱️ Block {} fetch timed out after {}ms", number, processing_timeout.as_millis());
+                    self.retry_queue
+                        .record_failure(number, format!("timed out after {}ms", processing_timeout.as_millis()))
+                        .await;
+                }
+            }
+        }
+
+        processed
+    }
+
+    /// Retries due blocks every `RETRY_POLL_INTERVAL`, whichever scan mode is running
+    async fn retry_loop(&self) {
+        let mut interval = tokio::time::interval(RETRY_POLL_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            self.retry_due_blocks().await;
+        }
+    }
+
+    /// Retries timed-out blocks whose backoff has elapsed, reusing any receipts
+    /// fetched by earlier attempts
+    async fn retry_due_blocks(&self) {
+        let processing_timeout = self.config.block_processing_timeout();
+        for number in self.retry_queue.take_due().await {
+            let start_time = Instant::now();
+            debug!("🔁 Retrying block {}", number);
+
+            match timeout(processing_timeout, self.process_single_block_by_number(number)).await {
+                Ok(Ok(())) => {
+                    info!("✅ Block {} processed on retry ({}ms)", number, start_time.elapsed().as_millis());
+                    self.retry_queue.complete(number).await;
+                }
+                Ok(Err(e)) => {
+                    self.retry_queue.record_failure(number, format!("{:?}", e)).await;
+                }
+                Err(_) => {
+                    self.retry_queue
+                        .record_failure(number, format!("timed out after {}ms", processing_timeout.as_millis()))
+                        .await;
+                }
+            }
+        }
+    }
+
+    /// Stream of circuit breaker state changes across all endpoints and operations,
+    /// e.g. to pause trade submission while RPC is degraded
+    pub fn subscribe_breaker_events(&self) -> tokio::sync::broadcast::Receiver<BreakerTransition> {
+        self.breakers.subscribe()
+    }
+
+    /// Registers a callback for circuit breaker state changes (runs inline, must not block)
+    pub fn on_breaker_transition<F>(&self, listener: F)
+    where
+        F: Fn(&BreakerTransition) + Send + Sync + 'static,
+    {
+        self.breakers.on_transition(listener);
+    }
+
+    /// State of every circuit breaker created so far
+    pub async fn breaker_status(&self) -> Vec<BreakerStatus> {
+        self.breakers.snapshot().await
+    }
+
+    /// Blocks that exhausted their retry attempts
+    pub async fn dead_letter_blocks(&self) -> Vec<DeadLetterBlock> {
+        self.retry_queue.dead_letters().await
+    }
+
+    /// Blocks currently waiting for another processing attempt
+    pub async fn pending_retry_blocks(&self) -> Vec<u64> {
+        self.retry_queue.pending_blocks().await
+    }
+
     async fn process_single_block_by_number(&self, number: u64) -> Result<()> {
-        let block = self.fallback_provider
-            .get_block(number)
+        let (block, receipts) = self.fetch_block_data(number).await?;
+        self.analyze_block_data(&block, receipts).await
+    }
+
+    /// Fetch stage: the block header plus all of its receipts
+    async fn fetch_block_data(&self, number: u64) -> Result<(Block<H256>, Vec<TransactionReceipt>)> {
+        let block = self.http_call(RpcOperation::BlockFetch, self.fallback_provider.get_block(number))
             .await?
             .ok_or_else(|| anyhow!("Block {} not found", number))?;
-        self.process_single_block(block).await
+        let receipts = self.get_block_receipts(&block).await?;
+        Ok((block, receipts))
     }
 
-
     async fn process_single_block(&self, block: Block<H256>) -> Result<()> {
-        let _start_time = Instant::now();
+        // 1. Get Transaction receipts for storage analysis
+        let receipts = self.get_block_receipts(&block).await?;
+
+        self.analyze_block_data(&block, receipts).await
+    }
+
+    /// Analysis stage: must be called in block order since it advances the state cache
+    async fn analyze_block_data(&self, block: &Block<H256>, receipts: Vec<TransactionReceipt>) -> Result<()> {
         let block_number = block.number
             .ok_or_else(|| anyhow!("Block missing number!!!!"))?
             .as_u64();
 
         debug!("🔍 Processing block {} with draft detection", block_number);
 
-        // 1. Get Transaction receipts for storage analysis
-        let receipts = self.get_block_receipts(&block).await?;
+        self.analyze_unseen_receipts(block, block_number, receipts).await
+    }
+
+    /// Runs drift analysis on the receipts not analyzed before. Replayed blocks (WS/HTTP
+    /// fallback, reorg handling) would otherwise apply the same deltas twice. Receipts are
+    /// only marked as seen once analysis succeeded, so a failed block can be retried.
+    async fn analyze_unseen_receipts(&self, block: &Block<H256>, block_number: u64, receipts: Vec<TransactionReceipt>) -> Result<()> {
+        let total = receipts.len();
+        let receipts: Vec<TransactionReceipt> = receipts
+            .into_iter()
+            .filter(|receipt| !self.seen.seen_tx(receipt.transaction_hash))
+            .collect();
+        if receipts.len() < total {
+            debug!("⏭️ Skipping {} already analyzed transactions in block {}", total - receipts.len(), block_number);
+        }
 
         // 2. Perform comprehensive storage drift analysis 
-        let drift_events = self.storage_drift_detector
-            .analyze_block(&block, receipts)
-            .await?;
+        let tx_hashes: Vec<H256> = receipts.iter().map(|receipt| receipt.transaction_hash).collect();
+        let logs: Vec<&Log> = receipts.iter().flat_map(|receipt| &receipt.logs).collect();
+        let updated_pools = self.pool_manager.apply_logs(logs.iter().copied());
+        if !updated_pools.is_empty() {
+            debug!("🏊 {} pools updated in block {}", updated_pools.len(), block_number);
+        }
+        self.reload_tick_data(self.pool_manager.position_changes(logs)).await;
+        self.arbitrage_detector.gas_oracle().observe_block(block);
+        self.project_pending_state(block_number, &block.transactions).await;
+        let analysis = if self.config.strategies().storage_drift {
+            self.storage_drift_detector.analyze_block_with_deltas(block, receipts).await?
+        } else {
+            BlockAnalysis::empty(block_number)
+        };
+        for hash in tx_hashes {
+            self.seen.mark_tx(hash);
+        }
 
+        let changed = self.changed_pools(&analysis, updated_pools);
+        self.detect_arbitrage(block_number, changed).await;
+        self.record_drift_events(block_number, analysis.drift_events).await;
+        Ok(())
+    }
+
+    /// Drops the pending swaps `block_number` included and projects the rest onto the
+    /// pools' state after it, so reserve drift is predicted for the next block
+    async fn project_pending_state(&self, block_number: u64, included: &[H256]) {
+        let Some(watcher) = &self.mempool_watcher else {
+            return;
+        };
+        let removed = watcher.remove_included(included);
+        let expired = watcher.prune();
+        let base_fee = self.arbitrage_detector.gas_oracle().estimate()
+            .map(|estimate| estimate.next_base_fee)
+            .unwrap_or_default();
+        let projection = PendingProjection::project(
+            block_number,
+            &watcher.pending(),
+            base_fee,
+            &self.pool_manager,
+            &self.config.chain().addresses,
+            |address| self.arbitrage_detector.v3_snapshot(address),
+        );
+        debug!("📥 Block {} included {} pending swaps, {} expired; {} projected onto {} pools ({} revert, {} skipped)",
+            block_number, removed, expired, projection.applied.len(), projection.pools.len(),
+            projection.reverted.len(), projection.skipped.len());
+        if self.config.strategies().storage_drift {
+            self.storage_drift_detector.set_pending_reserves(block_number, projection.reserve_projections()).await;
+        }
+    }
+
+    /// Records a block's drift events together with those its pending swaps predict
+    async fn record_drift_events(&self, block_number: u64, mut drift_events: Vec<SlotDriftEvent>) {
+        if !self.config.strategies().storage_drift {
+            return;
+        }
+        drift_events.extend(self.storage_drift_detector.pending_drift_events(block_number).await);
         let high_confidence_drifts = self.filter_high_confidence_drifts(&drift_events).await;
 
         if !high_confidence_drifts.is_empty() {
@@ -441,48 +1377,104 @@ impl MevScanner {
                 events.drain(0..len_now - 1000);
             }
         }
-        Ok(())
     }
 
 
     async fn get_block_receipts(&self, block: &Block<H256>) -> Result<Vec<TransactionReceipt>> {
-       
+        let block_number = block.number
+            .ok_or_else(|| anyhow!("Block missing number!!!!"))?
+            .as_u64();
 
-        let receipts = if let Some(ws_provider) = self.primary_provider.lock().await.clone() {
-            self.fetch_receipts(ws_provider, block).await
-        } else {
-            self.fetch_receipts(self.fallback_provider.clone(), block).await
-        };
+        // Nothing in the header bloom can match: skip every receipt request
+        let bloom_query = self.monitored_contracts.bloom_query(self.config.chain().events.tracked()).await;
+        if let (Some(query), Some(bloom)) = (&bloom_query, &block.logs_bloom)
+            && !query.may_match(bloom)
+        {
+            debug!("⏭️ Block {} has no relevant logs in its bloom, skipping {} receipts",
+                block_number, block.transactions.len());
+            return Ok(Vec::new());
+        }
+
+        // Receipts land in the retry queue's store as they arrive, so a timed-out
+        // attempt leaves its partial progress behind for the next one
+        let progress = self.retry_queue.progress_for(block_number);
+
+        let mut complete = false;
+        let ws_provider = self.primary_provider.lock().await.clone();
+        if let Some(ws_provider) = ws_provider
+            && self.breakers.allow(&self.ws_endpoint, RpcOperation::Receipts).await
+        {
+            let result = self.fetch_receipts(ws_provider, block, &progress).await;
+            complete = self.breakers.observe(&self.ws_endpoint, RpcOperation::Receipts, result).await.is_ok();
+        }
+
+        if !complete {
+            // HTTP picks up whatever the WS attempt did not fetch. Analyzing a partial set
+            // would lose the rest for good, so the block fails and is retried with its
+            // progress kept instead.
+            let http_endpoint = self.config.fallback_rpc_url();
+            if !self.breakers.allow(http_endpoint, RpcOperation::Receipts).await {
+                return Err(anyhow!("Receipt breakers open, {} of {} receipts for block {} fetched",
+                    progress.len(), block.transactions.len(), block_number));
+            }
+            let result = self.fetch_receipts(self.fallback_provider.clone(), block, &progress).await;
+            self.breakers.observe(http_endpoint, RpcOperation::Receipts, result).await
+                .with_context(|| format!("Failed to fetch receipts for block {}", block_number))?;
+        }
+
+        // Only reuse receipts that belong to this exact block (the number may have been reorged)
+        let receipts = block.transactions
+            .iter()
+            .filter_map(|tx_hash| progress.get(tx_hash).map(|r| r.clone()))
+            .filter(|receipt| block.hash.is_none() || receipt.block_hash == block.hash)
+            .filter(|receipt| bloom_query.as_ref().is_none_or(|query| query.may_match(&receipt.logs_bloom)))
+            .collect();
 
         Ok(receipts)
     }
 
-     // Helper closure: fetch all receipts from a provider of any connection type
+     // Helper closure: fetch missing receipts from a provider of any connection type
     async fn fetch_receipts<P: JsonRpcClient + 'static>(
             &self,
             provider: Arc<Provider<P>>,
             block: &Block<H256>,
-        ) -> Vec<TransactionReceipt> {
+            progress: &ReceiptProgress,
+        ) -> Result<(), ProviderError> {
+
+            let missing: Vec<H256> = block.transactions
+                .iter()
+                .filter(|tx_hash| {
+                    progress
+                        .get(*tx_hash)
+                        .is_none_or(|receipt| block.hash.is_some() && receipt.block_hash != block.hash)
+                })
+                .copied()
+                .collect();
+
+            if missing.len() < block.transactions.len() {
+                debug!("🔁 Reusing {} previously fetched receipts", block.transactions.len() - missing.len());
+            }
 
-            static RPC_SEMAPHORE: Lazy<Semaphore> = Lazy::new(||
-            Semaphore::new(MAX_RECEIPT_CONCURRENCY)
-            );
-            stream::iter(&block.transactions)
+            let results: Vec<Result<(), ProviderError>> = stream::iter(missing)
                 .map(|tx_hash| {
                     let provider = provider.clone();
+                    let progress = progress.clone();
+                    let permits = self.receipt_permits.clone();
                     async move {
-                        let _permit = RPC_SEMAPHORE.acquire().await.unwrap();
-                        provider
-                            .get_transaction_receipt(*tx_hash)
-                            .await
-                            .ok()
-                            .flatten()
+                        let _permit = permits.acquire().await.unwrap();
+                        // A receipt the node has not indexed yet fails the block like an error would
+                        let receipt = provider.get_transaction_receipt(tx_hash).await?
+                            .ok_or_else(|| ProviderError::CustomError(format!("Receipt for {:?} not available yet", tx_hash)))?;
+                        progress.insert(tx_hash, receipt);
+                        Ok(())
                     }
                 })
-                .buffered(MAX_RECEIPT_CONCURRENCY)
-                .filter_map(|x| async move { x })
+                .buffer_unordered(self.config.max_receipt_concurrency())
                 .collect()
-                .await
+                .await;
+
+            // Successful receipts are already in `progress`, report the first failure
+            results.into_iter().collect()
     }
 
 
@@ -640,8 +1632,8 @@ mod tests {
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_get_block_receipts_via_anvil() -> anyhow::Result<()> {
-        // Start a local anvil with WS enabled
-        let anvil = Anvil::new().arg("--silent").arg("--ws").spawn();
+        // Start a local anvil with WS enabled, on mainnet's chain id so the default profile accepts it
+        let anvil = Anvil::new().arg("--silent").arg("--ws").chain_id(1u64).spawn();
 
         // Configure env for ScannerConfig (PRIVATE_KEY just needs to be non-empty per current config)
         unsafe {
@@ -0,0 +1,253 @@
+//! Log-filter scanning mode
+//!
+//! Instead of pulling every receipt of every block, the scanner can ask the node for
+//! just the logs emitted by monitored contracts with tracked event topics. Logs arrive
+//! as a flat stream and are regrouped per block before being analyzed. Contracts whose
+//! analysis needs trace data are kept out of the filter and still go through the
+//! full-receipt path.
+//!
+//! The same address and topic sets drive the `logsBloom` pre-filter of the full-receipt
+//! path, which skips blocks and receipts that cannot contain a relevant log.
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tokio::sync::{Notify, RwLock, futures::Notified};
+use ethers::types::{Address, Bloom, Filter, Log, H256};
+
+use super::bloom_filter::{BloomFilter, EthBloomBits};
+
+/// Contracts the scanner watches, and whether they need trace data
+#[derive(Default)]
+pub struct MonitoredContracts {
+    contracts: RwLock<HashMap<Address, bool>>,
+    changed: Notify,
+}
+
+impl MonitoredContracts {
+    // Only a running subscription waits for changes, so nothing is stored for later:
+    // contracts added before it subscribes are already in its filter
+    pub async fn add(&self, address: Address, needs_traces: bool) {
+        let previous = self.contracts.write().await.insert(address, needs_traces);
+        if previous != Some(needs_traces) {
+            self.changed.notify_waiters();
+        }
+    }
+
+    pub async fn remove(&self, address: Address) -> bool {
+        let removed = self.contracts.write().await.remove(&address).is_some();
+        if removed {
+            self.changed.notify_waiters();
+        }
+        removed
+    }
+
+    pub async fn contains(&self, address: Address) -> bool {
+        self.contracts.read().await.contains_key(&address)
+    }
+
+    /// Contracts served by the log filter
+    pub async fn log_addresses(&self) -> Vec<Address> {
+        self.contracts.read().await
+            .iter()
+            .filter(|(_, needs_traces)| !**needs_traces)
+            .map(|(address, _)| *address)
+            .collect()
+    }
+
+    /// Contracts that fall back to full-receipt processing
+    pub async fn trace_addresses(&self) -> HashSet<Address> {
+        self.contracts.read().await
+            .iter()
+            .filter(|(_, needs_traces)| **needs_traces)
+            .map(|(address, _)| *address)
+            .collect()
+    }
+
+    /// Resolves once the monitored set changes (the log filter must be rebuilt). Changes
+    /// count from the first poll or `enable`, so enable it before reading the set.
+    pub fn changed(&self) -> Notified<'_> {
+        self.changed.notified()
+    }
+
+    /// Pre-filter for the current contract set and the chain's tracked topics, or `None`
+    /// if some contract needs trace data (its activity does not have to show up in any
+    /// logs bloom)
+    pub async fn bloom_query(&self, topics: Vec<H256>) -> Option<BloomQuery> {
+        let contracts = self.contracts.read().await;
+        if contracts.values().any(|needs_traces| *needs_traces) {
+            return None;
+        }
+        Some(BloomQuery::new(contracts.keys().copied(), topics))
+    }
+}
+
+/// Checks a `logsBloom` for a log that the drift detector could use: a tracked topic
+/// emitted by a monitored contract. With no monitored contracts any emitter matches.
+pub struct BloomQuery {
+    addresses: Vec<EthBloomBits>,
+    topics: Vec<EthBloomBits>,
+}
+
+impl BloomQuery {
+    pub fn new(addresses: impl IntoIterator<Item = Address>, topics: impl IntoIterator<Item = H256>) -> Self {
+        Self {
+            addresses: addresses.into_iter().map(|a| EthBloomBits::new(a.as_bytes())).collect(),
+            topics: topics.into_iter().map(|t| EthBloomBits::new(t.as_bytes())).collect(),
+        }
+    }
+
+    /// `false` only if no matching log can be in the bloom
+    pub fn may_match(&self, bloom: &Bloom) -> bool {
+        let filter = BloomFilter::from_ethereum_bloom(bloom);
+        self.topics.iter().any(|topic| filter.contains_bits(topic))
+            && (self.addresses.is_empty() || self.addresses.iter().any(|address| filter.contains_bits(address)))
+    }
+}
+
+/// Filter for monitored contracts and tracked topics. An empty address list
+/// matches the tracked topics on every contract.
+pub fn build_log_filter(addresses: Vec<Address>, topics: Vec<H256>) -> Filter {
+    let filter = Filter::new().topic0(topics);
+    if addresses.is_empty() {
+        filter
+    } else {
+        filter.address(addresses)
+    }
+}
+
+/// Groups a block-ordered log stream into per-block batches
+#[derive(Default)]
+pub struct LogBatcher {
+    current_block: Option<u64>,
+    logs: Vec<Log>,
+}
+
+impl LogBatcher {
+    /// Adds a log and returns the previous block's batch once a newer block shows up.
+    /// Logs removed by a reorg and logs without a block number are dropped.
+    pub fn push(&mut self, log: Log) -> Option<(u64, Vec<Log>)> {
+        if log.removed == Some(true) {
+            return None;
+        }
+        let block_number = log.block_number?.as_u64();
+
+        let completed = match self.current_block {
+            Some(current) if current != block_number => self.flush(),
+            _ => None,
+        };
+        self.current_block = Some(block_number);
+        self.logs.push(log);
+        completed
+    }
+
+    pub fn has_pending(&self) -> bool {
+        !self.logs.is_empty()
+    }
+
+    pub fn flush(&mut self) -> Option<(u64, Vec<Log>)> {
+        let block_number = self.current_block.take()?;
+        Some((block_number, std::mem::take(&mut self.logs)))
+    }
+}
+
+/// Groups `eth_getLogs` results per block, ordered by block and log index
+pub fn group_logs_by_block(logs: Vec<Log>) -> BTreeMap<u64, Vec<Log>> {
+    let mut grouped: BTreeMap<u64, Vec<Log>> = BTreeMap::new();
+    for log in logs {
+        if log.removed == Some(true) {
+            continue;
+        }
+        if let Some(block_number) = log.block_number {
+            grouped.entry(block_number.as_u64()).or_default().push(log);
+        }
+    }
+    for logs in grouped.values_mut() {
+        logs.sort_by_key(|log| log.log_index);
+    }
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::EventSignatures;
+
+    fn log_at(block: u64, index: u64) -> Log {
+        Log {
+            block_number: Some(block.into()),
+            log_index: Some(index.into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_batcher_emits_previous_block_on_change() {
+        let mut batcher = LogBatcher::default();
+        assert!(batcher.push(log_at(10, 0)).is_none());
+        assert!(batcher.push(log_at(10, 1)).is_none());
+
+        let (block, logs) = batcher.push(log_at(11, 0)).expect("block 10 completed");
+        assert_eq!(block, 10);
+        assert_eq!(logs.len(), 2);
+
+        let (block, logs) = batcher.flush().expect("block 11 pending");
+        assert_eq!(block, 11);
+        assert_eq!(logs.len(), 1);
+        assert!(!batcher.has_pending());
+    }
+
+    #[tokio::test]
+    async fn test_bloom_query_needs_monitored_address_and_tracked_topic() {
+        use ethers::abi::ethereum_types::BloomInput;
+
+        let pool = Address::repeat_byte(0x11);
+        let mut bloom = Bloom::default();
+        bloom.accrue(BloomInput::Raw(pool.as_bytes()));
+        bloom.accrue(BloomInput::Raw(H256::repeat_byte(0x99).as_bytes()));
+
+        let events = EventSignatures::uniswap();
+        let contracts = MonitoredContracts::default();
+        // Untracked event from the pool
+        assert!(!contracts.bloom_query(events.tracked()).await.unwrap().may_match(&bloom));
+
+        bloom.accrue(BloomInput::Raw(events.sync.as_bytes()));
+        assert!(contracts.bloom_query(events.tracked()).await.unwrap().may_match(&bloom));
+
+        contracts.add(Address::repeat_byte(0x22), false).await;
+        assert!(!contracts.bloom_query(events.tracked()).await.unwrap().may_match(&bloom));
+        contracts.add(pool, false).await;
+        assert!(contracts.bloom_query(events.tracked()).await.unwrap().may_match(&bloom));
+
+        contracts.add(Address::repeat_byte(0x33), true).await;
+        assert!(contracts.bloom_query(events.tracked()).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_only_waiting_subscriptions_see_changes() {
+        use std::{pin::pin, time::Duration};
+        use tokio::time::timeout;
+
+        let contracts = MonitoredContracts::default();
+        // Added before anyone subscribed: must not wake the first subscription
+        contracts.add(Address::repeat_byte(0x11), false).await;
+        let mut changed = pin!(contracts.changed());
+        changed.as_mut().enable();
+        assert!(timeout(Duration::from_millis(10), changed.as_mut()).await.is_err());
+
+        // Re-adding the same contract changes nothing
+        contracts.add(Address::repeat_byte(0x11), false).await;
+        assert!(timeout(Duration::from_millis(10), changed.as_mut()).await.is_err());
+
+        contracts.add(Address::repeat_byte(0x11), true).await;
+        assert!(timeout(Duration::from_millis(10), changed.as_mut()).await.is_ok());
+    }
+
+    #[test]
+    fn test_group_logs_skips_removed() {
+        let mut removed = log_at(5, 0);
+        removed.removed = Some(true);
+        let grouped = group_logs_by_block(vec![log_at(6, 1), removed, log_at(6, 0), log_at(5, 2)]);
+
+        assert_eq!(grouped.keys().copied().collect::<Vec<_>>(), vec![5, 6]);
+        assert_eq!(grouped[&5].len(), 1);
+        assert_eq!(grouped[&6][0].log_index, Some(0.into()));
+    }
+}
@@ -6,8 +6,23 @@
 mod core;
 mod bloom_filter;
 mod circuit_breaker;
+mod breaker_registry;
+mod retry_queue;
+mod watchdog;
+mod log_filter;
+mod checkpoint;
+mod replay;
 
 pub use core::MevScanner;
-pub use bloom_filter::BloomFilter;
-pub use circuit_breaker::CircuitBreaker;
+pub use bloom_filter::{BloomFilter, EthBloomBits, RotatingBloomFilter, ScalableBloomFilter, log_key, tx_key};
+pub use circuit_breaker::{
+    BreakerTransition, CircuitBreaker, CircuitState, FailureWindow, TransitionHub, TransitionListener,
+    TransitionReason, TripPolicy,
+};
+pub use breaker_registry::{BreakerRegistry, BreakerStatus, ErrorClass, RpcOperation, classify_error, redact_endpoint};
+pub use retry_queue::{BlockRetryQueue, DeadLetterBlock};
+pub use watchdog::WsWatchdog;
+pub use log_filter::{BloomQuery, LogBatcher, MonitoredContracts};
+pub use checkpoint::{ScannerCheckpoint, StateStore};
+pub use replay::{ReplayBlock, load_replay_file, save_replay_file};
 
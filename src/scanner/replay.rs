@@ -0,0 +1,82 @@
+//! Recorded blocks for offline analysis
+//!
+//! A replay file holds a block header with its receipts, or a list of them, as JSON.
+//! `inspect-block --save` writes one, and `replay` feeds them through a fresh drift
+//! detector without touching the network.
+use std::path::Path;
+use anyhow::{Context, Result, anyhow};
+use ethers::types::{Block, H256, TransactionReceipt};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayBlock {
+    pub block: Block<H256>,
+    pub receipts: Vec<TransactionReceipt>,
+}
+
+impl ReplayBlock {
+    pub fn number(&self) -> Result<u64> {
+        self.block.number
+            .map(|n| n.as_u64())
+            .ok_or_else(|| anyhow!("Replay block has no number"))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ReplayFile {
+    Many(Vec<ReplayBlock>),
+    One(Box<ReplayBlock>),
+}
+
+/// Reads a replay file, sorted by block number
+pub fn load_replay_file(path: &Path) -> Result<Vec<ReplayBlock>> {
+    let contents = std::fs::read(path)
+        .with_context(|| format!("Failed to read replay file {}", path.display()))?;
+    let mut blocks = match serde_json::from_slice(&contents)
+        .with_context(|| format!("{} is not a replay file (block + receipts JSON)", path.display()))?
+    {
+        ReplayFile::Many(blocks) => blocks,
+        ReplayFile::One(block) => vec![*block],
+    };
+
+    for block in &blocks {
+        block.number()?;
+    }
+    blocks.sort_by_key(|block| block.block.number);
+    Ok(blocks)
+}
+
+pub fn save_replay_file(path: &Path, block: &ReplayBlock) -> Result<()> {
+    std::fs::write(path, serde_json::to_vec_pretty(block)?)
+        .with_context(|| format!("Failed to write replay file {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_file_accepts_one_or_many_blocks() {
+        let dir = std::env::temp_dir().join(format!("replay-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let block_at = |number: u64| ReplayBlock {
+            block: Block { number: Some(number.into()), ..Default::default() },
+            receipts: vec![TransactionReceipt::default()],
+        };
+
+        let single = dir.join("single.json");
+        save_replay_file(&single, &block_at(7)).unwrap();
+        let loaded = load_replay_file(&single).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].receipts.len(), 1);
+
+        let many = dir.join("many.json");
+        std::fs::write(&many, serde_json::to_vec(&vec![block_at(9), block_at(8)]).unwrap()).unwrap();
+        let numbers: Vec<u64> = load_replay_file(&many).unwrap().iter().map(|b| b.number().unwrap()).collect();
+        assert_eq!(numbers, vec![8, 9]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
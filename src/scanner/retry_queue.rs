@@ -0,0 +1,197 @@
+//! Retry queue for blocks that could not be processed in time
+//!
+//! Blocks that hit the processing timeout are parked here instead of being dropped.
+//! Each entry is retried with exponential backoff, and the receipts fetched by earlier
+//! attempts are kept so a retry only has to fetch what is still missing. Blocks that
+//! run out of attempts end up in a bounded dead-letter list.
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::{Mutex, RwLock};
+use ethers::types::{H256, TransactionReceipt};
+use dashmap::DashMap;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::{warn, error};
+
+use crate::const_and_addr;
+
+const MAX_DEAD_LETTERS: usize = 1000;
+
+/// Receipts already fetched for a block, keyed by transaction hash
+pub type ReceiptProgress = Arc<DashMap<H256, TransactionReceipt>>;
+
+/// A block that exhausted all of its retry attempts
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadLetterBlock {
+    pub block_number: u64,
+    pub attempts: usize,
+    pub reason: String,
+    pub failed_at: DateTime<Utc>,
+}
+
+struct PendingRetry {
+    attempts: usize,
+    next_attempt: Instant,
+    in_flight: bool,
+}
+
+pub struct BlockRetryQueue {
+    max_attempts: usize,
+    base_delay: Duration,
+    pending: Mutex<BTreeMap<u64, PendingRetry>>,
+    progress: DashMap<u64, ReceiptProgress>,
+    dead_letters: RwLock<Vec<DeadLetterBlock>>,
+}
+
+impl BlockRetryQueue {
+    pub fn new(max_attempts: usize, base_delay: Duration) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            base_delay,
+            pending: Mutex::new(BTreeMap::new()),
+            progress: DashMap::new(),
+            dead_letters: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Returns the shared receipt store for a block, creating it on first use
+    pub fn progress_for(&self, block_number: u64) -> ReceiptProgress {
+        self.progress.entry(block_number).or_default().clone()
+    }
+
+    /// Records a failed attempt. Returns `true` if the block was queued for another
+    /// attempt and `false` if it was moved to the dead-letter list.
+    pub async fn record_failure(&self, block_number: u64, reason: impl Into<String>) -> bool {
+        let reason = reason.into();
+        let mut pending = self.pending.lock().await;
+        let entry = pending.entry(block_number).or_insert(PendingRetry {
+            attempts: 0,
+            next_attempt: Instant::now(),
+            in_flight: false,
+        });
+        entry.attempts += 1;
+        entry.in_flight = false;
+
+        if entry.attempts < self.max_attempts {
+            let delay = self.backoff(entry.attempts);
+            entry.next_attempt = Instant::now() + delay;
+            warn!("🔁 Block {} queued for retry (attempt {}/{}) in {}ms: {}",
+                block_number, entry.attempts, self.max_attempts, delay.as_millis(), reason);
+            return true;
+        }
+
+        let attempts = entry.attempts;
+        pending.remove(&block_number);
+        drop(pending);
+        self.progress.remove(&block_number);
+
+        error!("☠️ Block {} moved to dead-letter list after {} attempts: {}", block_number, attempts, reason);
+        let mut dead_letters = self.dead_letters.write().await;
+        dead_letters.push(DeadLetterBlock {
+            block_number,
+            attempts,
+            reason,
+            failed_at: Utc::now(),
+        });
+        if dead_letters.len() > MAX_DEAD_LETTERS {
+            let len_now = dead_letters.len();
+            dead_letters.drain(0..len_now - MAX_DEAD_LETTERS);
+        }
+        false
+    }
+
+    /// Queues blocks restored from a checkpoint, due right away with a fresh attempt count
+    pub async fn requeue(&self, blocks: impl IntoIterator<Item = u64>) {
+        let mut pending = self.pending.lock().await;
+        for block_number in blocks {
+            pending.entry(block_number).or_insert(PendingRetry {
+                attempts: 0,
+                next_attempt: Instant::now(),
+                in_flight: false,
+            });
+        }
+    }
+
+    /// Hands out the blocks whose backoff has elapsed, oldest first.
+    /// Every returned block must be followed by `complete` or `record_failure`.
+    pub async fn take_due(&self) -> Vec<u64> {
+        let now = Instant::now();
+        let mut pending = self.pending.lock().await;
+        pending
+            .iter_mut()
+            .filter(|(_, retry)| !retry.in_flight && retry.next_attempt <= now)
+            .map(|(block_number, retry)| {
+                retry.in_flight = true;
+                *block_number
+            })
+            .collect()
+    }
+
+    /// Marks a block as fully processed and releases its cached receipts
+    pub async fn complete(&self, block_number: u64) {
+        self.pending.lock().await.remove(&block_number);
+        self.progress.remove(&block_number);
+    }
+
+    /// Drops cached receipts for a block that will not be retried
+    pub fn discard_progress(&self, block_number: u64) {
+        self.progress.remove(&block_number);
+    }
+
+    pub async fn pending_blocks(&self) -> Vec<u64> {
+        self.pending.lock().await.keys().copied().collect()
+    }
+
+    pub async fn dead_letters(&self) -> Vec<DeadLetterBlock> {
+        self.dead_letters.read().await.clone()
+    }
+
+    fn backoff(&self, attempts: usize) -> Duration {
+        let factor = const_and_addr::BACKOFF_MULTIPLIER.powi(attempts.saturating_sub(1) as i32);
+        self.base_delay.mul_f64(factor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_block_dead_lettered_after_max_attempts() {
+        let queue = BlockRetryQueue::new(3, Duration::ZERO);
+
+        assert!(queue.record_failure(42, "timed out").await);
+        assert_eq!(queue.take_due().await, vec![42]);
+        // In-flight blocks are not handed out twice
+        assert!(queue.take_due().await.is_empty());
+
+        assert!(queue.record_failure(42, "timed out").await);
+        assert_eq!(queue.take_due().await, vec![42]);
+        assert!(!queue.record_failure(42, "timed out").await);
+
+        assert!(queue.pending_blocks().await.is_empty());
+        let dead_letters = queue.dead_letters().await;
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].block_number, 42);
+        assert_eq!(dead_letters[0].attempts, 3);
+    }
+
+    #[tokio::test]
+    async fn test_progress_survives_until_complete() {
+        let queue = BlockRetryQueue::new(3, Duration::from_secs(60));
+        let progress = queue.progress_for(7);
+        progress.insert(H256::repeat_byte(1), TransactionReceipt::default());
+
+        queue.record_failure(7, "timed out").await;
+        // Backoff has not elapsed yet
+        assert!(queue.take_due().await.is_empty());
+        assert_eq!(queue.progress_for(7).len(), 1);
+
+        queue.complete(7).await;
+        assert!(queue.progress_for(7).is_empty());
+        assert!(queue.pending_blocks().await.is_empty());
+    }
+}
@@ -0,0 +1,88 @@
+//! Liveness watchdog for WebSocket block subscriptions
+//!
+//! A half-open socket never yields an error, it just stops delivering heads. The
+//! watchdog turns "no new head within N block times" and "WS head is far behind the
+//! HTTP head" into explicit failures so the scanner can fall back and reconnect.
+use std::time::{Duration, Instant};
+use anyhow::{anyhow, Result};
+
+pub struct WsWatchdog {
+    head_timeout: Duration,
+    max_head_lag: u64,
+    last_head_at: Instant,
+    last_head_number: Option<u64>,
+}
+
+impl WsWatchdog {
+    /// `stale_multiplier` block times without a new head mark the subscription as stale
+    pub fn new(block_time: Duration, stale_multiplier: u32, max_head_lag: u64) -> Self {
+        Self {
+            head_timeout: block_time * stale_multiplier.max(1),
+            max_head_lag,
+            last_head_at: Instant::now(),
+            last_head_number: None,
+        }
+    }
+
+    pub fn record_head(&mut self, number: u64) {
+        self.last_head_at = Instant::now();
+        self.last_head_number = Some(self.last_head_number.map_or(number, |n| n.max(number)));
+    }
+
+    pub fn head_timeout(&self) -> Duration {
+        self.head_timeout
+    }
+
+    pub fn last_head_number(&self) -> Option<u64> {
+        self.last_head_number
+    }
+
+    /// Time left before the subscription is considered stale
+    pub fn time_until_stale(&self) -> Duration {
+        self.head_timeout.saturating_sub(self.last_head_at.elapsed())
+    }
+
+    pub fn is_stale(&self) -> bool {
+        self.last_head_at.elapsed() >= self.head_timeout
+    }
+
+    /// Compares the head reported by the WS node against the HTTP provider's head
+    pub fn check_lag(&self, ws_head: u64, http_head: u64) -> Result<()> {
+        let lag = http_head.saturating_sub(ws_head);
+        if lag > self.max_head_lag {
+            return Err(anyhow!(
+                "WebSocket node lagging: WS head {} is {} blocks behind HTTP head {}",
+                ws_head, lag, http_head
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stale_after_missing_heads() {
+        let mut watchdog = WsWatchdog::new(Duration::from_millis(10), 2, 3);
+        assert_eq!(watchdog.head_timeout(), Duration::from_millis(20));
+        assert!(!watchdog.is_stale());
+
+        std::thread::sleep(Duration::from_millis(25));
+        assert!(watchdog.is_stale());
+        assert_eq!(watchdog.time_until_stale(), Duration::ZERO);
+
+        watchdog.record_head(100);
+        assert!(!watchdog.is_stale());
+        assert_eq!(watchdog.last_head_number(), Some(100));
+    }
+
+    #[test]
+    fn test_lagging_ws_head_is_rejected() {
+        let watchdog = WsWatchdog::new(Duration::from_secs(12), 3, 3);
+        assert!(watchdog.check_lag(100, 103).is_ok());
+        assert!(watchdog.check_lag(104, 103).is_ok());
+        assert!(watchdog.check_lag(100, 104).is_err());
+    }
+}
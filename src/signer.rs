@@ -0,0 +1,353 @@
+//! Where the transaction signer comes from
+//!
+//! The scanner itself never signs anything, so by default it runs read-only and needs
+//! no key at all. When submission is enabled the key comes from one of:
+//!
+//! - `PRIVATE_KEY` in the environment
+//! - an encrypted JSON keystore, unlocked with a password file, `KEYSTORE_PASSWORD`,
+//!   or an interactive prompt
+//! - a BIP-39 mnemonic (`MNEMONIC`) and a derivation path
+//!
+//! Key material is wrapped in `Secret`, which is zeroed on drop and never shows up in
+//! `Debug` output, so logging a `ScannerConfig` cannot leak it.
+use std::{fmt, path::{Path, PathBuf}, str::FromStr};
+use anyhow::{Context, Result, anyhow};
+use ethers::signers::{LocalWallet, MnemonicBuilder, coins_bip39::English};
+use zeroize::Zeroize;
+
+/// Account 0 of the standard Ethereum derivation path
+pub const DEFAULT_DERIVATION_PATH: &str = "m/44'/60'/0'/0/0";
+
+/// Key material that is redacted from `Debug` and wiped from memory on drop
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(<redacted>)")
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// How the keystore password is obtained
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeystorePassword {
+    /// Ask on the terminal when the signer is loaded
+    Prompt,
+    /// First line of a file, e.g. a mounted secret
+    File(PathBuf),
+    /// Given directly, usually from `KEYSTORE_PASSWORD`
+    Value(Secret),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum SignerSource {
+    /// No signer, submission stays disabled
+    #[default]
+    ReadOnly,
+    PrivateKey(Secret),
+    Keystore {
+        path: PathBuf,
+        password: KeystorePassword,
+    },
+    Mnemonic {
+        phrase: Secret,
+        derivation_path: String,
+    },
+}
+
+/// `signer.kind` values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignerKind {
+    ReadOnly,
+    PrivateKey,
+    Keystore,
+    Mnemonic,
+}
+
+impl FromStr for SignerKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "read_only" | "readonly" | "none" => Ok(SignerKind::ReadOnly),
+            "private_key" => Ok(SignerKind::PrivateKey),
+            "keystore" => Ok(SignerKind::Keystore),
+            "mnemonic" => Ok(SignerKind::Mnemonic),
+            other => Err(anyhow!(
+                "Unknown signer kind '{}', expected 'read_only', 'private_key', 'keystore' or 'mnemonic'",
+                other
+            )),
+        }
+    }
+}
+
+/// Non-secret signer settings from the config layers. Key material itself is only
+/// read from the environment (`PRIVATE_KEY`, `MNEMONIC`, `KEYSTORE_PASSWORD`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SignerSettings {
+    /// Inferred from what is available when unset
+    pub kind: Option<SignerKind>,
+    pub keystore: Option<PathBuf>,
+    pub password_file: Option<PathBuf>,
+    pub derivation_path: Option<String>,
+}
+
+impl SignerSettings {
+    /// Picks the signer source. `secret` looks up an environment variable.
+    ///
+    /// Without an explicit `signer.kind` a keystore path wins over `MNEMONIC`, which
+    /// wins over `PRIVATE_KEY`. Nothing configured means read-only.
+    pub fn resolve(&self, secret: impl Fn(&str) -> Option<String>) -> Result<SignerSource> {
+        let secret = |name: &str| secret(name).filter(|v| !v.trim().is_empty()).map(Secret::new);
+
+        let kind = match self.kind {
+            Some(kind) => kind,
+            None if self.keystore.is_some() => SignerKind::Keystore,
+            None if secret("MNEMONIC").is_some() => SignerKind::Mnemonic,
+            None if secret("PRIVATE_KEY").is_some() => SignerKind::PrivateKey,
+            None => SignerKind::ReadOnly,
+        };
+
+        Ok(match kind {
+            SignerKind::ReadOnly => SignerSource::ReadOnly,
+            SignerKind::PrivateKey => SignerSource::PrivateKey(
+                secret("PRIVATE_KEY").ok_or_else(|| anyhow!("signer.kind = private_key requires PRIVATE_KEY in the environment"))?,
+            ),
+            SignerKind::Keystore => SignerSource::Keystore {
+                path: self.keystore.clone().ok_or_else(|| anyhow!("signer.kind = keystore requires signer.keystore"))?,
+                password: match (&self.password_file, secret("KEYSTORE_PASSWORD")) {
+                    (Some(file), _) => KeystorePassword::File(file.clone()),
+                    (None, Some(password)) => KeystorePassword::Value(password),
+                    (None, None) => KeystorePassword::Prompt,
+                },
+            },
+            SignerKind::Mnemonic => SignerSource::Mnemonic {
+                phrase: secret("MNEMONIC").ok_or_else(|| anyhow!("signer.kind = mnemonic requires MNEMONIC in the environment"))?,
+                derivation_path: self.derivation_path.clone().unwrap_or_else(|| DEFAULT_DERIVATION_PATH.to_string()),
+            },
+        })
+    }
+}
+
+impl SignerSource {
+    pub fn kind(&self) -> SignerKind {
+        match self {
+            SignerSource::ReadOnly => SignerKind::ReadOnly,
+            SignerSource::PrivateKey(_) => SignerKind::PrivateKey,
+            SignerSource::Keystore { .. } => SignerKind::Keystore,
+            SignerSource::Mnemonic { .. } => SignerKind::Mnemonic,
+        }
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        matches!(self, SignerSource::ReadOnly)
+    }
+
+    /// Short description without any key material, for logs and `config check`
+    pub fn describe(&self) -> String {
+        match self {
+            SignerSource::ReadOnly => "read_only".to_string(),
+            SignerSource::PrivateKey(_) => "private_key (PRIVATE_KEY)".to_string(),
+            SignerSource::Keystore { path, password } => {
+                let password = match password {
+                    KeystorePassword::Prompt => "prompt".to_string(),
+                    KeystorePassword::File(file) => format!("password file {}", file.display()),
+                    KeystorePassword::Value(_) => "KEYSTORE_PASSWORD".to_string(),
+                };
+                format!("keystore {} ({})", path.display(), password)
+            }
+            SignerSource::Mnemonic { derivation_path, .. } => format!("mnemonic (MNEMONIC) at {}", derivation_path),
+        }
+    }
+
+    /// Builds the wallet, prompting for a keystore password if needed. Blocks while
+    /// the keystore is decrypted, run it off the async runtime.
+    /// Returns `None` in read-only mode.
+    pub fn load(&self) -> Result<Option<LocalWallet>> {
+        let wallet = match self {
+            SignerSource::ReadOnly => return Ok(None),
+            SignerSource::PrivateKey(key) => LocalWallet::from_str(key.expose().trim_start_matches("0x"))
+                .map_err(|e| anyhow!("Invalid PRIVATE_KEY: {}", e))?,
+            SignerSource::Keystore { path, password } => {
+                let password = match password {
+                    KeystorePassword::Prompt => prompt_password(&format!("Password for keystore {}: ", path.display()))?,
+                    KeystorePassword::File(file) => read_password_file(file)?,
+                    KeystorePassword::Value(value) => value.clone(),
+                };
+                LocalWallet::decrypt_keystore(path, password.expose())
+                    .with_context(|| format!("Failed to decrypt keystore {}", path.display()))?
+            }
+            SignerSource::Mnemonic { phrase, derivation_path } => MnemonicBuilder::<English>::default()
+                .phrase(phrase.expose())
+                .derivation_path(derivation_path)
+                .and_then(|builder| builder.build())
+                .map_err(|e| anyhow!("Invalid mnemonic or derivation path '{}': {}", derivation_path, e))?,
+        };
+        Ok(Some(wallet))
+    }
+}
+
+fn read_password_file(path: &Path) -> Result<Secret> {
+    let contents = Secret::new(
+        std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read password file {}", path.display()))?,
+    );
+    let password = contents.expose().lines().next().unwrap_or_default();
+    Ok(Secret::new(password))
+}
+
+/// Reads a line from the terminal without echoing it
+fn prompt_password(prompt: &str) -> Result<Secret> {
+    use std::io::{BufRead, Write};
+
+    eprint!("{}", prompt);
+    std::io::stderr().flush()?;
+
+    let _echo_off = EchoGuard::disable();
+    let mut line = Secret::new(String::new());
+    std::io::stdin().lock().read_line(&mut line.0).context("Failed to read password")?;
+    eprintln!();
+
+    let trimmed = line.0.trim_end_matches(['\r', '\n']).len();
+    line.0.truncate(trimmed);
+    Ok(line)
+}
+
+/// Turns terminal echo off for its lifetime (no-op when stdin is not a terminal)
+struct EchoGuard {
+    #[cfg(unix)]
+    original: Option<libc::termios>,
+}
+
+impl EchoGuard {
+    #[cfg(unix)]
+    fn disable() -> Self {
+        // SAFETY: termios is plain data, and tcgetattr fills it in before it is read
+        unsafe {
+            let mut term: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut term) != 0 {
+                return Self { original: None };
+            }
+            let original = term;
+            term.c_lflag &= !libc::ECHO;
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &term);
+            Self { original: Some(original) }
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn disable() -> Self {
+        Self {}
+    }
+}
+
+impl Drop for EchoGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(original) = self.original {
+            // SAFETY: restores the attributes read in `disable`
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &original);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::signers::Signer;
+
+    const TEST_MNEMONIC: &str = "test test test test test test test test test test test junk";
+    const TEST_KEY: &str = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+    const TEST_ADDRESS: &str = "0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266";
+
+    #[test]
+    fn test_debug_output_redacts_key_material() {
+        let sources = [
+            SignerSource::PrivateKey(Secret::new(TEST_KEY)),
+            SignerSource::Mnemonic { phrase: Secret::new(TEST_MNEMONIC), derivation_path: DEFAULT_DERIVATION_PATH.into() },
+            SignerSource::Keystore { path: "key.json".into(), password: KeystorePassword::Value(Secret::new("hunter2")) },
+        ];
+        for source in sources {
+            let debug = format!("{:?} {}", source, source.describe());
+            assert!(!debug.contains("ac0974bec39a"), "{}", debug);
+            assert!(!debug.contains("junk"), "{}", debug);
+            assert!(!debug.contains("hunter2"), "{}", debug);
+        }
+    }
+
+    #[test]
+    fn test_resolve_infers_kind_from_available_material() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| vars.iter().find(|(k, _)| *k == name).map(|(_, v)| v.to_string())
+        };
+        let settings = SignerSettings::default();
+
+        assert_eq!(settings.resolve(env(&[])).unwrap(), SignerSource::ReadOnly);
+        assert_eq!(settings.resolve(env(&[("PRIVATE_KEY", TEST_KEY)])).unwrap().kind(), SignerKind::PrivateKey);
+        assert_eq!(
+            settings.resolve(env(&[("PRIVATE_KEY", TEST_KEY), ("MNEMONIC", TEST_MNEMONIC)])).unwrap().kind(),
+            SignerKind::Mnemonic
+        );
+
+        let keystore = SignerSettings { keystore: Some("key.json".into()), ..Default::default() };
+        assert_eq!(
+            keystore.resolve(env(&[("PRIVATE_KEY", TEST_KEY)])).unwrap(),
+            SignerSource::Keystore { path: "key.json".into(), password: KeystorePassword::Prompt }
+        );
+
+        let explicit = SignerSettings { kind: Some(SignerKind::Mnemonic), ..Default::default() };
+        let error = explicit.resolve(env(&[("PRIVATE_KEY", TEST_KEY)])).unwrap_err();
+        assert!(error.to_string().contains("MNEMONIC"));
+    }
+
+    #[test]
+    fn test_every_source_loads_a_wallet() {
+        assert!(SignerSource::ReadOnly.load().unwrap().is_none());
+
+        let from_key = SignerSource::PrivateKey(Secret::new(TEST_KEY)).load().unwrap().unwrap();
+        assert_eq!(format!("{:?}", from_key.address()), TEST_ADDRESS);
+
+        let from_mnemonic = SignerSource::Mnemonic {
+            phrase: Secret::new(TEST_MNEMONIC),
+            derivation_path: DEFAULT_DERIVATION_PATH.into(),
+        }.load().unwrap().unwrap();
+        assert_eq!(from_mnemonic.address(), from_key.address());
+
+        let dir = std::env::temp_dir().join(format!("signer-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut rng = ethers::core::rand::thread_rng();
+        let (created, _) = LocalWallet::new_keystore(&dir, &mut rng, "hunter2", Some("key.json")).unwrap();
+        std::fs::write(dir.join("password"), "hunter2\n").unwrap();
+
+        let keystore = SignerSource::Keystore {
+            path: dir.join("key.json"),
+            password: KeystorePassword::File(dir.join("password")),
+        };
+        assert_eq!(keystore.load().unwrap().unwrap().address(), created.address());
+
+        let wrong_password = SignerSource::Keystore {
+            path: dir.join("key.json"),
+            password: KeystorePassword::Value(Secret::new("wrong")),
+        };
+        assert!(wrong_password.load().is_err());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,157 @@
+//! Transactions to simulate, and how an arbitrage opportunity turns into them
+use anyhow::{Context, Result, anyhow};
+use ethers::{
+    abi::{self, Token},
+    types::{Address, Bytes, TransactionRequest, U256, transaction::eip2718::TypedTransaction},
+    utils::id,
+};
+
+use crate::{
+    address_book::DexKind,
+    arbitrage::ArbitrageOpportunity,
+    chain::ChainProfile,
+    pools::PoolManager,
+};
+
+/// Transactions sent in order by one searcher account
+#[derive(Debug, Clone, Default)]
+pub struct Bundle {
+    pub searcher: Address,
+    pub transactions: Vec<TypedTransaction>,
+    /// Tokens whose searcher balances are compared before and after
+    pub tracked_tokens: Vec<Address>,
+    /// Native balance the searcher is given before the first transaction
+    pub funding: U256,
+}
+
+impl Bundle {
+    pub fn new(searcher: Address) -> Self {
+        Self { searcher, ..Self::default() }
+    }
+
+    pub fn push(&mut self, to: Address, data: Vec<u8>, value: U256) {
+        let tx = TransactionRequest::new().from(self.searcher).to(to).data(Bytes::from(data)).value(value);
+        self.transactions.push(tx.into());
+    }
+
+    pub fn track(&mut self, token: Address) {
+        if !self.tracked_tokens.contains(&token) {
+            self.tracked_tokens.push(token);
+        }
+    }
+
+    /// Router swaps executing an opportunity hop by hop: wrap the input, then approve and
+    /// swap on every pool's router. Intermediate hops accept any output, the last one
+    /// requires `min_amount_out`, which covers the input and gas, so a losing cycle reverts.
+    pub fn from_opportunity(
+        opportunity: &ArbitrageOpportunity,
+        searcher: Address,
+        chain: &ChainProfile,
+        pool_manager: &PoolManager,
+    ) -> Result<Self> {
+        let mut bundle = Self::new(searcher);
+        bundle.funding = opportunity.amount_in;
+        bundle.track(opportunity.profit_token);
+        bundle.push(opportunity.profit_token, id("deposit()").to_vec(), opportunity.amount_in);
+
+        let last = opportunity.path.len().saturating_sub(1);
+        for (i, hop) in opportunity.path.iter().enumerate() {
+            let pool = pool_manager.get(hop.pool).ok_or_else(|| anyhow!("Pool {:?} is not watched", hop.pool))?;
+            let factory = chain.addresses.factory_by_address(pool.factory)
+                .ok_or_else(|| anyhow!("Unknown factory {:?} of pool {:?}", pool.factory, hop.pool))?;
+            let router = chain.addresses.router(&factory.name)
+                .with_context(|| format!("No router for factory {}", factory.name))?
+                .address;
+            let min_out = if i == last { opportunity.min_amount_out } else { U256::zero() };
+
+            bundle.track(hop.token_out);
+            bundle.push(hop.token_in, encode("approve(address,uint256)", &[Token::Address(router), Token::Uint(hop.amount_in)]), U256::zero());
+            let swap = match hop.dex {
+                DexKind::UniswapV2 => encode("swapExactTokensForTokens(uint256,uint256,address[],address,uint256)", &[
+                    Token::Uint(hop.amount_in),
+                    Token::Uint(min_out),
+                    Token::Array(vec![Token::Address(hop.token_in), Token::Address(hop.token_out)]),
+                    Token::Address(searcher),
+                    Token::Uint(U256::MAX),
+                ]),
+                DexKind::UniswapV3 => encode("exactInputSingle((address,address,uint24,address,uint256,uint256,uint256,uint160))", &[
+                    Token::Tuple(vec![
+                        Token::Address(hop.token_in),
+                        Token::Address(hop.token_out),
+                        Token::Uint(pool.fee.into()),
+                        Token::Address(searcher),
+                        Token::Uint(U256::MAX),
+                        Token::Uint(hop.amount_in),
+                        Token::Uint(min_out),
+                        Token::Uint(U256::zero()),
+                    ]),
+                ]),
+            };
+            bundle.push(router, swap, U256::zero());
+        }
+        Ok(bundle)
+    }
+}
+
+fn encode(signature: &str, args: &[Token]) -> Vec<u8> {
+    let mut data = id(signature).to_vec();
+    data.extend(abi::encode(args));
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use crate::{arbitrage::SwapHop, pools::PoolState};
+
+    #[test]
+    fn test_encodes_opportunity_as_router_swaps() {
+        let chain = ChainProfile::ethereum();
+        let (weth, usdc) = (chain.addresses.wrapped_native(), chain.addresses.token("USDC").unwrap().address);
+        let (v2, v3) = (chain.addresses.factory("sushiswap").unwrap(), chain.addresses.factory("uniswap_v3").unwrap());
+        let manager = PoolManager::new(Vec::new(), chain.events.clone());
+        manager.add_pool(PoolState::v3(Address::repeat_byte(1), v3.address, usdc, weth, 500));
+        manager.add_pool(PoolState::v2(Address::repeat_byte(2), v2.address, usdc, weth, 30));
+
+        let hop = |pool: u8, dex, token_in, token_out, amount_in: u64, amount_out: u64| SwapHop {
+            pool: Address::repeat_byte(pool), dex, token_in, token_out, amount_in: amount_in.into(), amount_out: amount_out.into(),
+        };
+        let opportunity = ArbitrageOpportunity {
+            block_number: 1,
+            profit_token: weth,
+            intermediate_token: usdc,
+            buy_pool: Address::repeat_byte(1),
+            buy_dex: DexKind::UniswapV3,
+            sell_pool: Address::repeat_byte(2),
+            sell_dex: DexKind::UniswapV2,
+            path: vec![hop(1, DexKind::UniswapV3, weth, usdc, 1_000, 2_000), hop(2, DexKind::UniswapV2, usdc, weth, 2_000, 1_100)],
+            amount_in: 1_000.into(),
+            intermediate_amount: 2_000.into(),
+            amount_out: 1_100.into(),
+            min_amount_out: 1_090.into(),
+            profit: 100.into(),
+            gas_units: 0,
+            gas_cost: U256::zero(),
+            net_profit: 100.into(),
+            simulated_profit: None,
+            simulated_gas: None,
+            detected_at: Utc::now(),
+        };
+
+        let searcher = Address::repeat_byte(0x5e);
+        let bundle = Bundle::from_opportunity(&opportunity, searcher, &chain, &manager).unwrap();
+        assert_eq!(bundle.tracked_tokens, vec![weth, usdc]);
+        assert_eq!(bundle.funding, U256::from(1_000u64));
+        let to: Vec<Address> = bundle.transactions.iter().map(|tx| *tx.to_addr().unwrap()).collect();
+        let v3_router = chain.addresses.router("uniswap_v3").unwrap().address;
+        let v2_router = chain.addresses.router("sushiswap").unwrap().address;
+        assert_eq!(to, vec![weth, weth, v3_router, usdc, v2_router]);
+        assert_eq!(bundle.transactions[0].value(), Some(&U256::from(1_000u64)));
+
+        // The last swap carries the slippage bound
+        let data = bundle.transactions[4].data().unwrap();
+        assert_eq!(&data[..4], &id("swapExactTokensForTokens(uint256,uint256,address[],address,uint256)"));
+        assert_eq!(U256::from_big_endian(&data[36..68]), U256::from(1_090u64));
+    }
+}
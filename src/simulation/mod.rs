@@ -0,0 +1,7 @@
+// ! Simulation module - runs candidate bundles on node state or a local fork
+
+mod bundle;
+mod simulator;
+
+pub use bundle::Bundle;
+pub use simulator::{SimulationBackend, SimulationResult, Simulator, TransactionOutcome, decode_revert_reason};
@@ -0,0 +1,504 @@
+//! Bundle simulation before anything is queued or submitted
+//!
+//! Two backends run the same `Bundle` on top of the state after a given block:
+//! - `EthCall` traces each transaction with `debug_traceCall` on the node. The call
+//!   tracer reports gas, logs and revert data, and the prestate diff of every successful
+//!   transaction is folded into the state overrides of the next one, so later
+//!   transactions see what earlier ones did. Needs the node's `debug` namespace.
+//! - `Fork` resets a local anvil to a fork at the block, impersonates the searcher and
+//!   mines the transactions one by one.
+//!
+//! Neither backend charges gas, so the searcher's native balance delta is value moved
+//! only; gas is reported separately.
+use std::{collections::BTreeMap, process::Command, str::FromStr, sync::Arc};
+use anyhow::{Context, Result, anyhow};
+use ethers::{
+    abi::{self, ParamType, Token},
+    providers::{Http, Middleware, Provider, RawCall, RpcError},
+    types::{
+        Address, BlockId, BlockNumber, Bytes, CallFrame, DiffMode, GethDebugBuiltInTracerConfig,
+        GethDebugBuiltInTracerType, GethDebugTracerConfig, GethDebugTracerType, GethDebugTracingCallOptions,
+        GethDebugTracingOptions, H256, I256, Log, PreStateConfig, TransactionRequest, U256, U64, spoof,
+        transaction::eip2718::TypedTransaction, CallConfig,
+    },
+    utils::{Anvil, AnvilInstance, id},
+};
+use serde::Serialize;
+use serde_json::json;
+use tokio::sync::Mutex;
+use tracing::debug;
+
+use crate::const_and_addr;
+use super::Bundle;
+
+/// `Error(string)`
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+/// `Panic(uint256)`
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum SimulationBackend {
+    EthCall,
+    Fork,
+}
+
+impl SimulationBackend {
+    /// Config value naming the backend
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SimulationBackend::EthCall => "eth_call",
+            SimulationBackend::Fork => "fork",
+        }
+    }
+}
+
+impl FromStr for SimulationBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "eth_call" | "trace_call" => Ok(SimulationBackend::EthCall),
+            "fork" | "anvil" => Ok(SimulationBackend::Fork),
+            other => Err(anyhow!("Unknown simulation backend '{}', expected 'eth_call' or 'fork'", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TransactionOutcome {
+    pub success: bool,
+    pub gas_used: u64,
+    pub revert_reason: Option<String>,
+    /// Logs of the calls that did not revert
+    pub logs: Vec<Log>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SimulationResult {
+    /// Block whose post-state the bundle ran on
+    pub block_number: u64,
+    /// One per transaction up to and including the first failure
+    pub outcomes: Vec<TransactionOutcome>,
+    /// Searcher balance changes per tracked token, the native balance under `Address::zero()`
+    pub balance_deltas: BTreeMap<Address, I256>,
+}
+
+impl SimulationResult {
+    pub fn success(&self) -> bool {
+        !self.outcomes.is_empty() && self.outcomes.iter().all(|outcome| outcome.success)
+    }
+
+    pub fn gas_used(&self) -> u64 {
+        self.outcomes.iter().map(|outcome| outcome.gas_used).sum()
+    }
+
+    /// Why the first failing transaction reverted
+    pub fn revert_reason(&self) -> Option<&str> {
+        self.outcomes.iter().find(|outcome| !outcome.success)?.revert_reason.as_deref()
+    }
+
+    pub fn logs(&self) -> impl Iterator<Item = &Log> {
+        self.outcomes.iter().flat_map(|outcome| &outcome.logs)
+    }
+
+    pub fn balance_delta(&self, token: Address) -> I256 {
+        self.balance_deltas.get(&token).copied().unwrap_or_default()
+    }
+}
+
+enum Backend {
+    EthCall,
+    /// Dropping the instance stops anvil
+    Fork { _anvil: AnvilInstance, fork_url: String },
+}
+
+pub struct Simulator {
+    /// The node for `EthCall`, the local anvil for `Fork`
+    provider: Arc<Provider<Http>>,
+    backend: Backend,
+    /// The fork is shared state, simulations on it run one at a time
+    fork_lock: Mutex<()>,
+}
+
+impl Simulator {
+    pub fn eth_call(provider: Arc<Provider<Http>>) -> Self {
+        Self { provider, backend: Backend::EthCall, fork_lock: Mutex::new(()) }
+    }
+
+    /// Spawns an anvil forking `fork_url`, which must serve historical state
+    pub fn fork(fork_url: impl Into<String>) -> Result<Self> {
+        let fork_url = fork_url.into();
+        Command::new("anvil").arg("--version").output().context("anvil is not installed")?;
+        let anvil = Anvil::new().fork(fork_url.clone()).arg("--silent").spawn();
+        let provider = Provider::<Http>::try_from(anvil.endpoint())?;
+        Ok(Self {
+            provider: Arc::new(provider),
+            backend: Backend::Fork { _anvil: anvil, fork_url },
+            fork_lock: Mutex::new(()),
+        })
+    }
+
+    pub fn backend(&self) -> SimulationBackend {
+        match self.backend {
+            Backend::EthCall => SimulationBackend::EthCall,
+            Backend::Fork { .. } => SimulationBackend::Fork,
+        }
+    }
+
+    /// Endpoint whose state the simulations read: the node, or the fork's upstream
+    pub fn endpoint(&self) -> &str {
+        match &self.backend {
+            Backend::EthCall => self.provider.url().as_str(),
+            Backend::Fork { fork_url, .. } => fork_url,
+        }
+    }
+
+    /// Runs `bundle` on the state after `block_number`. Stops at the first failing
+    /// transaction; a revert is a result, RPC failures are errors.
+    pub async fn simulate(&self, bundle: &Bundle, block_number: u64) -> Result<SimulationResult> {
+        let result = match &self.backend {
+            Backend::EthCall => self.simulate_eth_call(bundle, block_number).await?,
+            Backend::Fork { fork_url, .. } => {
+                let _fork = self.fork_lock.lock().await;
+                self.simulate_fork(bundle, block_number, fork_url).await?
+            }
+        };
+        debug!("🧪 Simulated {} transactions on block {}: success {}, {} gas",
+            result.outcomes.len(), block_number, result.success(), result.gas_used());
+        Ok(result)
+    }
+
+    async fn simulate_eth_call(&self, bundle: &Bundle, block_number: u64) -> Result<SimulationResult> {
+        let block = BlockId::from(block_number);
+        let mut overrides = spoof::state();
+        let native_before = if bundle.funding.is_zero() {
+            self.provider.get_balance(bundle.searcher, Some(block)).await?
+        } else {
+            overrides.account(bundle.searcher).balance(bundle.funding);
+            bundle.funding
+        };
+        let tokens_before = self.token_balances(bundle, block, &overrides).await?;
+
+        let mut outcomes = Vec::new();
+        for tx in &bundle.transactions {
+            let tx = with_sender(tx, bundle.searcher);
+            let frame: CallFrame = self.trace_call(&tx, block, &overrides, call_tracer()).await?;
+            let outcome = outcome_from_frame(&frame);
+            let success = outcome.success;
+            outcomes.push(outcome);
+            if !success {
+                break;
+            }
+            let diff: DiffMode = self.trace_call(&tx, block, &overrides, prestate_diff_tracer()).await?;
+            fold_state_diff(&mut overrides, &diff);
+        }
+
+        let tokens_after = self.token_balances(bundle, block, &overrides).await?;
+        let native_after = overrides_balance(&overrides, bundle.searcher).unwrap_or(native_before);
+        Ok(SimulationResult {
+            block_number,
+            outcomes,
+            balance_deltas: balance_deltas(bundle, native_before, native_after, &tokens_before, &tokens_after),
+        })
+    }
+
+    async fn simulate_fork(&self, bundle: &Bundle, block_number: u64, fork_url: &str) -> Result<SimulationResult> {
+        let provider = self.provider.as_ref();
+        let forking = json!({ "forking": { "jsonRpcUrl": fork_url, "blockNumber": block_number } });
+        provider.request::<_, serde_json::Value>("anvil_reset", [forking]).await.context("anvil_reset failed")?;
+        provider.request::<_, serde_json::Value>("anvil_impersonateAccount", [bundle.searcher]).await?;
+        if !bundle.funding.is_zero() {
+            provider.request::<_, serde_json::Value>("anvil_setBalance", (bundle.searcher, bundle.funding)).await?;
+        }
+
+        let latest = BlockId::from(BlockNumber::Latest);
+        let empty = spoof::state();
+        let native_before = provider.get_balance(bundle.searcher, None).await?;
+        let tokens_before = self.token_balances(bundle, latest, &empty).await?;
+
+        let mut outcomes = Vec::new();
+        for tx in &bundle.transactions {
+            let mut tx = with_sender(tx, bundle.searcher);
+            if tx.gas().is_none() {
+                tx.set_gas(const_and_addr::PRIORITY_GAS_LIMIT);
+            }
+            tx.set_gas_price(U256::zero());
+            provider.request::<_, serde_json::Value>("anvil_setNextBlockBaseFeePerGas", [U256::zero()]).await?;
+            let hash: H256 = provider.request("eth_sendTransaction", [&tx]).await.context("eth_sendTransaction failed")?;
+            let receipt = provider.get_transaction_receipt(hash).await?
+                .ok_or_else(|| anyhow!("Transaction {:?} was not mined", hash))?;
+
+            let success = receipt.status == Some(U64::one());
+            let revert_reason = match (success, receipt.block_number) {
+                (false, Some(mined)) => self.replay_revert(&tx, mined.as_u64().saturating_sub(1)).await,
+                _ => None,
+            };
+            outcomes.push(TransactionOutcome {
+                success,
+                gas_used: receipt.gas_used.unwrap_or_default().as_u64(),
+                revert_reason,
+                logs: receipt.logs,
+            });
+            if !success {
+                break;
+            }
+        }
+
+        let tokens_after = self.token_balances(bundle, latest, &empty).await?;
+        let native_after = provider.get_balance(bundle.searcher, None).await?;
+        provider.request::<_, serde_json::Value>("anvil_stopImpersonatingAccount", [bundle.searcher]).await?;
+        Ok(SimulationResult {
+            block_number,
+            outcomes,
+            balance_deltas: balance_deltas(bundle, native_before, native_after, &tokens_before, &tokens_after),
+        })
+    }
+
+    /// Revert reason of a mined failure, by calling it again on its parent block
+    async fn replay_revert(&self, tx: &TypedTransaction, parent: u64) -> Option<String> {
+        let error = self.provider.call(tx, Some(parent.into())).await.err()?;
+        let data = error.as_error_response()?.as_revert_data()?;
+        decode_revert_reason(&data).or_else(|| Some(error.to_string()))
+    }
+
+    async fn trace_call<T: serde::de::DeserializeOwned>(
+        &self,
+        tx: &TypedTransaction,
+        block: BlockId,
+        overrides: &spoof::State,
+        tracing_options: GethDebugTracingOptions,
+    ) -> Result<T> {
+        let options = GethDebugTracingCallOptions {
+            tracing_options,
+            state_overrides: Some(overrides.clone()),
+            block_overrides: None,
+        };
+        let trace: serde_json::Value = self.provider
+            .request("debug_traceCall", (tx, block, options))
+            .await
+            .context("debug_traceCall failed")?;
+        serde_json::from_value(trace).context("Unexpected debug_traceCall output")
+    }
+
+    /// `balanceOf(searcher)` of every tracked token
+    async fn token_balances(&self, bundle: &Bundle, block: BlockId, overrides: &spoof::State) -> Result<Vec<U256>> {
+        let mut balances = Vec::with_capacity(bundle.tracked_tokens.len());
+        for token in &bundle.tracked_tokens {
+            let mut data = id("balanceOf(address)").to_vec();
+            data.extend(abi::encode(&[Token::Address(bundle.searcher)]));
+            let tx: TypedTransaction = TransactionRequest::new().to(*token).data(Bytes::from(data)).into();
+            let raw = self.provider.call_raw(&tx).block(block).state(overrides).await
+                .with_context(|| format!("balanceOf on {:?} failed", token))?;
+            let balance = abi::decode(&[ParamType::Uint(256)], &raw)
+                .ok()
+                .and_then(|mut tokens| tokens.pop()?.into_uint())
+                .ok_or_else(|| anyhow!("Unexpected balanceOf output from {:?}", token))?;
+            balances.push(balance);
+        }
+        Ok(balances)
+    }
+}
+
+fn with_sender(tx: &TypedTransaction, sender: Address) -> TypedTransaction {
+    let mut tx = tx.clone();
+    tx.set_from(sender);
+    tx
+}
+
+fn call_tracer() -> GethDebugTracingOptions {
+    GethDebugTracingOptions {
+        tracer: Some(GethDebugTracerType::BuiltInTracer(GethDebugBuiltInTracerType::CallTracer)),
+        tracer_config: Some(GethDebugTracerConfig::BuiltInTracer(GethDebugBuiltInTracerConfig::CallTracer(
+            CallConfig { only_top_call: None, with_log: Some(true) },
+        ))),
+        ..Default::default()
+    }
+}
+
+fn prestate_diff_tracer() -> GethDebugTracingOptions {
+    GethDebugTracingOptions {
+        tracer: Some(GethDebugTracerType::BuiltInTracer(GethDebugBuiltInTracerType::PreStateTracer)),
+        tracer_config: Some(GethDebugTracerConfig::BuiltInTracer(GethDebugBuiltInTracerConfig::PreStateTracer(
+            PreStateConfig { diff_mode: Some(true) },
+        ))),
+        ..Default::default()
+    }
+}
+
+fn outcome_from_frame(frame: &CallFrame) -> TransactionOutcome {
+    let success = frame.error.is_none();
+    let revert_reason = (!success).then(|| {
+        frame.output.as_ref()
+            .and_then(|output| decode_revert_reason(output))
+            .or_else(|| frame.error.clone())
+            .unwrap_or_default()
+    });
+    let mut logs = Vec::new();
+    if success {
+        collect_logs(frame, &mut logs);
+    }
+    TransactionOutcome { success, gas_used: frame.gas_used.low_u64(), revert_reason, logs }
+}
+
+/// Logs of `frame` and its subcalls, leaving out subcalls that reverted
+fn collect_logs(frame: &CallFrame, logs: &mut Vec<Log>) {
+    for log in frame.logs.iter().flatten() {
+        logs.push(Log {
+            address: log.address.unwrap_or_default(),
+            topics: log.topics.clone().unwrap_or_default(),
+            data: log.data.clone().unwrap_or_default(),
+            ..Default::default()
+        });
+    }
+    for call in frame.calls.iter().flatten().filter(|call| call.error.is_none()) {
+        collect_logs(call, logs);
+    }
+}
+
+/// Applies the post-state of a prestate diff to the overrides the next call runs with.
+/// Slots the diff only lists in `pre` were cleared.
+fn fold_state_diff(overrides: &mut spoof::State, diff: &DiffMode) {
+    for (address, post) in &diff.post {
+        let account = overrides.account(*address);
+        if let Some(balance) = post.balance {
+            account.balance(balance);
+        }
+        if let Some(nonce) = post.nonce {
+            account.nonce(U64::from(nonce.low_u64()));
+        }
+        if let Some(code) = post.code.as_deref().and_then(|code| code.parse::<Bytes>().ok()) {
+            account.code(code);
+        }
+        for (slot, value) in post.storage.iter().flatten() {
+            account.store(*slot, *value);
+        }
+    }
+    for (address, pre) in &diff.pre {
+        let post_storage = diff.post.get(address).and_then(|post| post.storage.as_ref());
+        for slot in pre.storage.iter().flatten().map(|(slot, _)| slot) {
+            if !post_storage.is_some_and(|storage| storage.contains_key(slot)) {
+                overrides.account(*address).store(*slot, H256::zero());
+            }
+        }
+    }
+}
+
+fn overrides_balance(overrides: &spoof::State, address: Address) -> Option<U256> {
+    // `spoof::State` hides its map, its JSON form does not
+    let state = serde_json::to_value(overrides).ok()?;
+    let balance = state.get(format!("{:?}", address))?.get("balance")?.clone();
+    serde_json::from_value(balance).ok()
+}
+
+fn balance_deltas(bundle: &Bundle, native_before: U256, native_after: U256, before: &[U256], after: &[U256]) -> BTreeMap<Address, I256> {
+    let delta = |before: U256, after: U256| {
+        I256::from_raw(after).saturating_sub(I256::from_raw(before))
+    };
+    let mut deltas = BTreeMap::from([(Address::zero(), delta(native_before, native_after))]);
+    for ((token, before), after) in bundle.tracked_tokens.iter().zip(before).zip(after) {
+        deltas.insert(*token, delta(*before, *after));
+    }
+    deltas
+}
+
+/// Human readable form of revert data: the `Error(string)` message, the `Panic` code, or
+/// the raw hex of custom errors. `None` for empty data.
+pub fn decode_revert_reason(data: &[u8]) -> Option<String> {
+    if data.is_empty() {
+        return None;
+    }
+    if data.len() >= 4 && data[..4] == ERROR_SELECTOR
+        && let Ok(mut tokens) = abi::decode(&[ParamType::String], &data[4..])
+        && let Some(Token::String(message)) = tokens.pop()
+    {
+        return Some(message);
+    }
+    if data.len() >= 4 && data[..4] == PANIC_SELECTOR
+        && let Ok(mut tokens) = abi::decode(&[ParamType::Uint(256)], &data[4..])
+        && let Some(Token::Uint(code)) = tokens.pop()
+    {
+        return Some(format!("panic 0x{:x}", code));
+    }
+    Some(format!("0x{}", hex_string(data)))
+}
+
+fn hex_string(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decodes_revert_reasons_and_folds_state_diffs() {
+        let mut data = ERROR_SELECTOR.to_vec();
+        data.extend(abi::encode(&[Token::String("UniswapV2Router: INSUFFICIENT_OUTPUT_AMOUNT".into())]));
+        assert_eq!(decode_revert_reason(&data).as_deref(), Some("UniswapV2Router: INSUFFICIENT_OUTPUT_AMOUNT"));
+        let mut panic = PANIC_SELECTOR.to_vec();
+        panic.extend(abi::encode(&[Token::Uint(0x11.into())]));
+        assert_eq!(decode_revert_reason(&panic).as_deref(), Some("panic 0x11"));
+        assert_eq!(decode_revert_reason(&[0xde, 0xad, 0xbe, 0xef]).as_deref(), Some("0xdeadbeef"));
+        assert_eq!(decode_revert_reason(&[]), None);
+
+        // A transfer that raises one slot, clears another and spends ether
+        let (searcher, token) = (Address::repeat_byte(0x5e), Address::repeat_byte(0x70));
+        let diff: DiffMode = serde_json::from_value(json!({
+            "pre": {
+                format!("{:?}", searcher): { "balance": "0x64", "nonce": 1 },
+                format!("{:?}", token): { "storage": {
+                    format!("{:?}", H256::from_low_u64_be(1)): format!("{:?}", H256::from_low_u64_be(5)),
+                    format!("{:?}", H256::from_low_u64_be(2)): format!("{:?}", H256::from_low_u64_be(9)),
+                } },
+            },
+            "post": {
+                format!("{:?}", searcher): { "balance": "0x32", "nonce": 2 },
+                format!("{:?}", token): { "storage": {
+                    format!("{:?}", H256::from_low_u64_be(1)): format!("{:?}", H256::from_low_u64_be(14)),
+                } },
+            },
+        })).unwrap();
+        let mut overrides = spoof::state();
+        overrides.account(searcher).balance(100.into());
+        fold_state_diff(&mut overrides, &diff);
+        assert_eq!(overrides_balance(&overrides, searcher), Some(U256::from(50u64)));
+
+        let mut expected = spoof::state();
+        expected.account(searcher).balance(50.into()).nonce(2.into());
+        expected.account(token).store(H256::from_low_u64_be(1), H256::from_low_u64_be(14)).store(H256::from_low_u64_be(2), H256::zero());
+        assert_eq!(overrides, expected);
+    }
+
+    /// Wraps ether on a mainnet fork with both backends. Needs anvil and FORK_URL; the
+    /// eth_call backend runs against the fork's own debug namespace.
+    #[tokio::test]
+    #[ignore = "requires anvil and FORK_URL"]
+    async fn test_backends_agree_on_anvil_fork() -> anyhow::Result<()> {
+        use crate::chain::ChainProfile;
+
+        let fork_url = std::env::var("FORK_URL").map_err(|_| anyhow!("FORK_URL is not set"))?;
+        let weth = ChainProfile::ethereum().addresses.wrapped_native();
+        let fork = Simulator::fork(fork_url.clone())?;
+        let block = fork.provider.get_block_number().await?.as_u64();
+
+        let mut bundle = Bundle::new(Address::repeat_byte(0x5e));
+        bundle.funding = U256::exp10(18);
+        bundle.track(weth);
+        bundle.push(weth, id("deposit()").to_vec(), U256::exp10(17));
+        // Transfers more than was wrapped, so the bundle reverts there
+        bundle.push(weth, id("transfer(address,uint256)").into_iter()
+            .chain(abi::encode(&[Token::Address(Address::repeat_byte(1)), Token::Uint(U256::exp10(18))]))
+            .collect(), U256::zero());
+
+        let on_fork = fork.simulate(&bundle, block).await?;
+        let traced = Simulator::eth_call(fork.provider.clone()).simulate(&bundle, block).await?;
+        for result in [&on_fork, &traced] {
+            assert_eq!(result.outcomes.len(), 2);
+            assert!(result.outcomes[0].success && !result.success());
+            assert_eq!(result.balance_delta(weth), I256::exp10(17));
+            assert_eq!(result.balance_delta(Address::zero()), -I256::exp10(17));
+            assert_eq!(result.logs().count(), 1);
+        }
+        Ok(())
+    }
+}
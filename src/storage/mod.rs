@@ -1,6 +1,7 @@
 mod storage_drift;
 
 pub use storage_drift::{
-    StorageDriftDetector, SlotDriftEvent,  SlotKey, StorageDelta,
+    StorageDriftDetector, SlotDriftEvent,  SlotKey, StorageDelta, BlockAnalysis,
     StorageChangeType, SlotSemantic, CriticalLevel, SimpleStateCache,
+    StorageLayout, SlotInfo, MappingInfo, ContractType, ReserveProjection,
 };
\ No newline at end of file
@@ -20,10 +20,18 @@ use std::collections::{HashMap, HashSet, BTreeMap};
 use tokio::sync::RwLock;
 use ethers::types::{Address, Block, Log, H256, U256, TransactionReceipt};
 use anyhow::{Result, anyhow};
-use tracing::warn;
+use tracing::{debug, warn};
 use chrono::{DateTime, Utc};
 use serde::{Serialize, Deserialize};
 
+use crate::chain::{ChainProfile, EventSignatures};
+
+/// `UniswapV2Pair` storage slots of `reserve0` and `reserve1`
+const RESERVE0_SLOT: u64 = 8;
+const RESERVE1_SLOT: u64 = 9;
+/// Confidence of drift predicted from pending swaps, which may not all land next block
+const PENDING_DRIFT_CONFIDENCE: f64 = 0.9;
+
 
 // use crate::{
 //     types::{SlotKey, SlotState, SlotDriftEvent, StoragePattern, StorageDelta},
@@ -88,6 +96,33 @@ pub struct StorageDelta {
     pub contract: Address,
 }
 
+/// V2 reserves before and after the pending swaps through a pool, as `[reserve0, reserve1]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReserveProjection {
+    pub current: [U256; 2],
+    pub predicted: [U256; 2],
+    /// Pending transactions behind the prediction
+    pub transactions: usize,
+}
+
+/// Block the projected reserves start from, and the projections per pool
+type PendingReserves = (u64, HashMap<Address, ReserveProjection>);
+
+/// Everything the detector derived from one block
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockAnalysis {
+    pub block_number: u64,
+    pub deltas: Vec<StorageDelta>,
+    pub drift_events: Vec<SlotDriftEvent>,
+}
+
+impl BlockAnalysis {
+    /// A block nothing was derived from
+    pub fn empty(block_number: u64) -> Self {
+        Self { block_number, deltas: Vec::new(), drift_events: Vec::new() }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum StorageChangeType {
     DirectWrite,
@@ -274,39 +309,90 @@ pub struct StorageDriftDetector {
     cache: Arc<SimpleStateCache>,
     contract_layouts: Arc<RwLock<HashMap<Address, StorageLayout>>>,
     drift_history: Arc<RwLock<BTreeMap<u64, Vec<SlotDriftEvent>>>>,
+    /// Reserves the pending swaps leave after the next block, and the block they start from
+    pending_reserves: Arc<RwLock<Option<PendingReserves>>>,
     anomaly_threshold: f64,
+    /// Chain name stamped on every drift event
+    chain: String,
+    events: EventSignatures,
 }
 
 impl StorageDriftDetector {
     pub fn new ()-> Self {
+        Self::for_chain(&ChainProfile::ethereum())
+    }
+
+    /// Detector using the chain's name and event signatures
+    pub fn for_chain(profile: &ChainProfile) -> Self {
         Self{
             cache: Arc::new(SimpleStateCache::new()),
             contract_layouts: Arc::new(RwLock::new(HashMap::new())),
             drift_history: Arc::new(RwLock::new(BTreeMap::new())),
+            pending_reserves: Arc::new(RwLock::new(None)),
             anomaly_threshold: 0.7, // Default threshold for anomaly detection
+            chain: profile.name.clone(),
+            events: profile.events.clone(),
         }
     }
 
 
     /// Main entry point - analyze a block for a storage drifts
     pub async fn analyze_block(&self, block:&Block<H256>, receipts: Vec<TransactionReceipt>)-> Result<Vec<SlotDriftEvent>> {
+        Ok(self.analyze_block_with_deltas(block, receipts).await?.drift_events)
+    }
+
+    /// Like `analyze_block`, but also returns the storage deltas behind the drift events
+    pub async fn analyze_block_with_deltas(&self, block:&Block<H256>, receipts: Vec<TransactionReceipt>) -> Result<BlockAnalysis> {
         let block_number = block.number.ok_or_else (|| anyhow!("Block missing number"))?.as_u64();
 
-        println!("🔍 Analyzing block {} with {} transcations", block_number, receipts.len());
+        debug!("🔍 Analyzing block {} with {} transcations", block_number, receipts.len());
 
         // Step 1: Extract storage changes from transaction log
         let storage_deltas = self.extract_storage_changes(&receipts,block_number).await?;
 
+        let drift_events = self.apply_deltas(block_number, &storage_deltas).await?;
+        Ok(BlockAnalysis {
+            block_number,
+            deltas: storage_deltas,
+            drift_events,
+        })
+    }
+
+    /// Analyze pre-filtered logs of a single block (log subscription mode).
+    /// Unlike receipts, logs carry the emitting contract directly.
+    pub async fn analyze_logs(&self, block_number: u64, logs: &[Log]) -> Result<Vec<SlotDriftEvent>> {
+        Ok(self.analyze_logs_with_deltas(block_number, logs).await?.drift_events)
+    }
+
+    /// Like `analyze_logs`, but also returns the storage deltas behind the drift events
+    pub async fn analyze_logs_with_deltas(&self, block_number: u64, logs: &[Log]) -> Result<BlockAnalysis> {
+        debug!("🔍 Analyzing block {} with {} filtered logs", block_number, logs.len());
+
+        let mut storage_deltas = Vec::new();
+        for log in logs {
+            let layout = self.get_storage_layout(log.address).await;
+            storage_deltas.extend(self.analyze_log(log, &layout, block_number, log.address).await?);
+        }
+
+        let drift_events = self.apply_deltas(block_number, &storage_deltas).await?;
+        Ok(BlockAnalysis {
+            block_number,
+            deltas: storage_deltas,
+            drift_events,
+        })
+    }
+
+    async fn apply_deltas(&self, block_number: u64, storage_deltas: &[StorageDelta]) -> Result<Vec<SlotDriftEvent>> {
         // Step 2: Update our cache with new values
-        self.update_cache(&storage_deltas).await;
+        self.update_cache(storage_deltas).await;
 
         // Step 3: Detect drift patterns
-        let drift_events = self.detect_drift_events(&storage_deltas, block_number).await?;
+        let drift_events = self.detect_drift_events(storage_deltas, block_number).await?;
 
         // Step 4: Store results
         self.store_drift_events(block_number, &drift_events).await;
 
-        println!("✅ Found {} potential drift events", drift_events.len());
+        debug!("✅ Found {} potential drift events", drift_events.len());
 
         Ok(drift_events)
     }
@@ -504,18 +590,21 @@ impl StorageDriftDetector {
         let drift_score = self.calculate_drift_score(&changes, contract, slot_key.clone()).await;
 
         if drift_score > self.anomaly_threshold {
-            // Predict future value
-            let predicted_value = self.predict_future_value(contract, slot_key.clone()).await;
             let current_value = changes.last().unwrap().new_value;
+            // AMM reserves follow the pending swaps into the next block when they are known
+            let (predicted_value, predicted_block) = match self.projected_reserve(contract, &slot_key, block_number, current_value).await {
+                Some(predicted_value) => (predicted_value, block_number + 1),
+                None => (self.predict_future_value(contract, slot_key.clone()).await, block_number + 10), //Predict 10 blocks ahead
+            };
 
             drift_events.push(SlotDriftEvent {
-                chain: "ethereum".to_string(),
+                chain: self.chain.clone(),
                 contract,
                 slot_key,
                 current_value,
                 predicted_value,
                 current_block: block_number,
-                predicted_block: block_number + 10, //Predict 10 blocks ahead
+                predicted_block,
                 timestamp:  Utc::now(),
                 confidence: drift_score,
             });
@@ -569,6 +658,62 @@ impl StorageDriftDetector {
 
     } 
 
+    /// Stores the reserves pending swaps are predicted to leave after the block following
+    /// `block_number`. Reserve drift in `block_number` is then predicted from them.
+    pub async fn set_pending_reserves(&self, block_number: u64, reserves: HashMap<Address, ReserveProjection>) {
+        *self.pending_reserves.write().await = Some((block_number, reserves));
+    }
+
+    /// Predicted value of a reserve slot after the next block, `None` for other slots or
+    /// without a projection from `block_number`. Pools no pending swap touches keep
+    /// `current_value`.
+    async fn projected_reserve(&self, contract: Address, slot_key: &SlotKey, block_number: u64, current_value: H256) -> Option<H256> {
+        let index = match slot_key {
+            SlotKey::Reserves(RESERVE0_SLOT) => 0,
+            SlotKey::Reserves(RESERVE1_SLOT) => 1,
+            _ => return None,
+        };
+        let pending = self.pending_reserves.read().await;
+        let (_, reserves) = pending.as_ref().filter(|(base_block, _)| *base_block == block_number)?;
+        Some(reserves.get(&contract).map_or(current_value, |projection| self._u256_to_bytes32(projection.predicted[index])))
+    }
+
+    /// Drift events for the reserves pending swaps move in the next block, for slots the
+    /// block's own drift events do not cover already. Stored with the block's events.
+    pub async fn pending_drift_events(&self, block_number: u64) -> Vec<SlotDriftEvent> {
+        let covered: HashSet<(Address, SlotKey)> = self.drift_history.read().await
+            .get(&block_number)
+            .map(|events| events.iter().map(|event| (event.contract, event.slot_key.clone())).collect())
+            .unwrap_or_default();
+        let events: Vec<SlotDriftEvent> = {
+            let pending = self.pending_reserves.read().await;
+            let Some((_, reserves)) = pending.as_ref().filter(|(base_block, _)| *base_block == block_number) else {
+                return Vec::new();
+            };
+            reserves.iter()
+                .flat_map(|(contract, projection)| {
+                    [RESERVE0_SLOT, RESERVE1_SLOT].into_iter().enumerate().filter_map(move |(i, slot)| {
+                        (projection.current[i] != projection.predicted[i]).then_some((*contract, SlotKey::Reserves(slot), i, projection))
+                    })
+                })
+                .filter(|(contract, slot_key, _, _)| !covered.contains(&(*contract, slot_key.clone())))
+                .map(|(contract, slot_key, i, projection)| SlotDriftEvent {
+                    chain: self.chain.clone(),
+                    contract,
+                    slot_key,
+                    current_value: self._u256_to_bytes32(projection.current[i]),
+                    predicted_value: self._u256_to_bytes32(projection.predicted[i]),
+                    current_block: block_number,
+                    predicted_block: block_number + 1,
+                    timestamp: Utc::now(),
+                    confidence: PENDING_DRIFT_CONFIDENCE,
+                })
+                .collect()
+        };
+        self.store_drift_events(block_number, &events).await;
+        events
+    }
+
     /// Predict future value using simple trend analysis 
     async fn predict_future_value(&self, contract: Address, slot_key: SlotKey) -> H256 {
         let history = self.cache.get_slot_history(contract, slot_key).await;
@@ -606,7 +751,7 @@ impl StorageDriftDetector {
     /// Store drift events in history
     async fn store_drift_events(&self, block_number: u64, events: &[SlotDriftEvent]) {
         let mut history = self.drift_history.write().await;
-        history.insert(block_number, events.to_vec());
+        history.entry(block_number).or_default().extend_from_slice(events);
 
         // Keep only last 1000 blocks
         if history.len() > 1000 {
@@ -616,7 +761,7 @@ impl StorageDriftDetector {
     }
 
     /// Get storage layout for a contract (simmplied)
-    async fn get_storage_layout(&self, contract: Address) -> StorageLayout {
+    pub async fn get_storage_layout(&self, contract: Address) -> StorageLayout {
         let layouts = self.contract_layouts.read().await;
 
         if let Some(layout) = layouts.get(&contract) {
@@ -669,16 +814,14 @@ impl StorageDriftDetector {
 
     /// Classify event by signature
     fn classify_event(&self, signature: H256) -> EventType {
-        let sig_str = format!("{:x}", signature);
-
-        match sig_str.as_str() {
-            // ERC20 Transfer 
-            "ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef" => EventType::Transfer,
-            // Uniswap V2 swap
-            "d78ad95fa46c994b6551d0da85fc275fe613ce37657fb8d5e3d130840159d822" => EventType::Swap,
-            // Uniswap V2 Sync
-            "1c411e9a96e071241c2f21f7726b17ae89e3cab4c78be50e062b03a9fffbbad1" => EventType::Sync,
-            _ => EventType::Unknown,
+        if signature == self.events.transfer {
+            EventType::Transfer
+        } else if signature == self.events.swap {
+            EventType::Swap
+        } else if signature == self.events.sync {
+            EventType::Sync
+        } else {
+            EventType::Unknown
         }
     }
 